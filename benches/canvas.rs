@@ -0,0 +1,114 @@
+// Compares the flat Canvas storage against the nested Vec<Vec<Color>> layout it replaced.
+// Run with `cargo bench --bench canvas`.
+
+mod common;
+
+use raytracer::{Canvas, Color, Float};
+
+use common::time;
+use std::hint::black_box;
+use std::time::Duration;
+
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+const ITERATIONS: u32 = 10;
+
+struct NestedCanvas {
+    dimensions: (usize, usize),
+    pixels: Vec<Vec<Color>>,
+}
+
+impl NestedCanvas {
+    fn new(width: usize, height: usize) -> NestedCanvas {
+        NestedCanvas {
+            dimensions: (width, height),
+            pixels: vec![vec![Color::black(); width]; height],
+        }
+    }
+}
+
+fn gradient((x, y): (usize, usize)) -> Color {
+    Color::new(
+        x as Float / WIDTH as Float,
+        y as Float / HEIGHT as Float,
+        0.5,
+    )
+}
+
+fn report(name: &str, nested: Duration, flat: Duration) {
+    println!(
+        "{:<28} nested {:>10.3?}  flat {:>10.3?}  ({:.2}x)",
+        name,
+        nested,
+        flat,
+        nested.as_secs_f64() / flat.as_secs_f64()
+    );
+}
+
+fn main() {
+    let allocate_nested = time(ITERATIONS, || {
+        black_box(NestedCanvas::new(WIDTH, HEIGHT));
+    });
+    let allocate_flat = time(ITERATIONS, || {
+        black_box(Canvas::new(WIDTH, HEIGHT));
+    });
+    report("allocate", allocate_nested, allocate_flat);
+
+    let mut nested = NestedCanvas::new(WIDTH, HEIGHT);
+    let mut flat = Canvas::new(WIDTH, HEIGHT);
+
+    let fill_nested = time(ITERATIONS, || {
+        let (width, height) = nested.dimensions;
+        for y in 0..height {
+            for x in 0..width {
+                nested.pixels[y][x] = gradient((x, y));
+            }
+        }
+        black_box(&nested.pixels);
+    });
+    let fill_flat = time(ITERATIONS, || {
+        for (y, row) in flat.rows_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = gradient((x, y));
+            }
+        }
+        black_box(flat.as_slice());
+    });
+    report("fill by row", fill_nested, fill_flat);
+
+    let index_nested = time(ITERATIONS, || {
+        let (width, height) = nested.dimensions;
+        for y in 0..height {
+            for x in 0..width {
+                black_box(nested.pixels[y][x]);
+            }
+        }
+    });
+    let index_flat = time(ITERATIONS, || {
+        let (width, height) = flat.dimensions;
+        for y in 0..height {
+            for x in 0..width {
+                black_box(flat[(x, y)]);
+            }
+        }
+    });
+    report("index every pixel", index_nested, index_flat);
+
+    let sum_nested = time(ITERATIONS, || {
+        let mut sum = Color::black();
+        for row in &nested.pixels {
+            for pixel in row {
+                sum = sum + *pixel;
+            }
+        }
+        black_box(sum);
+    });
+    let sum_flat = time(ITERATIONS, || {
+        let mut sum = Color::black();
+        for pixel in flat.as_slice() {
+            sum = sum + *pixel;
+        }
+        black_box(sum);
+    });
+    report("sum as slice", sum_nested, sum_flat);
+}
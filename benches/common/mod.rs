@@ -0,0 +1,13 @@
+// Helpers shared by the benches, each of which pulls this in with `mod common;`
+
+use std::time::{Duration, Instant};
+
+// The average time of a run of f, after one untimed run to warm the caches up
+pub fn time<F: FnMut()>(iterations: u32, mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    start.elapsed() / iterations
+}
@@ -0,0 +1,127 @@
+// Compares the heap allocated Matrix against the fixed size Matrix4, both for the raw
+// operations and for transforming every camera ray of a small render into object space.
+// Run with `cargo bench --bench matrix`.
+
+mod common;
+
+use raytracer::{Canvas, Color, Float, Matrix, Matrix4, Point, Ray, Sphere};
+
+use common::time;
+use raytracer::consts::PI;
+use std::hint::black_box;
+use std::time::Duration;
+
+const SIZE: usize = 256;
+const OPERATIONS: usize = 100_000;
+const ITERATIONS: u32 = 5;
+
+fn report(name: &str, general: Duration, fixed: Duration) {
+    println!(
+        "{:<28} Matrix {:>10.3?}  Matrix4 {:>10.3?}  ({:.2}x)",
+        name,
+        general,
+        fixed,
+        general.as_secs_f64() / fixed.as_secs_f64()
+    );
+}
+
+// The ray from the camera through the center of a pixel on the wall behind the sphere
+fn camera_ray((x, y): (usize, usize)) -> Ray {
+    let origin = Point::new(0.0, 0.0, -5.0);
+    let (wall_z, wall_size) = (10.0, 7.0);
+    let pixel_size = wall_size / SIZE as Float;
+    let target = Point::new(
+        -wall_size / 2.0 + pixel_size * (x as Float + 0.5),
+        wall_size / 2.0 - pixel_size * (y as Float + 0.5),
+        wall_z,
+    );
+    Ray::new(origin, (target - origin).normalize())
+}
+
+fn render<F: Fn(&Ray) -> Ray>(to_object: F) -> Canvas {
+    let sphere = Sphere::new();
+    let mut canvas = Canvas::new(SIZE, SIZE);
+    for xy in canvas.iter() {
+        let ray = to_object(&camera_ray(xy));
+        if sphere.hit(&ray).is_some() {
+            canvas[xy] = Color::new(1.0, 0.0, 0.0);
+        }
+    }
+    canvas
+}
+
+fn main() {
+    let general = (Matrix::translation(0.5, 0.0, 0.0)
+        * Matrix::rotation_z(PI / 4.0)
+        * Matrix::scale(1.0, 0.5, 1.0))
+    .unwrap();
+    let fixed = Matrix4::translation(0.5, 0.0, 0.0)
+        * Matrix4::rotation_z(PI / 4.0)
+        * Matrix4::scale(1.0, 0.5, 1.0);
+
+    let multiply_general = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            black_box((&general * &general).unwrap());
+        }
+    });
+    let multiply_fixed = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            black_box(black_box(fixed) * black_box(fixed));
+        }
+    });
+    report("multiply", multiply_general, multiply_fixed);
+
+    let inverse_general = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            black_box(general.inverse().unwrap());
+        }
+    });
+    let inverse_fixed = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            black_box(black_box(fixed).inverse().unwrap());
+        }
+    });
+    report("inverse", inverse_general, inverse_fixed);
+
+    let point = Point::new(1.0, 2.0, 3.0);
+    let point_general = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            black_box((general.clone() * point).unwrap());
+        }
+    });
+    let point_fixed = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            black_box(black_box(fixed) * point);
+        }
+    });
+    report("transform point", point_general, point_fixed);
+
+    let general_inverse = general.inverse().unwrap();
+    let fixed_inverse = fixed.inverse().unwrap();
+    let general_to_object = |ray: &Ray| {
+        Ray::new(
+            (general_inverse.clone() * ray.origin).unwrap(),
+            (general_inverse.clone() * ray.direction).unwrap(),
+        )
+    };
+    let fixed_to_object =
+        |ray: &Ray| Ray::new(fixed_inverse * ray.origin, fixed_inverse * ray.direction);
+
+    // Both paths must render the same image for the comparison to mean anything
+    assert_eq!(
+        render(general_to_object).as_slice(),
+        render(fixed_to_object).as_slice()
+    );
+
+    let render_general = time(ITERATIONS, || {
+        black_box(render(general_to_object));
+    });
+    let render_fixed = time(ITERATIONS, || {
+        black_box(render(fixed_to_object));
+    });
+    report(
+        &format!("render {}x{}", SIZE, SIZE),
+        render_general,
+        render_fixed,
+    );
+}
@@ -0,0 +1,100 @@
+// Traces the camera rays of a render through a BVH of spheres one at a time and four at a time.
+// Run with `cargo bench --bench packet`, and again with `--features simd` to see the packets
+// with SSE underneath.
+
+mod common;
+
+use raytracer::{
+    simd, Bounds, Bvh, Float, Matrix4, Point, Ray, RayPacket, Sphere, Vector, PACKET_WIDTH,
+};
+
+use common::time;
+use std::hint::black_box;
+
+const SIZE: usize = 256;
+const SPHERES: i32 = 12;
+const ITERATIONS: u32 = 5;
+
+// A wall of spheres at different depths, returned as their inverse transforms and bounds
+fn scene() -> (Vec<Matrix4>, Vec<Bounds>) {
+    let (mut inverses, mut bounds) = (vec![], vec![]);
+    for y in 0..SPHERES {
+        for x in 0..SPHERES {
+            let center = Point::new(
+                (x - SPHERES / 2) as Float * 2.2,
+                (y - SPHERES / 2) as Float * 2.2,
+                ((x * 7 + y * 3) % 5) as Float,
+            );
+            let Point(cx, cy, cz) = center;
+            inverses.push(Matrix4::translation(cx, cy, cz).inverse().unwrap());
+            let radius = Vector::new(1.0, 1.0, 1.0);
+            bounds.push(Bounds::new(center - radius, center + radius));
+        }
+    }
+    (inverses, bounds)
+}
+
+// Rows of pixels, in the order packets take them
+fn camera_rays() -> Vec<Ray> {
+    let origin = Point::new(0.0, 0.0, -40.0);
+    let wall_size = SPHERES as Float * 2.2;
+    let pixel_size = wall_size / SIZE as Float;
+    let mut rays = Vec::with_capacity(SIZE * SIZE);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let target = Point::new(
+                -wall_size / 2.0 + pixel_size * (x as Float + 0.5),
+                wall_size / 2.0 - pixel_size * (y as Float + 0.5),
+                0.0,
+            );
+            rays.push(Ray::new(origin, (target - origin).normalize()));
+        }
+    }
+    rays
+}
+
+fn main() {
+    let sphere = Sphere::new();
+    let (inverses, bounds) = scene();
+    let bvh = Bvh::build(&bounds);
+    let rays = camera_rays();
+
+    let single = || {
+        rays.iter()
+            .map(|ray| bvh.intersect(ray, |i, ray| sphere.hit(&ray.transform(&inverses[i]))))
+            .collect::<Vec<_>>()
+    };
+    let packets = || {
+        let mut hits = Vec::with_capacity(rays.len());
+        for chunk in rays.chunks(PACKET_WIDTH) {
+            let packet = RayPacket::new(chunk);
+            let closest = bvh.intersect_packet(&packet, |i, packet| {
+                sphere.intersect_packet(&packet.transform(&inverses[i]))
+            });
+            hits.extend_from_slice(&closest[..chunk.len()]);
+        }
+        hits
+    };
+
+    // Packets are only worth it if they find exactly what single rays do
+    assert_eq!(single(), packets());
+
+    let single_time = time(ITERATIONS, || {
+        black_box(single());
+    });
+    let packet_time = time(ITERATIONS, || {
+        black_box(packets());
+    });
+    println!(
+        "{} rays, {} spheres, {} backend",
+        rays.len(),
+        bounds.len(),
+        simd::backend()
+    );
+    println!(
+        "single {:>10.3?}  packets {:>10.3?}  ({:.2}x)",
+        single_time,
+        packet_time,
+        single_time.as_secs_f64() / packet_time.as_secs_f64()
+    );
+}
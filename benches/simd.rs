@@ -0,0 +1,112 @@
+// Compares the scalar lane math against whichever backend the build selected. Run with
+// `cargo bench --bench simd --features simd` to time SSE, and with `--features simd,f64` for
+// SSE2, without the feature both columns are the scalar code. The single operations have to be
+// packed into registers every time, which is why only Matrix4 and ray packets use them.
+
+mod common;
+
+use raytracer::simd::{self, scalar, Lanes, Rows};
+use raytracer::{Color, Float, Matrix4, Point, Vector};
+
+use common::time;
+use std::hint::black_box;
+use std::time::Duration;
+
+const OPERATIONS: usize = 1_000_000;
+const ITERATIONS: u32 = 5;
+
+fn report(name: &str, scalar: Duration, selected: Duration) {
+    println!(
+        "{:<20} scalar {:>10.3?}  {} {:>10.3?}  ({:.2}x)",
+        name,
+        scalar,
+        simd::backend(),
+        selected,
+        scalar.as_secs_f64() / selected.as_secs_f64()
+    );
+}
+
+fn compare<T, S, F>(name: &str, scalar: S, selected: F)
+where
+    T: PartialEq + std::fmt::Debug,
+    S: Fn() -> T,
+    F: Fn() -> T,
+{
+    // A faster answer is only worth having if it's the same answer
+    assert_eq!(scalar(), selected(), "{} differs between backends", name);
+    let scalar_time = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            black_box(scalar());
+        }
+    });
+    let selected_time = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            black_box(selected());
+        }
+    });
+    report(name, scalar_time, selected_time);
+}
+
+fn main() {
+    let a: Lanes = [1.5, -2.25, 3.0, 0.0];
+    let b: Lanes = [0.1, 7.0, -0.3, 0.0];
+    let factor: Float = 0.7;
+    let m: Rows = *Matrix4::rotation_z(0.4).rows();
+    let n: Rows = *(Matrix4::translation(1.0, 2.0, 3.0) * Matrix4::scale(2.0, 0.5, 1.0)).rows();
+    let p: Lanes = [0.3, -1.7, 2.9, 1.0];
+
+    compare(
+        "add",
+        || scalar::add(black_box(a), black_box(b)),
+        || simd::add(black_box(a), black_box(b)),
+    );
+    compare(
+        "mul",
+        || scalar::mul(black_box(a), black_box(b)),
+        || simd::mul(black_box(a), black_box(b)),
+    );
+    compare(
+        "scale",
+        || scalar::scale(black_box(a), black_box(factor)),
+        || simd::scale(black_box(a), black_box(factor)),
+    );
+    compare(
+        "dot",
+        || scalar::dot3(black_box(a), black_box(b)),
+        || simd::dot3(black_box(a), black_box(b)),
+    );
+    compare(
+        "cross",
+        || scalar::cross(black_box(a), black_box(b)),
+        || simd::cross(black_box(a), black_box(b)),
+    );
+    compare(
+        "matrix multiply",
+        || scalar::matrix_mul(black_box(&m), black_box(&n)),
+        || simd::matrix_mul(black_box(&m), black_box(&n)),
+    );
+    compare(
+        "matrix transform",
+        || scalar::matrix_transform(black_box(&m), black_box(p)),
+        || simd::matrix_transform(black_box(&m), black_box(p)),
+    );
+
+    // And through the public types, where only the Matrix4 uses the selected backend
+    let v = Vector::new(1.5, -2.25, 3.0);
+    let c = Color::new(0.2, 0.4, 0.6);
+    let transform = Matrix4::new(m) * Matrix4::new(n);
+    let types = time(ITERATIONS, || {
+        for _ in 0..OPERATIONS {
+            let v = black_box(v);
+            black_box(v.cross(&v.normalize()).dot(&v));
+            black_box(black_box(c) * black_box(c) + black_box(c) * 0.5);
+            black_box(black_box(transform) * Point::new(1.0, 2.0, 3.0));
+        }
+    });
+    println!(
+        "{:<20} {} {:>10.3?}",
+        "vector, color, point",
+        simd::backend(),
+        types
+    );
+}
@@ -1,36 +1,110 @@
+mod alpha;
+mod diff;
+mod dither;
+mod draw;
+mod exr;
+mod font;
+mod png;
+mod stats;
+mod transformations;
+
+pub use diff::*;
+pub use dither::*;
+pub use exr::*;
+pub use stats::*;
+pub use transformations::*;
+
 use super::*;
 use iterator::*;
 use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
 use std::ops::{Index, IndexMut};
 use std::path::Path;
+use std::slice::{ChunksExact, ChunksExactMut};
 
 const MAX_PPM_LINE_LENGTH: usize = 70;
 
+// Pixels are stored row by row in a single allocation, so (x, y) lives at y * width + x
+#[derive(Debug, Clone)]
 pub struct Canvas {
     pub dimensions: (usize, usize),
-    pixels: Vec<Vec<Color>>,
+    pixels: Vec<Color>,
+    // Coverage of each pixel, laid out like pixels, or None when the canvas is opaque
+    alpha: Option<Vec<Float>>,
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Canvas {
-        let row = vec![Color::black(); width];
-        let mut v2 = Vec::with_capacity(height);
-        for _ in 0..height {
-            v2.push(row.clone())
-        }
-
         Canvas {
             dimensions: (width, height),
-            pixels: v2,
+            pixels: vec![Color::black(); width * height],
+            alpha: None,
         }
     }
 
+    pub fn as_slice(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [Color] {
+        &mut self.pixels
+    }
+
+    pub fn row(&self, y: usize) -> &[Color] {
+        let width = self.dimensions.0;
+        &self.pixels[y * width..(y + 1) * width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [Color] {
+        let width = self.dimensions.0;
+        &mut self.pixels[y * width..(y + 1) * width]
+    }
+
+    pub fn rows(&self) -> ChunksExact<'_, Color> {
+        self.chunks(1)
+    }
+
+    pub fn rows_mut(&mut self) -> ChunksExactMut<'_, Color> {
+        self.chunks_mut(1)
+    }
+
+    // Bands of whole rows, e.g. to hand each band to a different thread. The last band is
+    // left out of the iterator when the height isn't a multiple of rows_per_chunk, and can be
+    // reached through remainder(). Like slice::chunks, rows_per_chunk can't be 0. A canvas with
+    // no width has no pixels to put in a band, so it has no bands at all.
+    pub fn chunks(&self, rows_per_chunk: usize) -> ChunksExact<'_, Color> {
+        self.pixels.chunks_exact(self.chunk_size(rows_per_chunk))
+    }
+
+    pub fn chunks_mut(&mut self, rows_per_chunk: usize) -> ChunksExactMut<'_, Color> {
+        let chunk_size = self.chunk_size(rows_per_chunk);
+        self.pixels.chunks_exact_mut(chunk_size)
+    }
+
+    fn chunk_size(&self, rows_per_chunk: usize) -> usize {
+        assert!(rows_per_chunk > 0, "rows_per_chunk must be more than 0");
+        // Only 0 when there are no pixels, where any size gives no chunks
+        self.dimensions.0.saturating_mul(rows_per_chunk).max(1)
+    }
+
+    #[inline]
+    fn offset(&self, (x, y): (usize, usize)) -> usize {
+        let width = self.dimensions.0;
+        // Rows past the end are caught by the slice, columns past the end would wrap around
+        assert!(x < width, "x: {} is outside the canvas width {}", x, width);
+        y * width + x
+    }
+
+    // Paints an opaque pixel, so on a canvas with an alpha plane the pixel becomes fully covered
     pub fn set(&mut self, (x, y): (usize, usize), color: Color) {
         let (width, height) = self.dimensions;
         // Ignore any pixels outside the canvas
         if x < width && y < height {
-            self[(x, y)] = color
+            let offset = self.offset((x, y));
+            self.pixels[offset] = color;
+            if let Some(alpha) = &mut self.alpha {
+                alpha[offset] = 1.0;
+            }
         }
     }
 
@@ -39,15 +113,31 @@ impl Canvas {
     }
 
     pub fn write_ppm<W: Write>(&self, write: &mut W) -> Result<(), std::io::Error> {
+        self.write_ppm_encoded(write, &Encoding::default())
+    }
+
+    pub fn write_ppm_encoded<W: Write>(
+        &self,
+        write: &mut W,
+        encoding: &Encoding,
+    ) -> Result<(), std::io::Error> {
         write.write_all(self.ppm_header().as_bytes())?;
-        self.write_ppm_pixels(write)?;
+        self.write_ppm_pixels(write, encoding)?;
         Ok(())
     }
 
     pub fn save_as_ppm(&self, path: &Path) -> Result<(), std::io::Error> {
+        self.save_as_ppm_encoded(path, &Encoding::default())
+    }
+
+    pub fn save_as_ppm_encoded(
+        &self,
+        path: &Path,
+        encoding: &Encoding,
+    ) -> Result<(), std::io::Error> {
         let f = File::create(path).expect("Unable to open file");
         let mut f = BufWriter::new(f);
-        self.write_ppm(&mut f)
+        self.write_ppm_encoded(&mut f, encoding)
     }
 
     pub fn ppm_header(&self) -> String {
@@ -60,13 +150,18 @@ impl Canvas {
         )
     }
 
-    pub fn write_ppm_pixels<W: Write>(&self, write: &mut W) -> Result<(), std::io::Error> {
-        for row in &self.pixels {
+    pub fn write_ppm_pixels<W: Write>(
+        &self,
+        write: &mut W,
+        encoding: &Encoding,
+    ) -> Result<(), std::io::Error> {
+        let (width, _) = self.dimensions;
+        let pixels = self.quantize(encoding);
+        for row in pixels.chunks_exact(width.max(1)) {
             let mut current_line = String::new();
 
-            for color in row {
-                let parts = color.ppm_parts();
-                for part in parts {
+            for pixel in row {
+                for part in pixel.iter().map(|value| value.to_string()) {
                     let part_with_space = if !current_line.is_empty() {
                         format!(" {}", part)
                     } else {
@@ -91,16 +186,123 @@ impl Canvas {
     }
 }
 
+impl Canvas {
+    // Reads plain (P3) and binary (P6) PPM images, scaling each value by the maximum in the
+    // header
+    pub fn read_ppm<R: Read>(read: &mut R) -> Result<Canvas, std::io::Error> {
+        let mut bytes = Vec::new();
+        read.read_to_end(&mut bytes)?;
+
+        let mut at = 0;
+        let magic = ppm_token(&bytes, &mut at)?;
+        let width = ppm_number(&bytes, &mut at)?;
+        let height = ppm_number(&bytes, &mut at)?;
+        let max = ppm_number(&bytes, &mut at)? as Float;
+        if max == 0.0 || max > 65535.0 {
+            return Err(invalid_data(format!("unsupported maximum value {}", max)));
+        }
+
+        // The sizes come from the file, so they are checked against the bytes actually there
+        // before anything is allocated for them
+        let count = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(3))
+            .ok_or_else(|| invalid_data(format!("PPM size {}x{} is too large", width, height)))?;
+        let truncated = || invalid_data(String::from("PPM pixel data is truncated"));
+        let mut values = Vec::new();
+        match magic.as_str() {
+            "P3" => {
+                // Every value takes at least a digit and the whitespace before it
+                if count > bytes.len().saturating_sub(at) / 2 {
+                    return Err(truncated());
+                }
+                values.reserve(count);
+                for _ in 0..count {
+                    values.push(ppm_number(&bytes, &mut at)? as Float / max);
+                }
+            }
+            "P6" => {
+                // A single whitespace byte separates the header from the pixels
+                let start = at + 1;
+                let size = if max < 256.0 { 1 } else { 2 };
+                let end = count
+                    .checked_mul(size)
+                    .and_then(|length| length.checked_add(start))
+                    .filter(|end| *end <= bytes.len())
+                    .ok_or_else(truncated)?;
+                values.reserve(count);
+                for value in bytes[start..end].chunks_exact(size) {
+                    let value = value.iter().fold(0, |v, byte| (v << 8) | *byte as u32);
+                    values.push(value as Float / max);
+                }
+            }
+            _ => return Err(invalid_data(format!("unsupported PPM format {}", magic))),
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        for (pixel, rgb) in canvas.as_mut_slice().iter_mut().zip(values.chunks_exact(3)) {
+            *pixel = Color::new(rgb[0], rgb[1], rgb[2]);
+        }
+        Ok(canvas)
+    }
+
+    pub fn load_ppm(path: &Path) -> Result<Canvas, std::io::Error> {
+        let f = File::open(path)?;
+        Self::read_ppm(&mut BufReader::new(f))
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+// The next whitespace separated token, skipping # comments
+fn ppm_token(bytes: &[u8], at: &mut usize) -> Result<String, std::io::Error> {
+    loop {
+        while *at < bytes.len() && bytes[*at].is_ascii_whitespace() {
+            *at += 1;
+        }
+        if *at < bytes.len() && bytes[*at] == b'#' {
+            while *at < bytes.len() && bytes[*at] != b'\n' {
+                *at += 1;
+            }
+        } else {
+            break;
+        }
+    }
+
+    let start = *at;
+    while *at < bytes.len() && !bytes[*at].is_ascii_whitespace() {
+        *at += 1;
+    }
+    if start == *at {
+        return Err(invalid_data(String::from("PPM ended unexpectedly")));
+    }
+    Ok(String::from_utf8_lossy(&bytes[start..*at]).into_owned())
+}
+
+fn ppm_number(bytes: &[u8], at: &mut usize) -> Result<usize, std::io::Error> {
+    let token = ppm_token(bytes, at)?;
+    token
+        .parse()
+        .map_err(|_| invalid_data(format!("expected a number but found {:?}", token)))
+}
+
 impl Index<(usize, usize)> for Canvas {
     type Output = Color;
-    fn index(&self, (x, y): (usize, usize)) -> &Color {
-        &self.pixels[y][x]
+    #[inline]
+    fn index(&self, xy: (usize, usize)) -> &Color {
+        &self.pixels[self.offset(xy)]
     }
 }
 
+// Writes through an index only change the (premultiplied) color and leave the coverage alone, so
+// drawing onto a transparent canvas has to go through set or set_rgba to be visible
 impl IndexMut<(usize, usize)> for Canvas {
-    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Color {
-        &mut self.pixels[y][x]
+    #[inline]
+    fn index_mut(&mut self, xy: (usize, usize)) -> &mut Color {
+        let offset = self.offset(xy);
+        &mut self.pixels[offset]
     }
 }
 
@@ -188,6 +390,134 @@ mod test {
         return_vec
     }
 
+    #[test]
+    fn save_ppm_with_srgb_encoding() {
+        let mut c = Canvas::new(2, 1);
+        c[(0, 0)] = Color::new(0.5, 0.2, 1.0);
+        c[(1, 0)] = Color::new(4.0, 0.0, 0.0);
+
+        let encoding = Encoding {
+            tone_map: ToneMap::Reinhard,
+            ..Encoding::srgb()
+        };
+        let mut v = Vec::new();
+        assert!(c.write_ppm_encoded(&mut v, &encoding).is_ok());
+        let s = String::from_utf8(v).unwrap();
+        let lines: Vec<&str> = s.lines().collect();
+        assert_eq!(lines[3], "156 113 188 231 0 0");
+    }
+
+    #[test]
+    fn pixels_are_stored_row_major() {
+        let mut c = Canvas::new(3, 2);
+        c[(1, 0)] = Color::white();
+        c[(2, 1)] = Color::new(0.5, 0.5, 0.5);
+
+        let slice = c.as_slice();
+        assert_eq!(6, slice.len());
+        assert_eq!(Color::white(), slice[1]);
+        assert_eq!(Color::new(0.5, 0.5, 0.5), slice[5]);
+
+        c.as_mut_slice()[3] = Color::white();
+        assert_eq!(Color::white(), c[(0, 1)]);
+    }
+
+    #[test]
+    fn iterating_rows() {
+        let mut c = Canvas::new(2, 3);
+        for (y, row) in c.rows_mut().enumerate() {
+            for pixel in row.iter_mut() {
+                *pixel = Color::new(y as Float, 0.0, 0.0);
+            }
+        }
+
+        assert_eq!(3, c.rows().len());
+        for (y, row) in c.rows().enumerate() {
+            assert_eq!(row, c.row(y));
+            assert_eq!(&[Color::new(y as Float, 0.0, 0.0); 2], row);
+        }
+
+        c.row_mut(1)[0] = Color::white();
+        assert_eq!(Color::white(), c[(0, 1)]);
+    }
+
+    #[test]
+    fn chunks_of_rows() {
+        let mut c = Canvas::new(4, 5);
+        let mut chunks = c.chunks_mut(2);
+        assert_eq!(2, chunks.len());
+        for chunk in &mut chunks {
+            assert_eq!(8, chunk.len());
+        }
+        assert_eq!(4, chunks.into_remainder().len());
+        assert_eq!(4, c.chunks(2).remainder().len());
+
+        // Bigger than the whole canvas leaves it all in the remainder
+        assert_eq!(0, c.chunks(usize::MAX).len());
+        assert_eq!(0, Canvas::new(0, 3).rows().len());
+    }
+
+    #[test]
+    #[should_panic(expected = "rows_per_chunk must be more than 0")]
+    fn chunks_of_no_rows() {
+        let _ = Canvas::new(4, 5).chunks(0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn indexing_past_the_row() {
+        let c = Canvas::new(3, 3);
+        let _ = c[(3, 0)];
+    }
+
+    #[test]
+    fn reading_ppm() {
+        let mut c = Canvas::new(5, 3);
+        c[(0, 0)] = Color::new(1.5, 0.0, 0.0);
+        c[(2, 1)] = Color::new(0.0, 0.5, 0.0);
+        c[(4, 2)] = Color::new(-0.5, 0.0, 1.0);
+
+        let mut v = Vec::new();
+        assert!(c.write_ppm(&mut v).is_ok());
+        let read = Canvas::read_ppm(&mut &v[..]).unwrap();
+        assert_eq!((5, 3), read.dimensions);
+        assert_eq!(Color::new(255.0 / 256.0, 0.0, 0.0), read[(0, 0)]);
+        assert_eq!(Color::new(0.0, 0.5, 0.0), read[(2, 1)]);
+        assert_eq!(Color::black(), read[(1, 1)]);
+    }
+
+    #[test]
+    fn reading_ppm_with_comments_and_binary() {
+        let plain = b"P3\n# a comment\n2 1 255\n255 0 51 # another\n0 255 0\n";
+        let c = Canvas::read_ppm(&mut &plain[..]).unwrap();
+        assert_eq!(Color::new(1.0, 0.0, 0.2), c[(0, 0)]);
+        assert_eq!(Color::new(0.0, 1.0, 0.0), c[(1, 0)]);
+
+        let mut binary = b"P6 2 1 255\n".to_vec();
+        binary.extend_from_slice(&[255, 0, 51, 0, 255, 0]);
+        let c = Canvas::read_ppm(&mut &binary[..]).unwrap();
+        assert_eq!(Color::new(1.0, 0.0, 0.2), c[(0, 0)]);
+        assert_eq!(Color::new(0.0, 1.0, 0.0), c[(1, 0)]);
+    }
+
+    #[test]
+    fn reading_invalid_ppm() {
+        assert!(Canvas::read_ppm(&mut &b"P5 1 1 255 0"[..]).is_err());
+        assert!(Canvas::read_ppm(&mut &b"P3 2 1 255 0 0 0"[..]).is_err());
+        assert!(Canvas::read_ppm(&mut &b"P6 2 1 255\n000"[..]).is_err());
+
+        // Sizes that overflow, or that the data can't back, are rejected before allocating
+        let huge: &[&[u8]] = &[
+            b"P6 18446744073709551615 2 255\n000000",
+            b"P6 6148914691236517205 1 65535\n000000",
+            b"P3 100000 100000 255 0 0 0",
+        ];
+        for header in huge.iter() {
+            let error = Canvas::read_ppm(&mut &header[..]).unwrap_err();
+            assert_eq!(ErrorKind::InvalidData, error.kind());
+        }
+    }
+
     #[test]
     fn pixels_outside_canvas() {
         let mut c = Canvas::new(2, 2);
@@ -0,0 +1,169 @@
+use super::*;
+
+// Canvases start out opaque and only allocate an alpha plane once a pixel needs one. While
+// there is one, the colors in the canvas are premultiplied by it.
+impl Canvas {
+    // A canvas with nothing drawn on it yet, e.g. for renders where rays that miss everything
+    // should leave the background see through
+    pub fn transparent(width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        canvas.alpha = Some(vec![0.0; width * height]);
+        canvas
+    }
+
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.is_some()
+    }
+
+    pub fn alpha_slice(&self) -> Option<&[Float]> {
+        self.alpha.as_deref()
+    }
+
+    pub fn alpha(&self, xy: (usize, usize)) -> Float {
+        match &self.alpha {
+            Some(alpha) => alpha[self.offset(xy)],
+            None => 1.0,
+        }
+    }
+
+    // Changes the coverage of a pixel without touching its (premultiplied) color
+    pub fn set_alpha(&mut self, (x, y): (usize, usize), alpha: Float) {
+        let (width, height) = self.dimensions;
+        if x >= width || y >= height || (self.alpha.is_none() && alpha == 1.0) {
+            return;
+        }
+        let offset = self.offset((x, y));
+        self.alpha.get_or_insert_with(|| vec![1.0; width * height])[offset] = alpha;
+    }
+
+    pub fn rgba(&self, xy: (usize, usize)) -> Rgba {
+        Rgba::from_premultiplied(self[xy], self.alpha(xy))
+    }
+
+    pub fn set_rgba(&mut self, xy: (usize, usize), rgba: Rgba) {
+        self.set(xy, rgba.premultiplied());
+        self.set_alpha(xy, rgba.alpha());
+    }
+
+    // Composites the canvas over a solid background, leaving an opaque canvas
+    pub fn flatten(&self, background: Color) -> Canvas {
+        let mut flattened = Canvas::new(self.dimensions.0, self.dimensions.1);
+        for xy in self.iter() {
+            flattened[xy] = self.rgba(xy).over(Rgba::opaque(background)).premultiplied();
+        }
+        flattened
+    }
+
+    // The straight colors with the alpha plane dropped, which is what formats that store
+    // unassociated alpha expect
+    pub(crate) fn unpremultiplied(&self) -> Canvas {
+        let mut straight = Canvas::new(self.dimensions.0, self.dimensions.1);
+        for xy in self.iter() {
+            straight[xy] = self.rgba(xy).unpremultiplied();
+        }
+        straight
+    }
+
+    // Runs a transformation that only moves or filters pixels over the alpha plane too, by
+    // treating the plane as a gray canvas
+    pub(crate) fn transform_alpha<F>(&self, transform: F) -> Option<Vec<Float>>
+    where
+        F: FnOnce(&Canvas) -> Canvas,
+    {
+        let alpha = self.alpha.as_ref()?;
+        let mut plane = Canvas::new(self.dimensions.0, self.dimensions.1);
+        for (pixel, a) in plane.as_mut_slice().iter_mut().zip(alpha) {
+            *pixel = Color::new(*a, *a, *a);
+        }
+        Some(
+            transform(&plane)
+                .as_slice()
+                .iter()
+                .map(|pixel| pixel.red())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn canvases_are_opaque_by_default() {
+        let mut c = Canvas::new(2, 2);
+        assert!(!c.has_alpha());
+        assert_feq!(1.0, c.alpha((1, 1)));
+
+        // Making a pixel opaque doesn't need an alpha plane
+        c.set_alpha((0, 0), 1.0);
+        assert!(!c.has_alpha());
+
+        c.set_alpha((0, 0), 0.5);
+        assert!(c.has_alpha());
+        assert_eq!(Some(&[0.5, 1.0, 1.0, 1.0][..]), c.alpha_slice());
+
+        c.set_alpha((5, 5), 0.5);
+        assert_eq!(4, c.alpha_slice().unwrap().len());
+    }
+
+    #[test]
+    fn transparent_canvas() {
+        let mut c = Canvas::transparent(2, 1);
+        assert_eq!(Rgba::transparent(), c.rgba((1, 0)));
+
+        let red = Rgba::new(Color::new(1.0, 0.0, 0.0), 0.5);
+        c.set_rgba((1, 0), red);
+        assert_eq!(Color::new(0.5, 0.0, 0.0), c[(1, 0)]);
+        assert_eq!(red, c.rgba((1, 0)));
+    }
+
+    #[test]
+    fn plain_writes_are_opaque() {
+        let mut c = Canvas::transparent(3, 1);
+        c.set((0, 0), Color::white());
+        assert_eq!(Rgba::opaque(Color::white()), c.rgba((0, 0)));
+
+        c.draw_line((1, 0), (1, 0), Color::white());
+        assert_eq!(1.0, c.alpha((1, 0)));
+
+        // Indexing only touches the color, the pixel stays uncovered
+        c[(2, 0)] = Color::white();
+        assert_eq!(0.0, c.alpha((2, 0)));
+    }
+
+    #[test]
+    fn antialiased_edges_are_partly_covered() {
+        let mut c = Canvas::transparent(6, 3);
+        c.draw_line_antialiased((0.0, 0.5), (5.0, 0.5), Color::white());
+        assert_eq!(Rgba::new(Color::white(), 0.5), c.rgba((2, 0)));
+        assert_eq!(Rgba::new(Color::white(), 0.5), c.rgba((2, 1)));
+        assert_eq!(Rgba::transparent(), c.rgba((2, 2)));
+    }
+
+    #[test]
+    fn flattening() {
+        let mut c = Canvas::transparent(2, 1);
+        c.set_rgba((0, 0), Rgba::new(Color::new(1.0, 0.0, 0.0), 0.5));
+        let flattened = c.flatten(Color::new(0.0, 0.0, 1.0));
+        assert!(!flattened.has_alpha());
+        assert_eq!(Color::new(0.5, 0.0, 0.5), flattened[(0, 0)]);
+        assert_eq!(Color::new(0.0, 0.0, 1.0), flattened[(1, 0)]);
+    }
+
+    #[test]
+    fn unpremultiplying() {
+        let mut c = Canvas::transparent(2, 1);
+        c.set_rgba((0, 0), Rgba::new(Color::new(0.2, 0.4, 0.6), 0.25));
+        let straight = c.unpremultiplied();
+        assert!(!straight.has_alpha());
+        assert_eq!(Color::new(0.2, 0.4, 0.6), straight[(0, 0)]);
+        assert_eq!(Color::black(), straight[(1, 0)]);
+    }
+
+    #[test]
+    fn flattening_no_height() {
+        let flattened = Canvas::transparent(3, 0).flatten(Color::white());
+        assert_eq!((3, 0), flattened.dimensions);
+    }
+}
@@ -0,0 +1,312 @@
+use super::*;
+
+const SSIM_RADIUS: isize = 3;
+const SSIM_C1: Float = 0.01 * 0.01;
+const SSIM_C2: Float = 0.03 * 0.03;
+
+// Errors are measured per channel on linear values, where 1.0 is the brightest displayable value
+#[derive(Debug)]
+pub struct CanvasDiff {
+    // The absolute difference of every channel of every pixel
+    pub difference: Canvas,
+    // The absolute difference of the coverage of every pixel, when either canvas has alpha
+    pub alpha_difference: Option<Vec<Float>>,
+    pub max_error: Float,
+    pub mean_error: Float,
+    pub rmse: Float,
+    // Peak signal to noise ratio in decibels, infinite for identical canvases
+    pub psnr: Float,
+    // Mean structural similarity of the luminance, 1.0 for identical canvases
+    pub ssim: Float,
+}
+
+impl Canvas {
+    pub fn diff(&self, other: &Canvas) -> Result<CanvasDiff, String> {
+        if self.dimensions != other.dimensions {
+            return Err(format!(
+                "canvases must be the same size: {}x{} and {}x{}",
+                self.dimensions.0, self.dimensions.1, other.dimensions.0, other.dimensions.1
+            ));
+        }
+
+        let (width, height) = self.dimensions;
+        let mut difference = Canvas::new(width, height);
+        let mut max_error: Float = 0.0;
+        let mut sum = 0.0;
+        let mut sum_of_squares = 0.0;
+        // Coverage counts as a fourth channel, since a missing pixel over an opaque black one
+        // has the same premultiplied color
+        let mut alpha_difference = if self.has_alpha() || other.has_alpha() {
+            Some(Vec::with_capacity(width * height))
+        } else {
+            None
+        };
+        for (xy, d) in self.iter().zip(difference.as_mut_slice().iter_mut()) {
+            let (a, b) = (self[xy], other[xy]);
+            // A NaN on either side is as wrong as a pixel can be, not a match
+            let error = |a: Float, b: Float| {
+                let error = (a - b).abs();
+                if error.is_nan() {
+                    Float::INFINITY
+                } else {
+                    error
+                }
+            };
+            let mut errors = vec![
+                error(a.red(), b.red()),
+                error(a.green(), b.green()),
+                error(a.blue(), b.blue()),
+            ];
+            *d = Color::new(errors[0], errors[1], errors[2]);
+            if let Some(alpha_difference) = &mut alpha_difference {
+                let alpha_error = error(self.alpha(xy), other.alpha(xy));
+                alpha_difference.push(alpha_error);
+                errors.push(alpha_error);
+            }
+            for &error in errors.iter() {
+                max_error = max_error.max(error);
+                sum += util::widen(error);
+                sum_of_squares += util::widen(error) * util::widen(error);
+            }
+        }
+
+        let channels = if alpha_difference.is_some() { 4 } else { 3 };
+        let samples = (width * height * channels).max(1) as f64;
+        let rmse = (sum_of_squares / samples).sqrt() as Float;
+        Ok(CanvasDiff {
+            difference,
+            alpha_difference,
+            max_error,
+            mean_error: (sum / samples) as Float,
+            rmse,
+            psnr: -20.0 * rmse.log10(),
+            ssim: ssim(self, other),
+        })
+    }
+}
+
+impl CanvasDiff {
+    // True when no channel of any pixel differs by more than the tolerance
+    pub fn within(&self, tolerance: Float) -> bool {
+        self.max_error <= tolerance
+    }
+
+    // The pixels whose largest channel error is biggest, worst first
+    pub fn worst_pixels(&self, count: usize) -> Vec<((usize, usize), Float)> {
+        let mut pixels: Vec<((usize, usize), Float)> = self
+            .difference
+            .iter()
+            .map(|xy| (xy, self.pixel_error(xy)))
+            .filter(|(_, error)| *error > 0.0)
+            .collect();
+        pixels.sort_by(|a, b| b.1.total_cmp(&a.1));
+        pixels.truncate(count);
+        pixels
+    }
+
+    // Pixels off by more than the tolerance are red, brighter the worse they are, and
+    // everything else is a dim gray version of the difference
+    pub fn visualize(&self, tolerance: Float) -> Canvas {
+        let (width, height) = self.difference.dimensions;
+        let mut visualized = Canvas::new(width, height);
+        for (xy, v) in self
+            .difference
+            .iter()
+            .zip(visualized.as_mut_slice().iter_mut())
+        {
+            let error = self.pixel_error(xy);
+            *v = if error > tolerance {
+                let intensity = (0.25 + 0.75 * (error / self.max_error)).min(1.0);
+                Color::new(intensity, 0.0, 0.0)
+            } else {
+                let gray = 0.2 * error / tolerance.max(Float::EPSILON);
+                Color::new(gray, gray, gray)
+            };
+        }
+        visualized
+    }
+
+    // The largest channel error of a pixel, coverage included
+    fn pixel_error(&self, xy: (usize, usize)) -> Float {
+        let difference = self.difference[xy];
+        let color_error = difference
+            .red()
+            .max(difference.green())
+            .max(difference.blue());
+        match &self.alpha_difference {
+            Some(alpha_difference) => color_error.max(alpha_difference[self.difference.offset(xy)]),
+            None => color_error,
+        }
+    }
+}
+
+// Structural similarity (Wang et al. 2004) over a 7x7 window around each pixel, with the
+// window clipped at the edges of the canvas
+fn ssim(a: &Canvas, b: &Canvas) -> Float {
+    let (width, height) = a.dimensions;
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+    let a: Vec<Float> = a.as_slice().iter().map(Color::luminance).collect();
+    let b: Vec<Float> = b.as_slice().iter().map(Color::luminance).collect();
+
+    let mut total: f64 = 0.0;
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) =
+                (0.0, 0.0, 0.0, 0.0, 0.0);
+            let mut count = 0.0;
+            for wy in (y - SSIM_RADIUS).max(0)..(y + SSIM_RADIUS + 1).min(height as isize) {
+                for wx in (x - SSIM_RADIUS).max(0)..(x + SSIM_RADIUS + 1).min(width as isize) {
+                    let i = wy as usize * width + wx as usize;
+                    sum_a += a[i];
+                    sum_b += b[i];
+                    sum_aa += a[i] * a[i];
+                    sum_bb += b[i] * b[i];
+                    sum_ab += a[i] * b[i];
+                    count += 1.0;
+                }
+            }
+
+            let (mean_a, mean_b) = (sum_a / count, sum_b / count);
+            let variance_a = sum_aa / count - mean_a * mean_a;
+            let variance_b = sum_bb / count - mean_b * mean_b;
+            let covariance = sum_ab / count - mean_a * mean_b;
+            let similarity = ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1)
+                    * (variance_a + variance_b + SSIM_C2));
+            total += util::widen(similarity);
+        }
+    }
+    (total / (width * height) as f64) as Float
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gradient_canvas() -> Canvas {
+        let mut c = Canvas::new(8, 8);
+        for (x, y) in c.iter() {
+            c[(x, y)] = Color::new(x as Float / 8.0, y as Float / 8.0, 0.5);
+        }
+        c
+    }
+
+    #[test]
+    fn identical_canvases() {
+        let c = gradient_canvas();
+        let diff = c.diff(&gradient_canvas()).unwrap();
+        assert_eq!(0.0, diff.max_error);
+        assert_eq!(0.0, diff.mean_error);
+        assert_eq!(0.0, diff.rmse);
+        assert!(diff.psnr.is_infinite());
+        assert_feq!(1.0, diff.ssim);
+        assert!(diff.within(0.0));
+        assert!(diff.worst_pixels(5).is_empty());
+    }
+
+    #[test]
+    fn single_pixel_difference() {
+        let a = Canvas::new(2, 2);
+        let mut b = Canvas::new(2, 2);
+        b[(1, 0)] = Color::new(0.5, 0.0, 0.25);
+
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(Color::new(0.5, 0.0, 0.25), diff.difference[(1, 0)]);
+        assert_feq!(0.5, diff.max_error);
+        assert_feq!(0.75 / 12.0, diff.mean_error);
+        assert_feq!(((0.3125 as Float) / 12.0).sqrt(), diff.rmse);
+        assert_feq!(-20.0 * diff.rmse.log10(), diff.psnr);
+        assert!(diff.within(0.5));
+        assert!(!diff.within(0.4));
+        assert_eq!(vec![((1, 0), 0.5)], diff.worst_pixels(5));
+    }
+
+    #[test]
+    fn worst_pixels_are_ordered() {
+        let a = Canvas::new(3, 1);
+        let mut b = Canvas::new(3, 1);
+        b[(0, 0)] = Color::new(0.1, 0.0, 0.0);
+        b[(1, 0)] = Color::new(0.0, 0.0, 0.3);
+        b[(2, 0)] = Color::new(0.0, 0.2, 0.0);
+        let worst = a.diff(&b).unwrap().worst_pixels(2);
+        assert_eq!((1, 0), worst[0].0);
+        assert_eq!((2, 0), worst[1].0);
+    }
+
+    #[test]
+    fn ssim_drops_with_structural_changes() {
+        let a = gradient_canvas();
+        let mut noisy = gradient_canvas();
+        for (x, y) in noisy.iter() {
+            if (x + y) % 2 == 0 {
+                noisy[(x, y)] = noisy[(x, y)] + Color::new(0.2, 0.2, 0.2);
+            }
+        }
+        let mut brighter = gradient_canvas();
+        for xy in brighter.iter() {
+            brighter[xy] = brighter[xy] + Color::new(0.01, 0.01, 0.01);
+        }
+
+        let noisy = a.diff(&noisy).unwrap().ssim;
+        let brighter = a.diff(&brighter).unwrap().ssim;
+        assert!(noisy < 0.9);
+        assert!(brighter > noisy);
+    }
+
+    #[test]
+    fn visualizing_mismatches() {
+        let a = Canvas::new(3, 1);
+        let mut b = Canvas::new(3, 1);
+        b[(0, 0)] = Color::new(0.01, 0.0, 0.0);
+        b[(2, 0)] = Color::new(0.0, 0.5, 0.0);
+
+        let visualized = a.diff(&b).unwrap().visualize(0.05);
+        assert_eq!(Color::new(0.04, 0.04, 0.04), visualized[(0, 0)]);
+        assert_eq!(Color::black(), visualized[(1, 0)]);
+        assert_eq!(Color::new(1.0, 0.0, 0.0), visualized[(2, 0)]);
+    }
+
+    #[test]
+    fn different_sizes() {
+        assert!(Canvas::new(2, 2).diff(&Canvas::new(2, 3)).is_err());
+    }
+
+    #[test]
+    fn no_height() {
+        let diff = Canvas::new(3, 0).diff(&Canvas::new(3, 0)).unwrap();
+        assert!(diff.worst_pixels(2).is_empty());
+    }
+
+    #[test]
+    fn nan_pixels_are_the_worst() {
+        let a = Canvas::new(3, 1);
+        let mut b = Canvas::new(3, 1);
+        b[(1, 0)] = Color::new(Float::NAN, 0.0, 0.0);
+        b[(2, 0)] = Color::new(0.0, 0.5, 0.0);
+        let diff = a.diff(&b).unwrap();
+        assert_eq!(Float::INFINITY, diff.max_error);
+        assert_eq!(Float::INFINITY, diff.mean_error);
+        assert!(!diff.within(1.0));
+        let worst: Vec<(usize, usize)> = diff.worst_pixels(3).iter().map(|(xy, _)| *xy).collect();
+        assert_eq!(vec![(1, 0), (2, 0)], worst);
+        assert_eq!(Color::new(1.0, 0.0, 0.0), diff.visualize(0.1)[(1, 0)]);
+    }
+
+    #[test]
+    fn missing_coverage_is_a_difference() {
+        let opaque = Canvas::new(2, 1);
+        let transparent = Canvas::transparent(2, 1);
+        let diff = opaque.diff(&transparent).unwrap();
+        assert_eq!(1.0, diff.max_error);
+        assert_eq!(0.25, diff.mean_error);
+        assert_eq!(Color::black(), diff.difference[(0, 0)]);
+        assert_eq!(2, diff.worst_pixels(3).len());
+
+        let mut painted = Canvas::transparent(2, 1);
+        painted.set((0, 0), Color::black());
+        painted.set((1, 0), Color::black());
+        assert!(opaque.diff(&painted).unwrap().within(0.0));
+    }
+}
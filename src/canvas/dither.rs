@@ -0,0 +1,252 @@
+use super::*;
+
+use std::sync::OnceLock;
+
+const BAYER_SIZE: usize = 8;
+const BLUE_NOISE_SIZE: usize = 32;
+const BLUE_NOISE_SIGMA: Float = 1.5;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Dither {
+    None,
+    // Ordered dithering with an 8x8 Bayer threshold matrix
+    Bayer,
+    // Error diffusion, pushing each pixel's rounding error onto its unvisited neighbors
+    FloydSteinberg,
+    // Ordered dithering with a tiled void-and-cluster threshold matrix
+    BlueNoise,
+}
+
+impl Canvas {
+    // Encodes and rounds every pixel to 8 bits, row by row
+    pub fn quantize(&self, encoding: &Encoding) -> Vec<[u8; 3]> {
+        let (width, height) = self.dimensions;
+        let mut values: Vec<[Float; 3]> = self
+            .as_slice()
+            .iter()
+            .map(|color| {
+                let c = encoding.encode(*color);
+                [c.red() * 255.0, c.green() * 255.0, c.blue() * 255.0]
+            })
+            .collect();
+
+        match encoding.dither {
+            Dither::None => {}
+            Dither::Bayer => {
+                for (i, value) in values.iter_mut().enumerate() {
+                    let threshold = bayer_threshold((i % width, i / width));
+                    offset(value, threshold);
+                }
+            }
+            Dither::BlueNoise => {
+                for (i, value) in values.iter_mut().enumerate() {
+                    let threshold = blue_noise_threshold((i % width, i / width));
+                    offset(value, threshold);
+                }
+            }
+            Dither::FloydSteinberg => {
+                for y in 0..height {
+                    for x in 0..width {
+                        let i = y * width + x;
+                        let old = values[i];
+                        let new = old.map(|v| v.round().clamp(0.0, 255.0));
+                        values[i] = new;
+
+                        let mut diffuse = |(dx, dy): (isize, isize), weight: Float| {
+                            let (nx, ny) = (x as isize + dx, y as isize + dy);
+                            if nx >= 0 && (nx as usize) < width && (ny as usize) < height {
+                                let neighbor = &mut values[ny as usize * width + nx as usize];
+                                for c in 0..3 {
+                                    neighbor[c] += (old[c] - new[c]) * weight;
+                                }
+                            }
+                        };
+                        diffuse((1, 0), 7.0 / 16.0);
+                        diffuse((-1, 1), 3.0 / 16.0);
+                        diffuse((0, 1), 5.0 / 16.0);
+                        diffuse((1, 1), 1.0 / 16.0);
+                    }
+                }
+            }
+        }
+
+        values
+            .iter()
+            .map(|value| value.map(|v| v.round().clamp(0.0, 255.0) as u8))
+            .collect()
+    }
+}
+
+// Thresholds are centered on zero and span one quantization step
+fn offset(value: &mut [Float; 3], threshold: Float) {
+    for v in value.iter_mut() {
+        *v += threshold - 0.5;
+    }
+}
+
+fn bayer_threshold((x, y): (usize, usize)) -> Float {
+    // Builds the index by interleaving the bits of x ^ y and y, least significant first
+    let (x, y) = (x % BAYER_SIZE, y % BAYER_SIZE);
+    let xor = x ^ y;
+    let mut index = 0;
+    for bit in 0..3 {
+        index = (index << 2) | (((xor >> bit) & 1) << 1) | ((y >> bit) & 1);
+    }
+    (index as Float + 0.5) / (BAYER_SIZE * BAYER_SIZE) as Float
+}
+
+fn blue_noise_threshold((x, y): (usize, usize)) -> Float {
+    static RANKS: OnceLock<Vec<usize>> = OnceLock::new();
+    let ranks = RANKS.get_or_init(|| void_and_cluster(BLUE_NOISE_SIZE));
+    let rank = ranks[(y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + (x % BLUE_NOISE_SIZE)];
+    (rank as Float + 0.5) / ranks.len() as Float
+}
+
+// Ulichney's void-and-cluster method: ranks every cell of a toroidal size x size tile so that
+// any threshold of the ranks yields evenly spread points without low frequency clumps.
+fn void_and_cluster(size: usize) -> Vec<usize> {
+    let count = size * size;
+    let mut kernel = vec![0.0; count];
+    for (i, k) in kernel.iter_mut().enumerate() {
+        let wrap = |d: usize| d.min(size - d) as Float;
+        let (dx, dy) = (wrap(i % size), wrap(i / size));
+        *k = (-(dx * dx + dy * dy) / (2.0 * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp();
+    }
+
+    let mut energy = vec![0.0; count];
+    let mut pattern = vec![false; count];
+    let toggle = |energy: &mut Vec<Float>, pattern: &mut Vec<bool>, at: usize, on: bool| {
+        pattern[at] = on;
+        let sign = if on { 1.0 } else { -1.0 };
+        let (ax, ay) = (at % size, at / size);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i % size + size - ax) % size;
+            let dy = (i / size + size - ay) % size;
+            *e += sign * kernel[dy * size + dx];
+        }
+    };
+    let tightest_cluster = |energy: &Vec<Float>, pattern: &Vec<bool>| {
+        (0..count)
+            .filter(|i| pattern[*i])
+            .max_by(|a, b| energy[*a].partial_cmp(&energy[*b]).unwrap())
+            .unwrap()
+    };
+    let largest_void = |energy: &Vec<Float>, pattern: &Vec<bool>| {
+        (0..count)
+            .filter(|i| !pattern[*i])
+            .min_by(|a, b| energy[*a].partial_cmp(&energy[*b]).unwrap())
+            .unwrap()
+    };
+
+    // A fixed seed keeps the tile, and every image dithered with it, reproducible
+    let mut seed: u32 = 0x9e37_79b9;
+    let initial = count / 10;
+    let mut placed = 0;
+    while placed < initial {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let at = seed as usize % count;
+        if !pattern[at] {
+            toggle(&mut energy, &mut pattern, at, true);
+            placed += 1;
+        }
+    }
+
+    // Spread the initial points out until moving the tightest point doesn't change anything
+    loop {
+        let cluster = tightest_cluster(&energy, &pattern);
+        toggle(&mut energy, &mut pattern, cluster, false);
+        let void = largest_void(&energy, &pattern);
+        toggle(&mut energy, &mut pattern, void, true);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; count];
+    let (initial_energy, initial_pattern) = (energy.clone(), pattern.clone());
+    for rank in (0..initial).rev() {
+        let cluster = tightest_cluster(&energy, &pattern);
+        toggle(&mut energy, &mut pattern, cluster, false);
+        ranks[cluster] = rank;
+    }
+
+    let (mut energy, mut pattern) = (initial_energy, initial_pattern);
+    for rank in initial..count {
+        let void = largest_void(&energy, &pattern);
+        toggle(&mut energy, &mut pattern, void, true);
+        ranks[void] = rank;
+    }
+    ranks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn flat_canvas(value: Float) -> Canvas {
+        let mut c = Canvas::new(32, 32);
+        for xy in c.iter() {
+            c[xy] = Color::new(value, value, value);
+        }
+        c
+    }
+
+    fn dithered(dither: Dither) -> Encoding {
+        Encoding {
+            dither,
+            ..Encoding::default()
+        }
+    }
+
+    fn mean_red(pixels: &[[u8; 3]]) -> Float {
+        pixels.iter().map(|p| p[0] as Float).sum::<Float>() / pixels.len() as Float
+    }
+
+    #[test]
+    fn no_dithering_rounds() {
+        let c = flat_canvas(0.5);
+        let pixels = c.quantize(&Encoding::default());
+        assert_eq!(32 * 32, pixels.len());
+        assert!(pixels.iter().all(|p| *p == [128, 128, 128]));
+    }
+
+    #[test]
+    fn bayer_matrix() {
+        let first_row: Vec<Float> = (0..8).map(|x| bayer_threshold((x, 0)) * 64.0).collect();
+        assert_eq!(vec![0.5, 32.5, 8.5, 40.5, 2.5, 34.5, 10.5, 42.5], first_row);
+        assert_feq!(bayer_threshold((0, 1)), 48.5 / 64.0);
+        assert_feq!(bayer_threshold((3, 9)), bayer_threshold((3, 1)));
+    }
+
+    #[test]
+    fn blue_noise_ranks_every_cell_once() {
+        let mut ranks = void_and_cluster(8);
+        ranks.sort_unstable();
+        assert_eq!((0..64).collect::<Vec<usize>>(), ranks);
+    }
+
+    #[test]
+    fn dithering_preserves_average_level() {
+        // 100.4 out of 255 sits between two levels, dithering mixes the two to hit the average
+        let level = 100.4;
+        let c = flat_canvas(level / 255.0);
+        for dither in &[Dither::Bayer, Dither::BlueNoise, Dither::FloydSteinberg] {
+            let pixels = c.quantize(&dithered(*dither));
+            assert!(pixels.iter().all(|p| p[0] == 100 || p[0] == 101));
+            assert!(pixels.iter().any(|p| p[0] == 101));
+            assert!((mean_red(&pixels) - level).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn dithering_keeps_extremes() {
+        for dither in &[Dither::Bayer, Dither::BlueNoise, Dither::FloydSteinberg] {
+            let pixels = flat_canvas(1.0).quantize(&dithered(*dither));
+            assert!(pixels.iter().all(|p| *p == [255, 255, 255]));
+            let pixels = flat_canvas(0.0).quantize(&dithered(*dither));
+            assert!(pixels.iter().all(|p| *p == [0, 0, 0]));
+        }
+    }
+}
@@ -0,0 +1,335 @@
+use super::font::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::*;
+
+// Blank columns and rows left between characters of text
+const GLYPH_SPACING: usize = 1;
+
+impl Canvas {
+    // Bresenham's line, including both end points: a pixel for every step along the longer axis,
+    // with the other axis rounded to the nearest pixel. Only the steps that land on the canvas
+    // are walked, so a line running far off it costs no more than one across it.
+    pub fn draw_line(&mut self, from: (isize, isize), to: (isize, isize), color: Color) {
+        let (width, height) = self.dimensions;
+        let (x0, y0) = (from.0 as i128, from.1 as i128);
+        let (dx, dy) = (to.0 as i128 - x0, to.1 as i128 - y0);
+        let steep = dy.abs() > dx.abs();
+        let (major, minor, d_major, d_minor, size) = if steep {
+            (y0, x0, dy, dx, height as i128)
+        } else {
+            (x0, y0, dx, dy, width as i128)
+        };
+
+        let steps = d_major.abs();
+        let (first, last) = if d_major >= 0 {
+            ((-major).max(0), (size - 1 - major).min(steps))
+        } else {
+            ((major - (size - 1)).max(0), major.min(steps))
+        };
+        for i in first..=last {
+            // Both factors are below 2^64, so the product fits
+            let offset = if steps == 0 {
+                0
+            } else {
+                ((i as u128 * d_minor.unsigned_abs() + steps as u128 / 2) / steps as u128) as i128
+            };
+            let a = major + i * d_major.signum();
+            let b = minor + offset * d_minor.signum();
+            if b >= 0 {
+                let (x, y) = if steep { (b, a) } else { (a, b) };
+                self.set((x as usize, y as usize), color);
+            }
+        }
+    }
+
+    // Xiaolin Wu's line, blending each pixel with the existing color by how much the line
+    // covers it
+    pub fn draw_line_antialiased(
+        &mut self,
+        from: (Float, Float),
+        to: (Float, Float),
+        color: Color,
+    ) {
+        let ((mut x0, mut y0), (mut x1, mut y1)) = (from, to);
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            std::mem::swap(&mut x0, &mut y0);
+            std::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        let (width, height) = self.dimensions;
+        let columns = if steep { height } else { width };
+        let mut plot = |x: Float, y: Float, coverage: Float| {
+            let xy = if steep { (y, x) } else { (x, y) };
+            self.blend((xy.0 as isize, xy.1 as isize), color, coverage);
+        };
+
+        // Also right for negative numbers, unlike Float::fract
+        let fract = |v: Float| v - v.floor();
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+
+        let x_start = x0.round();
+        let y_start = y0 + gradient * (x_start - x0);
+        let gap = 1.0 - fract(x0 + 0.5);
+        plot(x_start, y_start.floor(), (1.0 - fract(y_start)) * gap);
+        plot(x_start, y_start.floor() + 1.0, fract(y_start) * gap);
+
+        let x_end = x1.round();
+        let y_end = y1 + gradient * (x_end - x1);
+        let gap = fract(x1 + 0.5);
+        plot(x_end, y_end.floor(), (1.0 - fract(y_end)) * gap);
+        plot(x_end, y_end.floor() + 1.0, fract(y_end) * gap);
+
+        // Only the columns on the canvas, counted with an integer since past 2^24 adding 1 to
+        // an f32 no longer changes it
+        let first = (x_start + 1.0).max(0.0) as usize;
+        let last = x_end.min(columns as Float) as usize;
+        for column in first..last {
+            let x = column as Float;
+            let y = y_start + gradient * (x - x_start);
+            plot(x, y.floor(), 1.0 - fract(y));
+            plot(x, y.floor() + 1.0, fract(y));
+        }
+    }
+
+    pub fn draw_rect(
+        &mut self,
+        (x, y): (isize, isize),
+        (width, height): (usize, usize),
+        color: Color,
+    ) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (right, bottom) = (x + width as isize - 1, y + height as isize - 1);
+        self.draw_line((x, y), (right, y), color);
+        self.draw_line((x, bottom), (right, bottom), color);
+        self.draw_line((x, y), (x, bottom), color);
+        self.draw_line((right, y), (right, bottom), color);
+    }
+
+    pub fn fill_rect(
+        &mut self,
+        (x, y): (isize, isize),
+        (width, height): (usize, usize),
+        color: Color,
+    ) {
+        let (canvas_width, canvas_height) = self.dimensions;
+        let clip = |start: isize, length: usize, size: usize| {
+            let end = start.saturating_add(length.min(isize::MAX as usize) as isize);
+            (start.max(0) as usize, end.clamp(0, size as isize) as usize)
+        };
+        let (left, right) = clip(x, width, canvas_width);
+        let (top, bottom) = clip(y, height, canvas_height);
+        for y in top..bottom {
+            for x in left..right {
+                self.set((x, y), color);
+            }
+        }
+    }
+
+    // Midpoint circle outline
+    pub fn draw_circle(&mut self, (cx, cy): (isize, isize), radius: usize, color: Color) {
+        let mut x = radius as isize;
+        let mut y = 0;
+        let mut error = 1 - x;
+        while x >= y {
+            for (dx, dy) in &[(x, y), (y, x), (-y, x), (-x, y)] {
+                self.plot((cx + dx, cy + dy), color);
+                self.plot((cx - dx, cy - dy), color);
+            }
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    // Writes text with the built in 5x7 font, `at` being the top left corner of the first
+    // character. Lowercase letters are drawn as capitals and newlines start a new line.
+    pub fn draw_text(&mut self, (x, y): (isize, isize), text: &str, color: Color) {
+        let advance_x = (GLYPH_WIDTH + GLYPH_SPACING) as isize;
+        let advance_y = (GLYPH_HEIGHT + GLYPH_SPACING) as isize;
+
+        for (line_number, line) in text.lines().enumerate() {
+            let top = y + line_number as isize * advance_y;
+            for (column, c) in line.chars().enumerate() {
+                let left = x + column as isize * advance_x;
+                for (row, bits) in glyph(c).iter().enumerate() {
+                    for bit in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - bit)) != 0 {
+                            self.plot((left + bit as isize, top + row as isize), color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn plot(&mut self, (x, y): (isize, isize), color: Color) {
+        if x >= 0 && y >= 0 {
+            self.set((x as usize, y as usize), color);
+        }
+    }
+
+    fn blend(&mut self, (x, y): (isize, isize), color: Color, coverage: Float) {
+        let (width, height) = self.dimensions;
+        if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+            let xy = (x as usize, y as usize);
+            if self.has_alpha() {
+                let blended = Rgba::new(color, coverage).over(self.rgba(xy));
+                self.set_rgba(xy, blended);
+            } else {
+                let pixel = &mut self[xy];
+                *pixel = *pixel * (1.0 - coverage) + color * coverage;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lit(c: &Canvas) -> Vec<(usize, usize)> {
+        c.iter().filter(|xy| c[*xy] != Color::black()).collect()
+    }
+
+    #[test]
+    fn horizontal_and_vertical_lines() {
+        let mut c = Canvas::new(5, 5);
+        c.draw_line((1, 2), (3, 2), Color::white());
+        assert_eq!(vec![(1, 2), (2, 2), (3, 2)], lit(&c));
+
+        let mut c = Canvas::new(5, 5);
+        c.draw_line((4, 3), (4, 1), Color::white());
+        assert_eq!(vec![(4, 1), (4, 2), (4, 3)], lit(&c));
+    }
+
+    #[test]
+    fn shallow_line() {
+        let mut c = Canvas::new(5, 3);
+        c.draw_line((0, 0), (4, 2), Color::white());
+        assert_eq!(vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)], lit(&c));
+    }
+
+    #[test]
+    fn lines_are_clipped() {
+        let mut c = Canvas::new(3, 3);
+        c.draw_line((-2, -2), (5, 5), Color::white());
+        assert_eq!(vec![(0, 0), (1, 1), (2, 2)], lit(&c));
+    }
+
+    #[test]
+    fn antialiased_line() {
+        let mut c = Canvas::new(6, 3);
+        c.draw_line_antialiased((0.0, 1.0), (5.0, 1.0), Color::white());
+        for x in 1..5 {
+            assert_eq!(Color::white(), c[(x, 1)]);
+            assert_eq!(Color::black(), c[(x, 0)]);
+            assert_eq!(Color::black(), c[(x, 2)]);
+        }
+
+        // Halfway between two rows splits the color across both
+        let mut c = Canvas::new(6, 3);
+        c.draw_line_antialiased((0.0, 0.5), (5.0, 0.5), Color::white());
+        assert_eq!(Color::new(0.5, 0.5, 0.5), c[(2, 0)]);
+        assert_eq!(Color::new(0.5, 0.5, 0.5), c[(2, 1)]);
+    }
+
+    #[test]
+    fn steep_antialiased_line_is_clipped() {
+        let mut c = Canvas::new(3, 3);
+        c.draw_line_antialiased((1.0, -4.0), (1.0, 8.0), Color::white());
+        for y in 0..3 {
+            assert_eq!(Color::white(), c[(1, y)]);
+        }
+    }
+
+    #[test]
+    fn antialiased_line_between_negative_rows() {
+        // A quarter of the way into row -1, so row 0 gets the other three quarters
+        let mut c = Canvas::new(6, 2);
+        c.draw_line_antialiased((0.0, -0.25), (5.0, -0.25), Color::white());
+        for x in 0..6 {
+            let red = c[(x, 0)].red();
+            assert!((0.0..=1.0).contains(&red), "{:?}", c[(x, 0)]);
+        }
+        assert_eq!(Color::new(0.75, 0.75, 0.75), c[(2, 0)]);
+        assert_eq!(Color::black(), c[(2, 1)]);
+
+        let mut c = Canvas::new(6, 3);
+        c.draw_line_antialiased((-1.25, 1.5), (4.75, 1.5), Color::white());
+        assert_eq!(Color::new(0.5, 0.5, 0.5), c[(2, 1)]);
+        assert!(c.iter().all(|xy| c[xy].red() >= 0.0));
+    }
+
+    #[test]
+    fn huge_shapes_only_draw_what_is_on_the_canvas() {
+        let mut c = Canvas::new(4, 3);
+        c.draw_line_antialiased((0.0, 1.0), (2.0e7, 1.0), Color::white());
+        assert_eq!(Color::white(), c[(3, 1)]);
+
+        let mut c = Canvas::new(4, 3);
+        c.draw_line((isize::MIN, 1), (isize::MAX, 1), Color::white());
+        assert_eq!(4, lit(&c).len());
+
+        let mut c = Canvas::new(4, 3);
+        c.fill_rect((-(1 << 40), 1), (usize::MAX, 1), Color::white());
+        assert_eq!(vec![(0, 1), (1, 1), (2, 1), (3, 1)], lit(&c));
+    }
+
+    #[test]
+    fn rectangles() {
+        let mut c = Canvas::new(5, 5);
+        c.draw_rect((1, 1), (3, 3), Color::white());
+        assert_eq!(8, lit(&c).len());
+        assert_eq!(Color::black(), c[(2, 2)]);
+
+        let mut c = Canvas::new(5, 5);
+        c.fill_rect((3, -1), (4, 3), Color::white());
+        assert_eq!(vec![(3, 0), (4, 0), (3, 1), (4, 1)], lit(&c));
+    }
+
+    #[test]
+    fn circles() {
+        let mut c = Canvas::new(7, 7);
+        c.draw_circle((3, 3), 3, Color::white());
+        for xy in &[(0, 3), (6, 3), (3, 0), (3, 6)] {
+            assert_eq!(Color::white(), c[*xy]);
+        }
+        assert_eq!(Color::black(), c[(3, 3)]);
+        assert_eq!(Color::black(), c[(0, 0)]);
+
+        let points = lit(&c);
+        for (x, y) in &points {
+            assert!(points.contains(&(6 - x, *y)));
+            assert!(points.contains(&(*y, *x)));
+        }
+    }
+
+    #[test]
+    fn text() {
+        let mut c = Canvas::new(12, 16);
+        c.draw_text((0, 0), "i1\nT", Color::white());
+
+        // The capital I's top bar, then the 1 starts after a blank column
+        assert_eq!(Color::black(), c[(0, 0)]);
+        assert_eq!(Color::white(), c[(1, 0)]);
+        assert_eq!(Color::white(), c[(3, 0)]);
+        assert_eq!(Color::black(), c[(4, 0)]);
+        assert_eq!(Color::white(), c[(8, 0)]);
+
+        // The T on the second line
+        assert_eq!(Color::white(), c[(0, 8)]);
+        assert_eq!(Color::white(), c[(2, 14)]);
+        assert_eq!(Color::black(), c[(0, 14)]);
+    }
+}
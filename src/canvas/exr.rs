@@ -0,0 +1,738 @@
+use super::*;
+
+use flate2::write::ZlibEncoder;
+use std::io::{Error, ErrorKind};
+
+const MAGIC_NUMBER: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+const SINGLE_PART_SCANLINE_VERSION: [u8; 4] = [2, 0, 0, 0];
+const MAX_CHANNEL_NAME_LENGTH: usize = 31;
+const MAX_RUN_LENGTH: usize = 127;
+const MIN_RUN_LENGTH: usize = 3;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExrCompression {
+    None,
+    Rle,
+    Zip,
+}
+
+impl ExrCompression {
+    fn id(self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Rle => 1,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn scanlines_per_block(self) -> usize {
+        match self {
+            ExrCompression::None | ExrCompression::Rle => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExrPixelType {
+    Half,
+    Float,
+}
+
+impl ExrPixelType {
+    fn id(self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+}
+
+// An extra named channel written next to the canvas colors, one value per pixel in row-major
+// order. Names follow the EXR conventions, e.g. "Z" for depth or "N.X" for a normal's x.
+#[derive(Debug, Clone)]
+pub struct ExrChannel {
+    pub name: String,
+    pub pixel_type: ExrPixelType,
+    pub values: Vec<Float>,
+}
+
+impl ExrChannel {
+    pub fn new(name: &str, pixel_type: ExrPixelType, values: Vec<Float>) -> ExrChannel {
+        ExrChannel {
+            name: String::from(name),
+            pixel_type,
+            values,
+        }
+    }
+
+    pub fn depth(values: Vec<Float>) -> ExrChannel {
+        Self::new("Z", ExrPixelType::Float, values)
+    }
+
+    // Which shape each pixel shows, as its handle's index or -1 where nothing was hit. Floats
+    // hold every index exactly up to 2^24, far more shapes than a scene will have.
+    pub fn object_ids(handles: &[Option<ShapeHandle>]) -> ExrChannel {
+        let values = handles
+            .iter()
+            .map(|handle| handle.map_or(-1.0, |handle| handle.index() as Float))
+            .collect();
+        Self::new("id", ExrPixelType::Float, values)
+    }
+
+    // Splits a canvas of normals (x, y, z stored as red, green, blue) into N.X, N.Y and N.Z
+    pub fn normals(normals: &Canvas) -> Vec<ExrChannel> {
+        let channel = |name, component: fn(&Color) -> Float| {
+            let values = normals.iter().map(|xy| component(&normals[xy])).collect();
+            Self::new(name, ExrPixelType::Float, values)
+        };
+        vec![
+            channel("N.X", Color::red),
+            channel("N.Y", Color::green),
+            channel("N.Z", Color::blue),
+        ]
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExrOptions {
+    pub compression: ExrCompression,
+    pub pixel_type: ExrPixelType,
+    pub extra_channels: Vec<ExrChannel>,
+}
+
+impl Default for ExrOptions {
+    fn default() -> Self {
+        ExrOptions {
+            compression: ExrCompression::Zip,
+            pixel_type: ExrPixelType::Half,
+            extra_channels: vec![],
+        }
+    }
+}
+
+enum Source<'a> {
+    Red,
+    Green,
+    Blue,
+    Values(&'a [Float]),
+}
+
+struct Layer<'a> {
+    name: &'a str,
+    pixel_type: ExrPixelType,
+    source: Source<'a>,
+}
+
+impl<'a> Layer<'a> {
+    // Files store at most single precision, whatever the precision of the canvas
+    fn sample(&self, canvas: &Canvas, (x, y): (usize, usize)) -> f32 {
+        let value: Float = match self.source {
+            Source::Red => canvas[(x, y)].red(),
+            Source::Green => canvas[(x, y)].green(),
+            Source::Blue => canvas[(x, y)].blue(),
+            Source::Values(values) => values[y * canvas.dimensions.0 + x],
+        };
+        value as f32
+    }
+}
+
+impl Canvas {
+    pub fn write_exr<W: Write>(&self, write: &mut W, options: &ExrOptions) -> Result<(), Error> {
+        let layers = self.exr_layers(options)?;
+        let header = self.exr_header(&layers, options.compression);
+
+        let (width, height) = self.dimensions;
+        let lines_per_block = options.compression.scanlines_per_block();
+        let mut chunks = Vec::new();
+        for first_line in (0..height).step_by(lines_per_block) {
+            let last_line = (first_line + lines_per_block).min(height);
+            let mut raw = Vec::new();
+            for y in first_line..last_line {
+                for layer in &layers {
+                    for x in 0..width {
+                        let value = layer.sample(self, (x, y));
+                        match layer.pixel_type {
+                            ExrPixelType::Half => {
+                                raw.extend_from_slice(&f32_to_half(value).to_le_bytes())
+                            }
+                            ExrPixelType::Float => raw.extend_from_slice(&value.to_le_bytes()),
+                        }
+                    }
+                }
+            }
+            chunks.push((first_line, compress(options.compression, raw)?));
+        }
+
+        write.write_all(&header)?;
+
+        // Each chunk is prefixed by its y coordinate and byte count
+        let mut offset = (header.len() + chunks.len() * 8) as u64;
+        for (_, data) in &chunks {
+            write.write_all(&offset.to_le_bytes())?;
+            offset += (data.len() + 8) as u64;
+        }
+        for (y, data) in &chunks {
+            write.write_all(&(*y as i32).to_le_bytes())?;
+            write.write_all(&(data.len() as i32).to_le_bytes())?;
+            write.write_all(data)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_as_exr(&self, path: &Path, options: &ExrOptions) -> Result<(), Error> {
+        let f = File::create(path)?;
+        let mut f = BufWriter::new(f);
+        self.write_exr(&mut f, options)
+    }
+
+    fn exr_layers<'a>(&'a self, options: &'a ExrOptions) -> Result<Vec<Layer<'a>>, Error> {
+        let (width, height) = self.dimensions;
+        let mut layers = vec![
+            Layer {
+                name: "R",
+                pixel_type: options.pixel_type,
+                source: Source::Red,
+            },
+            Layer {
+                name: "G",
+                pixel_type: options.pixel_type,
+                source: Source::Green,
+            },
+            Layer {
+                name: "B",
+                pixel_type: options.pixel_type,
+                source: Source::Blue,
+            },
+        ];
+        // EXR alpha is premultiplied, like the canvas
+        if let Some(alpha) = self.alpha_slice() {
+            layers.push(Layer {
+                name: "A",
+                pixel_type: options.pixel_type,
+                source: Source::Values(alpha),
+            });
+        }
+
+        for channel in &options.extra_channels {
+            if channel.name.is_empty() || channel.name.len() > MAX_CHANNEL_NAME_LENGTH {
+                return Err(invalid_input(format!(
+                    "channel name {:?} must be between 1 and {} bytes",
+                    channel.name, MAX_CHANNEL_NAME_LENGTH
+                )));
+            }
+            if channel.values.len() != width * height {
+                return Err(invalid_input(format!(
+                    "channel {} has {} value(s) but the canvas is {}x{}",
+                    channel.name,
+                    channel.values.len(),
+                    width,
+                    height
+                )));
+            }
+            if layers.iter().any(|layer| layer.name == channel.name) {
+                return Err(invalid_input(format!(
+                    "channel {} is defined more than once",
+                    channel.name
+                )));
+            }
+            layers.push(Layer {
+                name: &channel.name,
+                pixel_type: channel.pixel_type,
+                source: Source::Values(&channel.values),
+            });
+        }
+
+        // Readers expect channels (and their pixel data) in alphabetical order
+        layers.sort_by(|a, b| a.name.cmp(b.name));
+        Ok(layers)
+    }
+
+    fn exr_header(&self, layers: &[Layer<'_>], compression: ExrCompression) -> Vec<u8> {
+        let (width, height) = self.dimensions;
+        let mut header = Vec::new();
+        header.extend_from_slice(&MAGIC_NUMBER);
+        header.extend_from_slice(&SINGLE_PART_SCANLINE_VERSION);
+
+        let mut channels = Vec::new();
+        for layer in layers {
+            channels.extend_from_slice(layer.name.as_bytes());
+            channels.push(0);
+            channels.extend_from_slice(&layer.pixel_type.id().to_le_bytes());
+            // pLinear and three reserved bytes, followed by the x and y sampling
+            channels.extend_from_slice(&[0, 0, 0, 0]);
+            channels.extend_from_slice(&1_i32.to_le_bytes());
+            channels.extend_from_slice(&1_i32.to_le_bytes());
+        }
+        channels.push(0);
+        write_attribute(&mut header, "channels", "chlist", &channels);
+
+        write_attribute(
+            &mut header,
+            "compression",
+            "compression",
+            &[compression.id()],
+        );
+
+        let mut window = Vec::new();
+        for value in [0, 0, width as i32 - 1, height as i32 - 1].iter() {
+            window.extend_from_slice(&value.to_le_bytes());
+        }
+        write_attribute(&mut header, "dataWindow", "box2i", &window);
+        write_attribute(&mut header, "displayWindow", "box2i", &window);
+        write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        write_attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1.0_f32.to_le_bytes(),
+        );
+        write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        write_attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1.0_f32.to_le_bytes(),
+        );
+        header.push(0);
+        header
+    }
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+fn invalid_input(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+fn compress(compression: ExrCompression, raw: Vec<u8>) -> Result<Vec<u8>, Error> {
+    let compressed = match compression {
+        ExrCompression::None => return Ok(raw),
+        ExrCompression::Rle => rle_compress(&predict(&interleave(&raw))),
+        ExrCompression::Zip => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&predict(&interleave(&raw)))?;
+            encoder.finish()?
+        }
+    };
+
+    // Blocks that don't shrink are stored as-is; readers detect this by the byte count
+    if compressed.len() < raw.len() {
+        Ok(compressed)
+    } else {
+        Ok(raw)
+    }
+}
+
+// Splits the bytes of each value apart so the low and high bytes compress separately
+fn interleave(raw: &[u8]) -> Vec<u8> {
+    let evens = raw.iter().step_by(2);
+    let odds = raw.iter().skip(1).step_by(2);
+    evens.chain(odds).cloned().collect()
+}
+
+fn predict(data: &[u8]) -> Vec<u8> {
+    let mut predicted = data.to_vec();
+    for i in 1..data.len() {
+        predicted[i] = data[i].wrapping_sub(data[i - 1]).wrapping_add(128);
+    }
+    predicted
+}
+
+fn rle_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let mut end = start + 1;
+        while end < data.len() && data[end] == data[start] && end - start <= MAX_RUN_LENGTH {
+            end += 1;
+        }
+
+        if end - start >= MIN_RUN_LENGTH {
+            // A run of the same byte
+            out.push((end - start - 1) as u8);
+            out.push(data[start]);
+        } else {
+            // Literal bytes up to the next run of three
+            end = start;
+            while end < data.len()
+                && end - start < MAX_RUN_LENGTH
+                && !(end + 2 < data.len()
+                    && data[end] == data[end + 1]
+                    && data[end] == data[end + 2])
+            {
+                end += 1;
+            }
+            out.push((-((end - start) as i32)) as u8);
+            out.extend_from_slice(&data[start..end]);
+        }
+        start = end;
+    }
+    out
+}
+
+// Converts to IEEE 754 half precision, rounding to the nearest even value
+pub fn f32_to_half(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // Subnormal half, the implicit leading bit becomes explicit
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - exponent) as u32;
+        let round_bit = 1 << (shift - 1);
+        let mut half = mantissa >> shift;
+        if mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0 {
+            half += 1;
+        }
+        return sign | half as u16;
+    }
+
+    let half = sign | ((exponent as u16) << 10) | ((mantissa >> 13) as u16);
+    let round_bit = 0x1000;
+    if mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0 {
+        // A carry into the exponent still yields the correctly rounded value
+        half + 1
+    } else {
+        half
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    struct Decoded {
+        channels: Vec<(String, i32)>,
+        compression: u8,
+        lines: Vec<Vec<u8>>,
+    }
+
+    fn read_u32(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    fn read_str(bytes: &[u8], at: &mut usize) -> String {
+        let start = *at;
+        while bytes[*at] != 0 {
+            *at += 1;
+        }
+        *at += 1;
+        String::from_utf8(bytes[start..*at - 1].to_vec()).unwrap()
+    }
+
+    fn unpredict(data: &[u8]) -> Vec<u8> {
+        let mut out = data.to_vec();
+        for i in 1..out.len() {
+            out[i] = out[i - 1].wrapping_add(out[i]).wrapping_sub(128);
+        }
+        let half = out.len().div_ceil(2);
+        let mut raw = Vec::with_capacity(out.len());
+        for i in 0..half {
+            raw.push(out[i]);
+            if half + i < out.len() {
+                raw.push(out[half + i]);
+            }
+        }
+        raw
+    }
+
+    fn rle_decompress(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let count = data[i] as i8;
+            if count < 0 {
+                let n = -(count as i32) as usize;
+                out.extend_from_slice(&data[i + 1..i + 1 + n]);
+                i += n + 1;
+            } else {
+                out.extend(std::iter::repeat_n(data[i + 1], count as usize + 1));
+                i += 2;
+            }
+        }
+        out
+    }
+
+    fn decode(bytes: &[u8], (width, height): (usize, usize)) -> Decoded {
+        assert_eq!(&bytes[0..4], &MAGIC_NUMBER);
+        assert_eq!(&bytes[4..8], &SINGLE_PART_SCANLINE_VERSION);
+
+        let mut at = 8;
+        let mut channels = vec![];
+        let mut compression = 0;
+        loop {
+            let name = read_str(bytes, &mut at);
+            if name.is_empty() {
+                break;
+            }
+            let _kind = read_str(bytes, &mut at);
+            let size = read_u32(bytes, at) as usize;
+            at += 4;
+            let value = &bytes[at..at + size];
+            if name == "channels" {
+                let mut c = 0;
+                loop {
+                    let channel = read_str(value, &mut c);
+                    if channel.is_empty() {
+                        break;
+                    }
+                    channels.push((channel, read_u32(value, c) as i32));
+                    c += 16;
+                }
+            } else if name == "compression" {
+                compression = value[0];
+            }
+            at += size;
+        }
+
+        let bytes_per_line: usize = channels
+            .iter()
+            .map(|(_, kind)| if *kind == 1 { 2 * width } else { 4 * width })
+            .sum();
+        let lines_per_block = if compression == 3 { 16 } else { 1 };
+        let blocks = height.div_ceil(lines_per_block);
+
+        let mut lines = vec![];
+        for block in 0..blocks {
+            let offset = read_u32(bytes, at + block * 8) as usize;
+            let y = read_u32(bytes, offset) as usize;
+            assert_eq!(block * lines_per_block, y);
+            let size = read_u32(bytes, offset + 4) as usize;
+            let data = &bytes[offset + 8..offset + 8 + size];
+            let block_lines = lines_per_block.min(height - y);
+            let expected = block_lines * bytes_per_line;
+
+            let raw = if size == expected {
+                data.to_vec()
+            } else if compression == 1 {
+                unpredict(&rle_decompress(data))
+            } else {
+                let mut inflated = vec![];
+                ZlibDecoder::new(data).read_to_end(&mut inflated).unwrap();
+                unpredict(&inflated)
+            };
+            assert_eq!(expected, raw.len());
+            for line in raw.chunks(bytes_per_line) {
+                lines.push(line.to_vec());
+            }
+        }
+        Decoded {
+            channels,
+            compression,
+            lines,
+        }
+    }
+
+    fn gradient_canvas(width: usize, height: usize) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for (x, y) in c.iter() {
+            c[(x, y)] = Color::new(x as Float * 0.25, y as Float * 0.5, 1.5);
+        }
+        c
+    }
+
+    fn floats(line: &[u8], from: usize, count: usize) -> Vec<f32> {
+        (0..count)
+            .map(|i| f32::from_bits(read_u32(line, from + i * 4)))
+            .collect()
+    }
+
+    fn exr_bytes(canvas: &Canvas, options: &ExrOptions) -> Vec<u8> {
+        let mut v = Vec::new();
+        assert!(canvas.write_exr(&mut v, options).is_ok());
+        v
+    }
+
+    #[test]
+    fn converting_to_half() {
+        assert_eq!(0x0000, f32_to_half(0.0));
+        assert_eq!(0x8000, f32_to_half(-0.0));
+        assert_eq!(0x3c00, f32_to_half(1.0));
+        assert_eq!(0x3800, f32_to_half(0.5));
+        assert_eq!(0xc000, f32_to_half(-2.0));
+        assert_eq!(0x3555, f32_to_half(1.0 / 3.0));
+        assert_eq!(0x7bff, f32_to_half(65504.0));
+        assert_eq!(0x7c00, f32_to_half(1.0e6));
+        assert_eq!(0x0001, f32_to_half(5.9604645e-8));
+        assert_eq!(0x0000, f32_to_half(1.0e-10));
+        assert_eq!(0x7c00, f32_to_half(f32::INFINITY));
+        assert_eq!(0x7e00, f32_to_half(f32::NAN));
+    }
+
+    #[test]
+    fn uncompressed_float_channels() {
+        let c = gradient_canvas(3, 2);
+        let options = ExrOptions {
+            compression: ExrCompression::None,
+            pixel_type: ExrPixelType::Float,
+            extra_channels: vec![],
+        };
+        let decoded = decode(&exr_bytes(&c, &options), c.dimensions);
+        assert_eq!(0, decoded.compression);
+        assert_eq!(
+            vec![
+                (String::from("B"), 2),
+                (String::from("G"), 2),
+                (String::from("R"), 2)
+            ],
+            decoded.channels
+        );
+        assert_eq!(2, decoded.lines.len());
+        assert_eq!(vec![1.5, 1.5, 1.5], floats(&decoded.lines[1], 0, 3));
+        assert_eq!(vec![0.5, 0.5, 0.5], floats(&decoded.lines[1], 12, 3));
+        assert_eq!(vec![0.0, 0.25, 0.5], floats(&decoded.lines[1], 24, 3));
+    }
+
+    #[test]
+    fn half_channels() {
+        let c = gradient_canvas(2, 1);
+        let options = ExrOptions {
+            compression: ExrCompression::None,
+            ..ExrOptions::default()
+        };
+        let decoded = decode(&exr_bytes(&c, &options), c.dimensions);
+        assert!(decoded.channels.iter().all(|(_, kind)| *kind == 1));
+        assert_eq!(
+            vec![0x00, 0x3e, 0x00, 0x3e, 0, 0, 0, 0, 0, 0, 0x00, 0x34],
+            decoded.lines[0]
+        );
+    }
+
+    #[test]
+    fn compressed_output_matches_uncompressed() {
+        let c = gradient_canvas(40, 37);
+        let uncompressed = decode(
+            &exr_bytes(
+                &c,
+                &ExrOptions {
+                    compression: ExrCompression::None,
+                    ..ExrOptions::default()
+                },
+            ),
+            c.dimensions,
+        );
+
+        for compression in &[ExrCompression::Rle, ExrCompression::Zip] {
+            let options = ExrOptions {
+                compression: *compression,
+                ..ExrOptions::default()
+            };
+            let bytes = exr_bytes(&c, &options);
+            let decoded = decode(&bytes, c.dimensions);
+            assert_eq!(compression.id(), decoded.compression);
+            assert_eq!(uncompressed.lines, decoded.lines);
+        }
+    }
+
+    #[test]
+    fn rle_runs_and_literals() {
+        let mut data = vec![7; 300];
+        data.extend_from_slice(&[1, 2, 3, 3, 4]);
+        data.extend((0..200).map(|i| i as u8));
+        assert_eq!(data, rle_decompress(&rle_compress(&data)));
+        assert!(rle_compress(&[9; 100]).len() == 2);
+    }
+
+    #[test]
+    fn extra_channels_are_sorted_with_colors() {
+        let c = gradient_canvas(2, 2);
+        let mut normals = Canvas::new(2, 2);
+        normals[(1, 1)] = Color::new(0.0, 1.0, 0.0);
+
+        let mut extra_channels = ExrChannel::normals(&normals);
+        extra_channels.push(ExrChannel::depth(vec![1.0, 2.0, 3.0, 4.0]));
+        let options = ExrOptions {
+            compression: ExrCompression::Zip,
+            pixel_type: ExrPixelType::Half,
+            extra_channels,
+        };
+        let decoded = decode(&exr_bytes(&c, &options), c.dimensions);
+        let names: Vec<&str> = decoded.channels.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(vec!["B", "G", "N.X", "N.Y", "N.Z", "R", "Z"], names);
+
+        // B and G are halves (2 bytes per pixel), N.X, N.Y, N.Z and Z are floats
+        let line = &decoded.lines[1];
+        assert_eq!(vec![0.0, 1.0], floats(line, 8 + 8, 2));
+        assert_eq!(vec![3.0, 4.0], floats(line, 8 + 24 + 4, 2));
+    }
+
+    #[test]
+    fn object_id_channel() {
+        let c = Canvas::new(3, 1);
+        let ids = [
+            None,
+            Some(ShapeHandle::new(0)),
+            Some(ShapeHandle::new(70_000)),
+        ];
+        let options = ExrOptions {
+            compression: ExrCompression::None,
+            pixel_type: ExrPixelType::Half,
+            extra_channels: vec![ExrChannel::object_ids(&ids)],
+        };
+        let decoded = decode(&exr_bytes(&c, &options), c.dimensions);
+        let names: Vec<&str> = decoded.channels.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(vec!["B", "G", "R", "id"], names);
+        // Past the half colors, B, G and R at 2 bytes each
+        assert_eq!(
+            vec![-1.0, 0.0, 70_000.0],
+            floats(&decoded.lines[0], 3 * 6, 3)
+        );
+    }
+
+    #[test]
+    fn alpha_channel() {
+        let mut c = Canvas::transparent(2, 1);
+        c.set_rgba((1, 0), Rgba::new(Color::white(), 0.5));
+        let options = ExrOptions {
+            compression: ExrCompression::None,
+            pixel_type: ExrPixelType::Float,
+            extra_channels: vec![],
+        };
+        let decoded = decode(&exr_bytes(&c, &options), c.dimensions);
+        let names: Vec<&str> = decoded.channels.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(vec!["A", "B", "G", "R"], names);
+        assert_eq!(vec![0.0, 0.5], floats(&decoded.lines[0], 0, 2));
+        assert_eq!(vec![0.0, 0.5], floats(&decoded.lines[0], 8, 2));
+    }
+
+    #[test]
+    fn extra_channel_validation() {
+        let c = Canvas::new(2, 2);
+        let short = ExrOptions {
+            extra_channels: vec![ExrChannel::depth(vec![1.0])],
+            ..ExrOptions::default()
+        };
+        assert!(c.write_exr(&mut Vec::new(), &short).is_err());
+
+        let duplicate = ExrOptions {
+            extra_channels: vec![ExrChannel::new("R", ExrPixelType::Float, vec![0.0; 4])],
+            ..ExrOptions::default()
+        };
+        assert!(c.write_exr(&mut Vec::new(), &duplicate).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use super::*;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Crc;
+use std::io::{BufReader, Error, ErrorKind, Read};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const BIT_DEPTH: u8 = 8;
+const COLOR_TYPE_RGB: u8 = 2;
+const COLOR_TYPE_RGBA: u8 = 6;
+
+impl Canvas {
+    // Canvases with an alpha plane are written as RGBA, with the colors unpremultiplied as PNG
+    // expects
+    pub fn write_png<W: Write>(&self, write: &mut W) -> Result<(), std::io::Error> {
+        self.write_png_encoded(write, &Encoding::default())
+    }
+
+    pub fn write_png_encoded<W: Write>(
+        &self,
+        write: &mut W,
+        encoding: &Encoding,
+    ) -> Result<(), std::io::Error> {
+        let (width, height) = self.dimensions;
+        let color_type = if self.has_alpha() {
+            COLOR_TYPE_RGBA
+        } else {
+            COLOR_TYPE_RGB
+        };
+        write.write_all(&SIGNATURE)?;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // Bit depth, color type, then the default compression, filter and interlace methods
+        header.extend_from_slice(&[BIT_DEPTH, color_type, 0, 0, 0]);
+        write_chunk(write, b"IHDR", &header)?;
+
+        if encoding.transfer == Transfer::Srgb {
+            // Perceptual rendering intent
+            write_chunk(write, b"sRGB", &[0])?;
+        }
+
+        let pixels = match self.alpha_slice() {
+            Some(_) => self.unpremultiplied().quantize(encoding),
+            None => self.quantize(encoding),
+        };
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        for (y, row) in pixels.chunks_exact(width.max(1)).enumerate() {
+            // Each scanline starts with its filter type, 0 leaves the bytes unfiltered
+            encoder.write_all(&[0])?;
+            for (x, pixel) in row.iter().enumerate() {
+                encoder.write_all(pixel)?;
+                if let Some(alpha) = self.alpha_slice() {
+                    encoder.write_all(&[Color::to_256(alpha[y * width + x]) as u8])?;
+                }
+            }
+        }
+        write_chunk(write, b"IDAT", &encoder.finish()?)?;
+        write_chunk(write, b"IEND", &[])?;
+        Ok(())
+    }
+
+    pub fn save_as_png(&self, path: &Path) -> Result<(), std::io::Error> {
+        self.save_as_png_encoded(path, &Encoding::default())
+    }
+
+    pub fn save_as_png_encoded(
+        &self,
+        path: &Path,
+        encoding: &Encoding,
+    ) -> Result<(), std::io::Error> {
+        let f = File::create(path)?;
+        let mut f = BufWriter::new(f);
+        self.write_png_encoded(&mut f, encoding)
+    }
+}
+
+impl Canvas {
+    // Reads 8 bit RGB and RGBA PNGs without interlacing, which covers everything write_png
+    // produces. Translucent images get an alpha plane and premultiplied colors.
+    pub fn read_png<R: Read>(read: &mut R) -> Result<Canvas, std::io::Error> {
+        let mut bytes = Vec::new();
+        read.read_to_end(&mut bytes)?;
+        if bytes.len() < SIGNATURE.len() || bytes[..SIGNATURE.len()] != SIGNATURE {
+            return Err(invalid_data("not a PNG file"));
+        }
+
+        let mut header = None;
+        let mut compressed = Vec::new();
+        let mut at = SIGNATURE.len();
+        while at + 12 <= bytes.len() {
+            let length = read_u32(&bytes[at..]) as usize;
+            let kind = &bytes[at + 4..at + 8];
+            let end = at + 8 + length;
+            if end + 4 > bytes.len() {
+                return Err(invalid_data("PNG chunk is truncated"));
+            }
+            let data = &bytes[at + 8..end];
+
+            let mut crc = Crc::new();
+            crc.update(kind);
+            crc.update(data);
+            if crc.sum() != read_u32(&bytes[end..]) {
+                return Err(invalid_data("PNG chunk has a bad checksum"));
+            }
+
+            match kind {
+                b"IHDR" if data.len() == 13 => header = Some(data.to_vec()),
+                b"IDAT" => compressed.extend_from_slice(data),
+                b"IEND" => break,
+                _ => (),
+            }
+            at = end + 4;
+        }
+
+        let header = header.ok_or_else(|| invalid_data("PNG has no header"))?;
+        let width = read_u32(&header[0..]) as usize;
+        let height = read_u32(&header[4..]) as usize;
+        let channels = match (header[8], header[9], header[12]) {
+            (BIT_DEPTH, COLOR_TYPE_RGB, 0) => 3,
+            (BIT_DEPTH, COLOR_TYPE_RGBA, 0) => 4,
+            _ => return Err(invalid_data("only 8 bit RGB and RGBA PNGs are supported")),
+        };
+
+        // The sizes come from the file, so they are checked before anything is allocated for
+        // them, and only as much is decompressed as they call for
+        let stride = width
+            .checked_mul(channels)
+            .ok_or_else(|| invalid_data("PNG is too large"))?;
+        let length = stride
+            .checked_add(1)
+            .and_then(|line| line.checked_mul(height))
+            .ok_or_else(|| invalid_data("PNG is too large"))?;
+        let mut raw = Vec::new();
+        ZlibDecoder::new(&compressed[..])
+            .take(length as u64)
+            .read_to_end(&mut raw)?;
+        if raw.len() < length {
+            return Err(invalid_data("PNG pixel data is truncated"));
+        }
+
+        let mut canvas = Canvas::new(width, height);
+        let mut previous = vec![0; stride];
+        let mut current = vec![0; stride];
+        for (y, line) in raw.chunks_exact(stride + 1).take(height).enumerate() {
+            unfilter(line[0], &line[1..], &previous, &mut current, channels)?;
+            for (x, bytes) in current.chunks_exact(channels).enumerate() {
+                let color = Color::new(
+                    bytes[0] as Float / 255.0,
+                    bytes[1] as Float / 255.0,
+                    bytes[2] as Float / 255.0,
+                );
+                let alpha = bytes.get(3).map_or(1.0, |a| *a as Float / 255.0);
+                canvas.set_rgba((x, y), Rgba::new(color, alpha));
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+        Ok(canvas)
+    }
+
+    pub fn load_png(path: &Path) -> Result<Canvas, std::io::Error> {
+        let f = File::open(path)?;
+        Self::read_png(&mut BufReader::new(f))
+    }
+}
+
+// Undoes one of the five scanline filters, `previous` being the already unfiltered line above
+fn unfilter(
+    filter: u8,
+    line: &[u8],
+    previous: &[u8],
+    current: &mut [u8],
+    channels: usize,
+) -> Result<(), std::io::Error> {
+    for i in 0..line.len() {
+        let left = if i >= channels {
+            current[i - channels]
+        } else {
+            0
+        };
+        let up = previous[i];
+        let up_left = if i >= channels {
+            previous[i - channels]
+        } else {
+            0
+        };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(invalid_data("unknown PNG filter type")),
+        };
+        current[i] = line[i].wrapping_add(predicted);
+    }
+    Ok(())
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance = |v: u8| (estimate - v as i16).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn write_chunk<W: Write>(write: &mut W, kind: &[u8; 4], data: &[u8]) -> Result<(), std::io::Error> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+
+    write.write_all(&(data.len() as u32).to_be_bytes())?;
+    write.write_all(kind)?;
+    write.write_all(data)?;
+    write.write_all(&crc.sum().to_be_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chunks(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        assert_eq!(&SIGNATURE, &bytes[0..8]);
+        let mut chunks = vec![];
+        let mut at = 8;
+        while at < bytes.len() {
+            let mut length = [0; 4];
+            length.copy_from_slice(&bytes[at..at + 4]);
+            let length = u32::from_be_bytes(length) as usize;
+            let kind = String::from_utf8(bytes[at + 4..at + 8].to_vec()).unwrap();
+            let data = bytes[at + 8..at + 8 + length].to_vec();
+
+            let mut crc = Crc::new();
+            crc.update(&bytes[at + 4..at + 8 + length]);
+            assert_eq!(
+                &crc.sum().to_be_bytes(),
+                &bytes[at + 8 + length..at + 12 + length]
+            );
+
+            chunks.push((kind, data));
+            at += length + 12;
+        }
+        chunks
+    }
+
+    #[test]
+    fn writing_png() {
+        let mut c = Canvas::new(2, 2);
+        c[(0, 0)] = Color::new(1.5, 0.0, 0.0);
+        c[(1, 0)] = Color::new(0.0, 0.5, 0.0);
+        c[(1, 1)] = Color::new(-0.5, 0.0, 1.0);
+
+        let mut v = Vec::new();
+        assert!(c.write_png(&mut v).is_ok());
+        let chunks = chunks(&v);
+        let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_eq!(vec!["IHDR", "IDAT", "IEND"], kinds);
+        assert_eq!(vec![0, 0, 0, 2, 0, 0, 0, 2, 8, 2, 0, 0, 0], chunks[0].1);
+
+        let mut raw = vec![];
+        ZlibDecoder::new(&chunks[1].1[..])
+            .read_to_end(&mut raw)
+            .unwrap();
+        assert_eq!(vec![0, 255, 0, 0, 0, 128, 0, 0, 0, 0, 0, 0, 0, 255], raw);
+    }
+
+    #[test]
+    fn srgb_png_is_tagged() {
+        let c = Canvas::new(1, 1);
+        let mut v = Vec::new();
+        assert!(c.write_png_encoded(&mut v, &Encoding::srgb()).is_ok());
+        let chunks = chunks(&v);
+        assert_eq!("sRGB", chunks[1].0);
+        assert_eq!(vec![0], chunks[1].1);
+    }
+
+    #[test]
+    fn reading_png() {
+        let mut c = Canvas::new(3, 2);
+        c[(0, 0)] = Color::new(1.0, 0.0, 0.0);
+        c[(2, 0)] = Color::new(0.0, 0.2, 0.0);
+        c[(1, 1)] = Color::new(0.6, 0.4, 1.0);
+
+        let mut v = Vec::new();
+        assert!(c.write_png(&mut v).is_ok());
+        let read = Canvas::read_png(&mut &v[..]).unwrap();
+        assert_eq!((3, 2), read.dimensions);
+        assert_eq!(Color::new(1.0, 0.0, 0.0), read[(0, 0)]);
+        assert_eq!(Color::new(0.0, 51.0 / 255.0, 0.0), read[(2, 0)]);
+        assert_eq!(Color::new(153.0 / 255.0, 102.0 / 255.0, 1.0), read[(1, 1)]);
+    }
+
+    #[test]
+    fn reading_filtered_png() {
+        // Two RGBA pixels per line, the second line using the up filter and the third paeth
+        let raw = [
+            1, 10, 20, 30, 255, 5, 5, 5, 0, //
+            2, 1, 1, 1, 0, 1, 1, 1, 0, //
+            4, 0, 0, 0, 0, 1, 2, 3, 0,
+        ];
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&raw).unwrap();
+        let mut header = vec![0, 0, 0, 2, 0, 0, 0, 3];
+        header.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE_RGBA, 0, 0, 0]);
+
+        let mut v = SIGNATURE.to_vec();
+        write_chunk(&mut v, b"IHDR", &header).unwrap();
+        write_chunk(&mut v, b"IDAT", &encoder.finish().unwrap()).unwrap();
+        write_chunk(&mut v, b"IEND", &[]).unwrap();
+
+        let c = Canvas::read_png(&mut &v[..]).unwrap();
+        let bytes = |p: Color| {
+            [p.red(), p.green(), p.blue()]
+                .iter()
+                .map(|v| (v * 255.0).round() as u8)
+                .collect::<Vec<u8>>()
+        };
+        assert_eq!(vec![10, 20, 30], bytes(c[(0, 0)]));
+        assert_eq!(vec![15, 25, 35], bytes(c[(1, 0)]));
+        assert_eq!(vec![11, 21, 31], bytes(c[(0, 1)]));
+        assert_eq!(vec![16, 26, 36], bytes(c[(1, 1)]));
+        assert_eq!(vec![11, 21, 31], bytes(c[(0, 2)]));
+        assert_eq!(vec![17, 28, 39], bytes(c[(1, 2)]));
+    }
+
+    #[test]
+    fn writing_and_reading_png_with_alpha() {
+        let mut c = Canvas::transparent(2, 1);
+        c.set_rgba((1, 0), Rgba::new(Color::new(1.0, 0.2, 0.0), 0.6));
+
+        let mut v = Vec::new();
+        assert!(c.write_png(&mut v).is_ok());
+        let chunks = chunks(&v);
+        assert_eq!(COLOR_TYPE_RGBA, chunks[0].1[9]);
+        let mut raw = vec![];
+        ZlibDecoder::new(&chunks[1].1[..])
+            .read_to_end(&mut raw)
+            .unwrap();
+        assert_eq!(vec![0, 0, 0, 0, 0, 255, 51, 0, 153], raw);
+
+        let read = Canvas::read_png(&mut &v[..]).unwrap();
+        assert_feq!(0.0, read.alpha((0, 0)));
+        assert_feq!(0.6, read.alpha((1, 0)));
+        assert_eq!(Color::new(0.6, 0.12, 0.0), read[(1, 0)]);
+
+        // Opaque RGBA images don't need an alpha plane
+        let mut opaque = Vec::new();
+        Canvas::transparent(1, 1)
+            .flatten(Color::white())
+            .write_png(&mut opaque)
+            .unwrap();
+        assert!(!Canvas::read_png(&mut &opaque[..]).unwrap().has_alpha());
+    }
+
+    #[test]
+    fn reading_invalid_png() {
+        assert!(Canvas::read_png(&mut &b"P3 1 1 255 0 0 0"[..]).is_err());
+
+        let mut v = Vec::new();
+        Canvas::new(1, 1).write_png(&mut v).unwrap();
+        let last = v.len() - 1;
+        v[last] ^= 1;
+        assert!(Canvas::read_png(&mut &v[..]).is_err());
+
+        // A header far bigger than its data, and one too big to even count the bytes of
+        for size in [[0x00, 0x01, 0x00, 0x00], [0xff, 0xff, 0xff, 0xff]].iter() {
+            let mut header = size.to_vec();
+            header.extend_from_slice(size);
+            header.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE_RGBA, 0, 0, 0]);
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(&[0; 9]).unwrap();
+
+            let mut v = SIGNATURE.to_vec();
+            write_chunk(&mut v, b"IHDR", &header).unwrap();
+            write_chunk(&mut v, b"IDAT", &encoder.finish().unwrap()).unwrap();
+            write_chunk(&mut v, b"IEND", &[]).unwrap();
+            let error = Canvas::read_png(&mut &v[..]).unwrap_err();
+            assert_eq!(ErrorKind::InvalidData, error.kind());
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use super::*;
+
+// Middle grey, the scene luminance auto exposure brings the median to
+const MIDDLE_GREY: Float = 0.18;
+
+// Pixel counts per bin for each channel and for luminance. Bins are equal slices of
+// [0, max], with anything darker landing in the first bin and anything brighter in the last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub max: Float,
+    pub red: Vec<usize>,
+    pub green: Vec<usize>,
+    pub blue: Vec<usize>,
+    pub luminance: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn bins(&self) -> usize {
+        self.luminance.len()
+    }
+
+    // The range of values counted by a bin
+    pub fn bin_range(&self, bin: usize) -> (Float, Float) {
+        let width = self.max / self.bins() as Float;
+        (bin as Float * width, (bin + 1) as Float * width)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LuminanceStats {
+    pub min: Float,
+    pub max: Float,
+    pub mean: Float,
+    pub median: Float,
+}
+
+// Statistics only look at pixels with some coverage, so a transparent background doesn't
+// count as a large black area
+impl Canvas {
+    pub fn histogram(&self, bins: usize, max: Float) -> Histogram {
+        let bins = bins.max(1);
+        let bin = |value: Float| {
+            let bin = (value / max * bins as Float).floor();
+            (bin.max(0.0) as usize).min(bins - 1)
+        };
+
+        let mut histogram = Histogram {
+            max,
+            red: vec![0; bins],
+            green: vec![0; bins],
+            blue: vec![0; bins],
+            luminance: vec![0; bins],
+        };
+        for color in self.covered_colors() {
+            histogram.red[bin(color.red())] += 1;
+            histogram.green[bin(color.green())] += 1;
+            histogram.blue[bin(color.blue())] += 1;
+            histogram.luminance[bin(color.luminance())] += 1;
+        }
+        histogram
+    }
+
+    // None when no pixel has any coverage and a finite luminance
+    pub fn luminance_stats(&self) -> Option<LuminanceStats> {
+        let luminance = self.sorted_luminance();
+        if luminance.is_empty() {
+            return None;
+        }
+        let sum: f64 = luminance.iter().map(|&l| util::widen(l)).sum();
+        Some(LuminanceStats {
+            min: luminance[0],
+            max: luminance[luminance.len() - 1],
+            mean: (sum / luminance.len() as f64) as Float,
+            median: percentile(&luminance, 50.0),
+        })
+    }
+
+    // The luminance below which the given percentage of pixels fall, interpolating between
+    // the two closest pixels
+    pub fn luminance_percentile(&self, percent: Float) -> Option<Float> {
+        let luminance = self.sorted_luminance();
+        if luminance.is_empty() {
+            None
+        } else {
+            Some(percentile(&luminance, percent))
+        }
+    }
+
+    // The exposure, in stops for Encoding::exposure, that maps the median luminance to middle
+    // grey. A canvas with nothing lit needs no adjustment.
+    pub fn auto_exposure(&self) -> Float {
+        match self.luminance_stats() {
+            Some(stats) if stats.median > 0.0 => (MIDDLE_GREY / stats.median).log2(),
+            _ => 0.0,
+        }
+    }
+
+    fn covered_colors(&self) -> Vec<Color> {
+        self.iter()
+            .filter(|xy| self.alpha(*xy) > 0.0)
+            .map(|xy| self.rgba(xy).unpremultiplied())
+            .collect()
+    }
+
+    // NaN and infinite pixels, from a degenerate render, would swamp every statistic, so only
+    // the finite ones count
+    fn sorted_luminance(&self) -> Vec<Float> {
+        let mut luminance: Vec<Float> = self
+            .covered_colors()
+            .iter()
+            .map(Color::luminance)
+            .filter(|l| l.is_finite())
+            .collect();
+        luminance.sort_by(|a, b| a.total_cmp(b));
+        luminance
+    }
+}
+
+fn percentile(sorted: &[Float], percent: Float) -> Float {
+    let rank = (percent.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as Float;
+    let below = rank.floor() as usize;
+    let above = rank.ceil() as usize;
+    let t = rank - below as Float;
+    sorted[below] * (1.0 - t) + sorted[above] * t
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gray_canvas(values: &[Float]) -> Canvas {
+        let mut c = Canvas::new(values.len(), 1);
+        for (pixel, value) in c.as_mut_slice().iter_mut().zip(values) {
+            *pixel = Color::new(*value, *value, *value);
+        }
+        c
+    }
+
+    #[test]
+    fn histogram() {
+        let mut c = Canvas::new(4, 1);
+        c[(0, 0)] = Color::new(0.1, 0.6, 0.0);
+        c[(1, 0)] = Color::new(0.3, 0.9, 0.0);
+        c[(2, 0)] = Color::new(1.0, 2.0, -1.0);
+        let h = c.histogram(4, 1.0);
+        assert_eq!(4, h.bins());
+        assert_eq!(vec![2, 1, 0, 1], h.red);
+        assert_eq!(vec![1, 0, 1, 2], h.green);
+        assert_eq!(vec![4, 0, 0, 0], h.blue);
+        assert_eq!(4, h.luminance.iter().sum::<usize>());
+        assert_eq!((0.25, 0.5), h.bin_range(1));
+    }
+
+    #[test]
+    fn luminance_stats() {
+        let c = gray_canvas(&[0.5, 0.1, 0.4, 0.2]);
+        let stats = c.luminance_stats().unwrap();
+        assert_feq!(0.1, stats.min);
+        assert_feq!(0.5, stats.max);
+        assert_feq!(0.3, stats.mean);
+        assert_feq!(0.3, stats.median);
+    }
+
+    #[test]
+    fn percentiles() {
+        let c = gray_canvas(&[0.0, 0.1, 0.2, 0.3, 0.4]);
+        assert_feq!(0.0, c.luminance_percentile(0.0).unwrap());
+        assert_feq!(0.1, c.luminance_percentile(25.0).unwrap());
+        assert_feq!(0.35, c.luminance_percentile(87.5).unwrap());
+        assert_feq!(0.4, c.luminance_percentile(150.0).unwrap());
+    }
+
+    #[test]
+    fn transparent_pixels_are_ignored() {
+        let mut c = Canvas::transparent(3, 1);
+        assert_eq!(None, c.luminance_stats());
+        assert_eq!(None, c.luminance_percentile(50.0));
+
+        c.set_rgba((0, 0), Rgba::new(Color::new(0.8, 0.8, 0.8), 0.5));
+        let stats = c.luminance_stats().unwrap();
+        assert_feq!(0.8, stats.min);
+        assert_eq!(1, c.histogram(2, 1.0).luminance.iter().sum::<usize>());
+    }
+
+    #[test]
+    fn non_finite_pixels_are_ignored() {
+        let c = gray_canvas(&[0.3, Float::NAN, 0.1, Float::INFINITY, 0.2]);
+        let stats = c.luminance_stats().unwrap();
+        assert_feq!(0.1, stats.min);
+        assert_feq!(0.3, stats.max);
+        assert_feq!(0.2, stats.median);
+        assert_feq!(0.3, c.luminance_percentile(100.0).unwrap());
+        assert!(c.auto_exposure().is_finite());
+
+        assert_eq!(None, gray_canvas(&[Float::NAN]).luminance_stats());
+    }
+
+    #[test]
+    fn auto_exposure() {
+        // A median of 0.045 is two stops under middle grey
+        let c = gray_canvas(&[0.01, 0.045, 0.9]);
+        assert_feq!(2.0, c.auto_exposure());
+
+        let exposed = Encoding {
+            exposure: c.auto_exposure(),
+            ..Encoding::default()
+        };
+        assert_eq!(
+            Color::new(0.18, 0.18, 0.18),
+            exposed.encode(Color::new(0.045, 0.045, 0.045))
+        );
+
+        assert_eq!(0.0, Canvas::new(2, 2).auto_exposure());
+    }
+
+    #[test]
+    fn no_height() {
+        let h = Canvas::new(3, 0).histogram(4, 1.0);
+        assert_eq!(0, h.luminance.iter().sum::<usize>());
+    }
+}
@@ -0,0 +1,366 @@
+use super::*;
+
+use crate::util::consts::PI;
+
+const LANCZOS_LOBES: Float = 3.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ResizeFilter {
+    Nearest,
+    Bilinear,
+    Lanczos,
+}
+
+impl ResizeFilter {
+    fn support(self) -> Float {
+        match self {
+            ResizeFilter::Nearest => 0.5,
+            ResizeFilter::Bilinear => 1.0,
+            ResizeFilter::Lanczos => LANCZOS_LOBES,
+        }
+    }
+
+    fn weight(self, x: Float) -> Float {
+        let x = x.abs();
+        match self {
+            ResizeFilter::Nearest => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Bilinear => (1.0 - x).max(0.0),
+            ResizeFilter::Lanczos => {
+                if x >= LANCZOS_LOBES {
+                    0.0
+                } else {
+                    sinc(x) * sinc(x / LANCZOS_LOBES)
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: Float) -> Float {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl Canvas {
+    // Copies the region starting at (x, y). Any part of the region past the canvas is dropped.
+    pub fn crop(&self, (x, y): (usize, usize), (width, height): (usize, usize)) -> Canvas {
+        let (canvas_width, canvas_height) = self.dimensions;
+        let width = width.min(canvas_width.saturating_sub(x));
+        let height = height.min(canvas_height.saturating_sub(y));
+
+        let mut cropped = Canvas::new(width, height);
+        for (row, y2) in cropped.rows_mut().zip(y..) {
+            row.copy_from_slice(&self.row(y2)[x..x + width]);
+        }
+        cropped.alpha = self.transform_alpha(|alpha| alpha.crop((x, y), (width, height)));
+        cropped
+    }
+
+    pub fn flip_horizontal(&self) -> Canvas {
+        let mut flipped = Canvas::new(self.dimensions.0, self.dimensions.1);
+        for (row, original) in flipped.rows_mut().zip(self.rows()) {
+            row.copy_from_slice(original);
+            row.reverse();
+        }
+        flipped.alpha = self.transform_alpha(Canvas::flip_horizontal);
+        flipped
+    }
+
+    pub fn flip_vertical(&self) -> Canvas {
+        let mut flipped = Canvas::new(self.dimensions.0, self.dimensions.1);
+        for (row, original) in flipped.rows_mut().zip(self.rows().rev()) {
+            row.copy_from_slice(original);
+        }
+        flipped.alpha = self.transform_alpha(Canvas::flip_vertical);
+        flipped
+    }
+
+    // Rotates a quarter turn clockwise, a width x height canvas becomes height x width
+    pub fn rotate90(&self) -> Canvas {
+        let (width, height) = self.dimensions;
+        let mut rotated = Canvas::new(height, width);
+        for (x, y) in self.iter() {
+            rotated[(height - 1 - y, x)] = self[(x, y)];
+        }
+        rotated.alpha = self.transform_alpha(Canvas::rotate90);
+        rotated
+    }
+
+    pub fn resize(&self, width: usize, height: usize, filter: ResizeFilter) -> Canvas {
+        let (original_width, original_height) = self.dimensions;
+        if original_width == 0 || original_height == 0 {
+            let mut resized = Canvas::new(width, height);
+            resized.alpha = self.transform_alpha(|_| Canvas::new(width, height));
+            return resized;
+        }
+
+        // Colors are premultiplied, so filtering them alongside alpha doesn't bleed the color
+        // of transparent pixels into their neighbors. Resampling is also separable, so scale
+        // the rows first and then the columns
+        let mut horizontal = Canvas::new(width, original_height);
+        let weights = resample_weights(original_width, width, filter);
+        for (row, original) in horizontal.rows_mut().zip(self.rows()) {
+            for (pixel, taps) in row.iter_mut().zip(&weights) {
+                *pixel = taps
+                    .iter()
+                    .fold(Color::black(), |sum, (i, w)| sum + original[*i] * *w);
+            }
+        }
+
+        let mut resized = Canvas::new(width, height);
+        let weights = resample_weights(original_height, height, filter);
+        for (y, taps) in weights.iter().enumerate() {
+            for x in 0..width {
+                resized[(x, y)] = taps
+                    .iter()
+                    .fold(Color::black(), |sum, (i, w)| sum + horizontal[(x, *i)] * *w);
+            }
+        }
+        resized.alpha = self.transform_alpha(|alpha| alpha.resize(width, height, filter));
+        resized
+    }
+
+    // Composites other over this canvas with its top left corner at `at`, using other's alpha
+    // scaled by opacity (0 keeps this canvas, 1 draws other as is). Pixels landing outside the
+    // canvas are ignored.
+    pub fn blit(&mut self, other: &Canvas, (at_x, at_y): (isize, isize), opacity: Float) {
+        let (width, height) = self.dimensions;
+        for (x, y) in other.iter() {
+            let (x2, y2) = (at_x + x as isize, at_y + y as isize);
+            if x2 < 0 || y2 < 0 || x2 as usize >= width || y2 as usize >= height {
+                continue;
+            }
+            let target = (x2 as usize, y2 as usize);
+            let source = other.rgba((x, y)).scale_alpha(opacity);
+            self.set_rgba(target, source.over(self.rgba(target)));
+        }
+    }
+}
+
+// For every destination pixel, the source pixels it samples and their normalized weights
+fn resample_weights(from: usize, to: usize, filter: ResizeFilter) -> Vec<Vec<(usize, Float)>> {
+    let scale = from as Float / to as Float;
+    // Widen the filter when shrinking so every source pixel contributes
+    let filter_scale = if filter == ResizeFilter::Nearest {
+        1.0
+    } else {
+        scale.max(1.0)
+    };
+    let support = filter.support() * filter_scale;
+
+    (0..to)
+        .map(|i| {
+            let center = (i as Float + 0.5) * scale - 0.5;
+            if filter == ResizeFilter::Nearest {
+                let nearest = (center.round().max(0.0) as usize).min(from - 1);
+                return vec![(nearest, 1.0)];
+            }
+
+            let first = (center - support).ceil() as isize;
+            let last = (center + support).floor() as isize;
+            let mut taps: Vec<(usize, Float)> = (first..=last)
+                .map(|j| {
+                    let weight = filter.weight((j as Float - center) / filter_scale);
+                    (j.clamp(0, from as isize - 1) as usize, weight)
+                })
+                .filter(|(_, weight)| *weight != 0.0)
+                .collect();
+
+            let total: Float = taps.iter().map(|(_, weight)| weight).sum();
+            for tap in taps.iter_mut() {
+                tap.1 /= total;
+            }
+            taps
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn numbered_canvas(width: usize, height: usize) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for (x, y) in c.iter() {
+            c[(x, y)] = Color::new(x as Float, y as Float, 0.0);
+        }
+        c
+    }
+
+    fn flat_canvas(width: usize, height: usize, color: Color) -> Canvas {
+        let mut c = Canvas::new(width, height);
+        for xy in c.iter() {
+            c[xy] = color;
+        }
+        c
+    }
+
+    #[test]
+    fn cropping() {
+        let c = numbered_canvas(5, 4);
+        let cropped = c.crop((1, 2), (3, 2));
+        assert_eq!((3, 2), cropped.dimensions);
+        assert_eq!(Color::new(1.0, 2.0, 0.0), cropped[(0, 0)]);
+        assert_eq!(Color::new(3.0, 3.0, 0.0), cropped[(2, 1)]);
+    }
+
+    #[test]
+    fn cropping_past_the_edge() {
+        let c = numbered_canvas(5, 4);
+        let cropped = c.crop((3, 3), (10, 10));
+        assert_eq!((2, 1), cropped.dimensions);
+        assert_eq!(Color::new(4.0, 3.0, 0.0), cropped[(1, 0)]);
+
+        assert_eq!((0, 0), c.crop((6, 6), (2, 2)).dimensions);
+    }
+
+    #[test]
+    fn flipping() {
+        let c = numbered_canvas(3, 2);
+        let horizontal = c.flip_horizontal();
+        assert_eq!(Color::new(2.0, 0.0, 0.0), horizontal[(0, 0)]);
+        assert_eq!(Color::new(0.0, 1.0, 0.0), horizontal[(2, 1)]);
+
+        let vertical = c.flip_vertical();
+        assert_eq!(Color::new(0.0, 1.0, 0.0), vertical[(0, 0)]);
+        assert_eq!(Color::new(2.0, 0.0, 0.0), vertical[(2, 1)]);
+    }
+
+    #[test]
+    fn rotating() {
+        let c = numbered_canvas(3, 2);
+        let rotated = c.rotate90();
+        assert_eq!((2, 3), rotated.dimensions);
+        // The bottom left corner becomes the top left
+        assert_eq!(Color::new(0.0, 1.0, 0.0), rotated[(0, 0)]);
+        assert_eq!(Color::new(0.0, 0.0, 0.0), rotated[(1, 0)]);
+        assert_eq!(Color::new(2.0, 0.0, 0.0), rotated[(1, 2)]);
+
+        let full_turn = rotated.rotate90().rotate90().rotate90();
+        assert_eq!(c.as_slice(), full_turn.as_slice());
+    }
+
+    #[test]
+    fn resizing_nearest() {
+        let c = numbered_canvas(2, 2);
+        let resized = c.resize(4, 4, ResizeFilter::Nearest);
+        assert_eq!((4, 4), resized.dimensions);
+        assert_eq!(Color::new(0.0, 0.0, 0.0), resized[(1, 1)]);
+        assert_eq!(Color::new(1.0, 0.0, 0.0), resized[(2, 0)]);
+        assert_eq!(Color::new(1.0, 1.0, 0.0), resized[(3, 3)]);
+
+        let shrunk = numbered_canvas(4, 4).resize(2, 2, ResizeFilter::Nearest);
+        assert_eq!(Color::new(3.0, 3.0, 0.0), shrunk[(1, 1)]);
+    }
+
+    #[test]
+    fn resizing_bilinear() {
+        let c = numbered_canvas(2, 1);
+        let resized = c.resize(4, 1, ResizeFilter::Bilinear);
+        assert_eq!(Color::new(0.0, 0.0, 0.0), resized[(0, 0)]);
+        assert_eq!(Color::new(0.25, 0.0, 0.0), resized[(1, 0)]);
+        assert_eq!(Color::new(0.75, 0.0, 0.0), resized[(2, 0)]);
+        assert_eq!(Color::new(1.0, 0.0, 0.0), resized[(3, 0)]);
+
+        // Halving averages neighboring pixels
+        let shrunk = numbered_canvas(4, 1).resize(2, 1, ResizeFilter::Bilinear);
+        assert_eq!(Color::new(0.625, 0.0, 0.0), shrunk[(0, 0)]);
+    }
+
+    #[test]
+    fn resizing_preserves_flat_color() {
+        let color = Color::new(0.2, 0.4, 0.6);
+        let c = flat_canvas(7, 5, color);
+        for filter in &[
+            ResizeFilter::Nearest,
+            ResizeFilter::Bilinear,
+            ResizeFilter::Lanczos,
+        ] {
+            for (width, height) in &[(13, 11), (3, 2), (7, 5)] {
+                let resized = c.resize(*width, *height, *filter);
+                assert!(resized.as_slice().iter().all(|pixel| *pixel == color));
+            }
+        }
+    }
+
+    #[test]
+    fn lanczos_is_interpolating() {
+        for x in 1..3 {
+            assert_feq!(0.0, ResizeFilter::Lanczos.weight(x as Float));
+        }
+        assert_feq!(1.0, ResizeFilter::Lanczos.weight(0.0));
+        let c = numbered_canvas(6, 1);
+        assert_eq!(
+            c.as_slice(),
+            c.resize(6, 1, ResizeFilter::Lanczos).as_slice()
+        );
+    }
+
+    #[test]
+    fn blitting() {
+        let mut c = Canvas::new(3, 3);
+        let other = flat_canvas(2, 2, Color::white());
+        c.blit(&other, (2, 2), 1.0);
+        assert_eq!(Color::white(), c[(2, 2)]);
+        assert_eq!(Color::black(), c[(1, 2)]);
+
+        c.blit(&other, (-1, -1), 0.25);
+        assert_eq!(Color::new(0.25, 0.25, 0.25), c[(0, 0)]);
+        assert_eq!(Color::black(), c[(1, 1)]);
+    }
+
+    #[test]
+    fn blitting_with_alpha() {
+        let mut c = flat_canvas(2, 1, Color::new(0.0, 0.0, 1.0));
+        let mut other = Canvas::transparent(2, 1);
+        other.set_rgba((0, 0), Rgba::new(Color::new(1.0, 0.0, 0.0), 0.5));
+        c.blit(&other, (0, 0), 1.0);
+        assert!(!c.has_alpha());
+        assert_eq!(Color::new(0.5, 0.0, 0.5), c[(0, 0)]);
+        assert_eq!(Color::new(0.0, 0.0, 1.0), c[(1, 0)]);
+
+        // Onto a transparent canvas the coverage accumulates
+        let mut c = Canvas::transparent(1, 1);
+        c.blit(&other, (0, 0), 0.5);
+        assert_feq!(0.25, c.alpha((0, 0)));
+        assert_eq!(Color::new(1.0, 0.0, 0.0), c.rgba((0, 0)).unpremultiplied());
+    }
+
+    #[test]
+    fn transformations_keep_alpha() {
+        let mut c = Canvas::transparent(3, 2);
+        c.set_rgba((0, 1), Rgba::opaque(Color::white()));
+
+        assert!(!Canvas::new(3, 2).rotate90().has_alpha());
+        assert_feq!(1.0, c.flip_horizontal().alpha((2, 1)));
+        assert_feq!(1.0, c.flip_vertical().alpha((0, 0)));
+        assert_feq!(1.0, c.rotate90().alpha((0, 0)));
+        assert_feq!(1.0, c.crop((0, 1), (1, 1)).alpha((0, 0)));
+        assert_feq!(0.0, c.crop((1, 0), (2, 2)).alpha((0, 1)));
+
+        // Filtering premultiplied colors keeps transparent neighbors from darkening the edge
+        let resized = c.resize(6, 4, ResizeFilter::Bilinear);
+        assert_eq!(Color::white(), resized.rgba((1, 3)).unpremultiplied());
+        assert!(resized.alpha((1, 3)) < 1.0);
+    }
+
+    #[test]
+    fn canvases_with_no_height() {
+        let cropped = Canvas::new(5, 4).crop((0, 10), (2, 2));
+        assert_eq!((2, 0), cropped.dimensions);
+        assert_eq!((0, 2), cropped.rotate90().dimensions);
+
+        let mut c = Canvas::new(3, 3);
+        c.blit(&Canvas::new(2, 0), (0, 0), 1.0);
+        assert!(c.iter().all(|xy| c[xy] == Color::black()));
+    }
+}
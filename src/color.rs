@@ -1,4 +1,14 @@
-use crate::util;
+mod encoding;
+mod named;
+mod rgba;
+mod spaces;
+
+pub use encoding::*;
+pub use rgba::*;
+pub use spaces::*;
+
+use crate::simd::{scalar, Lanes};
+use crate::util::{self, Float};
 
 use std::ops::Add;
 use std::ops::Mul;
@@ -6,13 +16,13 @@ use std::ops::Sub;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Color {
-    red: f32,
-    green: f32,
-    blue: f32,
+    red: Float,
+    green: Float,
+    blue: Float,
 }
 
 impl Color {
-    pub fn new(red: f32, green: f32, blue: f32) -> Color {
+    pub fn new(red: Float, green: Float, blue: Float) -> Color {
         Color { red, green, blue }
     }
 
@@ -23,14 +33,20 @@ impl Color {
         Self::new(0.0, 0.0, 0.0)
     }
 
-    pub fn to_256(f: f32) -> u16 {
-        let mut f = f;
-        if f > 1.0 {
-            f = 1.0;
-        } else if f < 0.0 {
-            f = 0.0;
-        }
-        (255.0 * f).round() as u16
+    pub fn red(&self) -> Float {
+        self.red
+    }
+
+    pub fn green(&self) -> Float {
+        self.green
+    }
+
+    pub fn blue(&self) -> Float {
+        self.blue
+    }
+
+    pub fn to_256(f: Float) -> u16 {
+        (255.0 * f.clamp(0.0, 1.0)).round() as u16
     }
 
     pub fn ppm(&self) -> String {
@@ -42,6 +58,16 @@ impl Color {
         )
     }
 
+    #[inline]
+    fn lanes(&self) -> Lanes {
+        [self.red, self.green, self.blue, 0.0]
+    }
+
+    #[inline]
+    fn from_lanes([red, green, blue, _]: Lanes) -> Color {
+        Color { red, green, blue }
+    }
+
     pub fn ppm_parts(&self) -> Vec<String> {
         vec![
             Self::to_256(self.red).to_string(),
@@ -64,44 +90,36 @@ impl Eq for Color {}
 impl Add for Color {
     type Output = Color;
 
+    #[inline]
     fn add(self, rhs: Color) -> Color {
-        Color::new(
-            self.red + rhs.red,
-            self.green + rhs.green,
-            self.blue + rhs.blue,
-        )
+        Color::from_lanes(scalar::add(self.lanes(), rhs.lanes()))
     }
 }
 
 impl Sub for Color {
     type Output = Color;
 
+    #[inline]
     fn sub(self, rhs: Color) -> Color {
-        Color::new(
-            self.red - rhs.red,
-            self.green - rhs.green,
-            self.blue - rhs.blue,
-        )
+        Color::from_lanes(scalar::sub(self.lanes(), rhs.lanes()))
     }
 }
 
-impl Mul<f32> for Color {
+impl Mul<Float> for Color {
     type Output = Color;
 
-    fn mul(self, rhs: f32) -> Color {
-        Color::new(self.red * rhs, self.green * rhs, self.blue * rhs)
+    #[inline]
+    fn mul(self, rhs: Float) -> Color {
+        Color::from_lanes(scalar::scale(self.lanes(), rhs))
     }
 }
 
 impl Mul for Color {
     type Output = Color;
 
+    #[inline]
     fn mul(self, rhs: Color) -> Color {
-        Color::new(
-            self.red * rhs.red,
-            self.green * rhs.green,
-            self.blue * rhs.blue,
-        )
+        Color::from_lanes(scalar::mul(self.lanes(), rhs.lanes()))
     }
 }
 
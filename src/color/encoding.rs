@@ -0,0 +1,195 @@
+use super::*;
+use crate::Dither;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneMap {
+    Clamp,
+    Reinhard,
+    // Reinhard with a white point, the smallest value that maps to pure white
+    ReinhardExtended(Float),
+    AcesFilmic,
+}
+
+impl ToneMap {
+    pub fn apply(&self, color: Color) -> Color {
+        let map = |c: Float| {
+            // Negative light has no meaning on a display
+            let c = c.max(0.0);
+            match *self {
+                ToneMap::Clamp => c.min(1.0),
+                ToneMap::Reinhard => c / (1.0 + c),
+                ToneMap::ReinhardExtended(white) => {
+                    (c * (1.0 + c / (white * white)) / (1.0 + c)).min(1.0)
+                }
+                // Krzysztof Narkowicz's fit of the ACES reference rendering transform
+                ToneMap::AcesFilmic => {
+                    let mapped = (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14);
+                    mapped.clamp(0.0, 1.0)
+                }
+            }
+        };
+        Color::new(map(color.red), map(color.green), map(color.blue))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Transfer {
+    Linear,
+    Srgb,
+}
+
+// How linear colors become display values, for the 8-bit writers that take one like
+// write_png_encoded and write_ppm_encoded. The default, which the plain writers use, keeps the
+// book's behavior of clamping linear values with no gamma or dithering, so sRGB is opt-in
+// through Encoding::srgb() or the transfer field.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Encoding {
+    // Stops of exposure, each stop doubles the light before tone mapping
+    pub exposure: Float,
+    pub tone_map: ToneMap,
+    pub transfer: Transfer,
+    pub dither: Dither,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding {
+            exposure: 0.0,
+            tone_map: ToneMap::Clamp,
+            transfer: Transfer::Linear,
+            dither: Dither::None,
+        }
+    }
+}
+
+impl Encoding {
+    pub fn srgb() -> Encoding {
+        Encoding {
+            transfer: Transfer::Srgb,
+            ..Encoding::default()
+        }
+    }
+
+    pub fn encode(&self, color: Color) -> Color {
+        let exposed = color * (2.0 as Float).powf(self.exposure);
+        let mapped = self.tone_map.apply(exposed);
+        match self.transfer {
+            Transfer::Linear => mapped,
+            Transfer::Srgb => mapped.to_srgb(),
+        }
+    }
+}
+
+impl Color {
+    pub fn linear_to_srgb(f: Float) -> Float {
+        if f <= 0.003_130_8 {
+            f * 12.92
+        } else {
+            1.055 * f.powf(1.0 / 2.4) - 0.055
+        }
+    }
+
+    pub fn srgb_to_linear(f: Float) -> Float {
+        if f <= 0.040_45 {
+            f / 12.92
+        } else {
+            ((f + 0.055) / 1.055).powf(2.4)
+        }
+    }
+
+    pub fn to_srgb(&self) -> Color {
+        Color::new(
+            Self::linear_to_srgb(self.red),
+            Self::linear_to_srgb(self.green),
+            Self::linear_to_srgb(self.blue),
+        )
+    }
+
+    // Decodes a color holding sRGB values back to linear light, the inverse of to_srgb
+    pub fn to_linear(&self) -> Color {
+        Color::new(
+            Self::srgb_to_linear(self.red),
+            Self::srgb_to_linear(self.green),
+            Self::srgb_to_linear(self.blue),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn srgb_transfer() {
+        assert_feq!(0.0, Color::linear_to_srgb(0.0));
+        assert_feq!(1.0, Color::linear_to_srgb(1.0));
+        assert_feq!(
+            1.055 * (0.5 as Float).powf(1.0 / 2.4) - 0.055,
+            Color::linear_to_srgb(0.5)
+        );
+        assert_feq!(0.012_92, Color::linear_to_srgb(0.001));
+        assert_feq!(0.214_041_14, Color::srgb_to_linear(0.5));
+
+        let c = Color::new(0.0, 0.2, 0.9);
+        assert_eq!(c, c.to_srgb().to_linear());
+    }
+
+    #[test]
+    fn clamp_tone_map() {
+        let c = Color::new(-0.5, 0.5, 1.5);
+        assert_eq!(Color::new(0.0, 0.5, 1.0), ToneMap::Clamp.apply(c));
+    }
+
+    #[test]
+    fn reinhard_tone_map() {
+        let c = Color::new(0.0, 1.0, 3.0);
+        assert_eq!(Color::new(0.0, 0.5, 0.75), ToneMap::Reinhard.apply(c));
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_point_to_one() {
+        let tone_map = ToneMap::ReinhardExtended(4.0);
+        assert_eq!(Color::white(), tone_map.apply(Color::new(4.0, 4.0, 4.0)));
+        assert_eq!(Color::white(), tone_map.apply(Color::new(9.0, 9.0, 9.0)));
+        assert_feq!(0.53125, tone_map.apply(Color::white()).red);
+    }
+
+    #[test]
+    fn aces_filmic_tone_map() {
+        let tone_map = ToneMap::AcesFilmic;
+        assert_eq!(Color::black(), tone_map.apply(Color::black()));
+        assert_feq!(2.54 / 3.16, tone_map.apply(Color::white()).red);
+        assert_eq!(
+            Color::white(),
+            tone_map.apply(Color::new(100.0, 100.0, 100.0))
+        );
+    }
+
+    #[test]
+    fn exposure_scales_by_stops() {
+        let encoding = Encoding {
+            exposure: 1.0,
+            ..Encoding::default()
+        };
+        assert_eq!(
+            Color::new(0.5, 1.0, 1.0),
+            encoding.encode(Color::new(0.25, 0.5, 0.75))
+        );
+
+        let encoding = Encoding {
+            exposure: -2.0,
+            ..Encoding::default()
+        };
+        assert_eq!(
+            Color::new(0.25, 0.5, 0.0),
+            encoding.encode(Color::new(1.0, 2.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn default_encoding_matches_book_ppm() {
+        let c = Color::new(1.5, 0.2, -0.4);
+        assert_eq!(c.ppm(), Encoding::default().encode(c).ppm());
+        assert_eq!("255 124 0", Encoding::srgb().encode(c).ppm());
+    }
+}
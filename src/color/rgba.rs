@@ -0,0 +1,175 @@
+use super::*;
+
+// A color with coverage, stored premultiplied: the color has already been scaled by alpha, so
+// a fully transparent pixel is always black and compositing is a few multiplies and adds.
+#[derive(Debug, Copy, Clone)]
+pub struct Rgba {
+    color: Color,
+    alpha: Float,
+}
+
+impl Rgba {
+    // Takes an unpremultiplied (straight) color
+    pub fn new(color: Color, alpha: Float) -> Rgba {
+        Rgba {
+            color: color * alpha,
+            alpha,
+        }
+    }
+
+    pub fn from_premultiplied(color: Color, alpha: Float) -> Rgba {
+        Rgba { color, alpha }
+    }
+
+    pub fn opaque(color: Color) -> Rgba {
+        Self::from_premultiplied(color, 1.0)
+    }
+
+    pub fn transparent() -> Rgba {
+        Self::from_premultiplied(Color::black(), 0.0)
+    }
+
+    pub fn premultiplied(&self) -> Color {
+        self.color
+    }
+
+    pub fn alpha(&self) -> Float {
+        self.alpha
+    }
+
+    // The straight color, black when there's no coverage to divide by
+    pub fn unpremultiplied(&self) -> Color {
+        if self.alpha > 0.0 {
+            self.color * (1.0 / self.alpha)
+        } else {
+            Color::black()
+        }
+    }
+
+    // Porter-Duff over: self in front of below
+    pub fn over(self, below: Rgba) -> Rgba {
+        Rgba {
+            color: self.color + below.color * (1.0 - self.alpha),
+            alpha: self.alpha + below.alpha * (1.0 - self.alpha),
+        }
+    }
+
+    // self behind above
+    pub fn under(self, above: Rgba) -> Rgba {
+        above.over(self)
+    }
+
+    // The multiply blend mode: where both layers are opaque the colors are multiplied, and
+    // where only one is, that layer shows through as it would with over
+    pub fn multiply(self, other: Rgba) -> Rgba {
+        Rgba {
+            color: self.color * other.color
+                + self.color * (1.0 - other.alpha)
+                + other.color * (1.0 - self.alpha),
+            alpha: self.alpha + other.alpha - self.alpha * other.alpha,
+        }
+    }
+
+    pub fn scale_alpha(self, amount: Float) -> Rgba {
+        Rgba {
+            color: self.color * amount,
+            alpha: self.alpha * amount,
+        }
+    }
+}
+
+// Sums the light of both layers, e.g. for glows, with coverage capped at fully opaque
+impl Add for Rgba {
+    type Output = Rgba;
+
+    fn add(self, rhs: Rgba) -> Rgba {
+        Rgba {
+            color: self.color + rhs.color,
+            alpha: (self.alpha + rhs.alpha).min(1.0),
+        }
+    }
+}
+
+impl From<Color> for Rgba {
+    fn from(color: Color) -> Self {
+        Rgba::opaque(color)
+    }
+}
+
+// Approximate like Color's, so that values that went through premultiplying still compare equal
+impl PartialEq for Rgba {
+    fn eq(&self, other: &Rgba) -> bool {
+        self.color == other.color && util::feq(self.alpha, other.alpha)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn premultiplying() {
+        let c = Rgba::new(Color::new(1.0, 0.5, 0.0), 0.5);
+        assert_eq!(Color::new(0.5, 0.25, 0.0), c.premultiplied());
+        assert_eq!(Color::new(1.0, 0.5, 0.0), c.unpremultiplied());
+        assert_eq!(Color::black(), Rgba::transparent().unpremultiplied());
+    }
+
+    #[test]
+    fn compositing_over() {
+        let red = Rgba::new(Color::new(1.0, 0.0, 0.0), 0.5);
+        let blue = Rgba::opaque(Color::new(0.0, 0.0, 1.0));
+        let result = red.over(blue);
+        assert_eq!(Color::new(0.5, 0.0, 0.5), result.premultiplied());
+        assert_feq!(1.0, result.alpha());
+        assert_eq!(result, blue.under(red));
+
+        // Transparent pixels leave the other layer untouched
+        assert_eq!(blue, Rgba::transparent().over(blue));
+        assert_eq!(red, red.over(Rgba::transparent()));
+    }
+
+    #[test]
+    fn compositing_two_translucent_layers() {
+        let a = Rgba::new(Color::white(), 0.5);
+        let b = Rgba::new(Color::white(), 0.5);
+        let result = a.over(b);
+        assert_feq!(0.75, result.alpha());
+        assert_eq!(Color::white(), result.unpremultiplied());
+    }
+
+    #[test]
+    fn adding() {
+        let a = Rgba::new(Color::new(0.5, 0.0, 0.0), 0.6);
+        let b = Rgba::new(Color::new(0.0, 1.0, 0.0), 0.6);
+        let result = a + b;
+        assert_eq!(Color::new(0.3, 0.6, 0.0), result.premultiplied());
+        assert_feq!(1.0, result.alpha());
+    }
+
+    #[test]
+    fn multiplying() {
+        let a = Rgba::opaque(Color::new(0.5, 1.0, 1.0));
+        let b = Rgba::opaque(Color::new(1.0, 0.5, 0.2));
+        assert_eq!(Rgba::opaque(Color::new(0.5, 0.5, 0.2)), a.multiply(b));
+
+        // Multiplying with nothing keeps the layer
+        assert_eq!(a, a.multiply(Rgba::transparent()));
+    }
+
+    #[test]
+    fn approximately_equal() {
+        let (a, b): (Float, Float) = (0.1, 0.2);
+        let c = Rgba::new(Color::new(0.2, 0.4, 0.6), a + b);
+        assert_eq!(Rgba::new(Color::new(0.2, 0.4, 0.6), 0.3), c);
+        assert_eq!(c, Rgba::new(c.unpremultiplied(), c.alpha()));
+        assert_ne!(Rgba::new(Color::new(0.2, 0.4, 0.6), 0.4), c);
+    }
+
+    #[test]
+    fn scaling_alpha() {
+        let c = Rgba::new(Color::new(0.2, 0.4, 0.6), 0.5).scale_alpha(0.5);
+        assert_feq!(0.25, c.alpha());
+        assert_eq!(Color::new(0.2, 0.4, 0.6), c.unpremultiplied());
+    }
+}
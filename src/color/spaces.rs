@@ -0,0 +1,373 @@
+use super::*;
+
+// D65 reference white, which sRGB is defined against
+const WHITE_X: Float = 0.950_47;
+const WHITE_Y: Float = 1.0;
+const WHITE_Z: Float = 1.088_83;
+const LAB_EPSILON: Float = 6.0 / 29.0;
+
+// The range of the Planckian locus fit used by from_kelvin
+const MIN_KELVIN: Float = 1667.0;
+const MAX_KELVIN: Float = 25000.0;
+
+// Hue is in degrees from 0 up to 360, saturation and value from 0 to 1
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hsv {
+    pub hue: Float,
+    pub saturation: Float,
+    pub value: Float,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hsl {
+    pub hue: Float,
+    pub saturation: Float,
+    pub lightness: Float,
+}
+
+// CIE 1931 tristimulus values, with Y being the luminance
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Xyz {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+// CIE L*a*b* relative to D65, lightness going from 0 to 100
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Lab {
+    pub l: Float,
+    pub a: Float,
+    pub b: Float,
+}
+
+// Colors are linear light. HSV, HSL and hex are how people pick colors on screen, so those
+// conversions go through the sRGB encoding on the way in and out.
+impl Color {
+    // Relative luminance of linear Rec. 709/sRGB primaries
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    pub fn to_hsv(&self) -> Hsv {
+        let (hue, max, min) = hue_max_min(self.to_srgb());
+        Hsv {
+            hue,
+            saturation: if max > 0.0 { (max - min) / max } else { 0.0 },
+            value: max,
+        }
+    }
+
+    pub fn from_hsv(hsv: Hsv) -> Color {
+        let chroma = hsv.value * hsv.saturation;
+        from_hue(hsv.hue, chroma, hsv.value - chroma)
+    }
+
+    pub fn to_hsl(&self) -> Hsl {
+        let (hue, max, min) = hue_max_min(self.to_srgb());
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+        Hsl {
+            hue,
+            saturation,
+            lightness,
+        }
+    }
+
+    pub fn from_hsl(hsl: Hsl) -> Color {
+        let chroma = (1.0 - (2.0 * hsl.lightness - 1.0).abs()) * hsl.saturation;
+        from_hue(hsl.hue, chroma, hsl.lightness - chroma / 2.0)
+    }
+
+    pub fn to_xyz(&self) -> Xyz {
+        let (r, g, b) = (self.red, self.green, self.blue);
+        Xyz {
+            x: 0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b,
+            y: 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b,
+            z: 0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b,
+        }
+    }
+
+    pub fn from_xyz(xyz: Xyz) -> Color {
+        let Xyz { x, y, z } = xyz;
+        Color::new(
+            3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+            -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+            0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+        )
+    }
+
+    pub fn to_lab(&self) -> Lab {
+        let Xyz { x, y, z } = self.to_xyz();
+        let (fx, fy, fz) = (lab_f(x / WHITE_X), lab_f(y / WHITE_Y), lab_f(z / WHITE_Z));
+        Lab {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+
+    pub fn from_lab(lab: Lab) -> Color {
+        let fy = (lab.l + 16.0) / 116.0;
+        let fx = fy + lab.a / 500.0;
+        let fz = fy - lab.b / 200.0;
+        Self::from_xyz(Xyz {
+            x: WHITE_X * lab_f_inverse(fx),
+            y: WHITE_Y * lab_f_inverse(fy),
+            z: WHITE_Z * lab_f_inverse(fz),
+        })
+    }
+
+    // Parses #rgb or #rrggbb, with or without the #
+    pub fn from_hex(hex: &str) -> Result<Color, String> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        let parse = |s: &str| {
+            u8::from_str_radix(s, 16).map_err(|_| format!("{:?} is not a hex color", hex))
+        };
+        let (r, g, b) = match digits.len() {
+            3 if digits.is_ascii() => {
+                let r = parse(&digits[0..1])?;
+                let g = parse(&digits[1..2])?;
+                let b = parse(&digits[2..3])?;
+                (r * 17, g * 17, b * 17)
+            }
+            6 if digits.is_ascii() => (
+                parse(&digits[0..2])?,
+                parse(&digits[2..4])?,
+                parse(&digits[4..6])?,
+            ),
+            _ => return Err(format!("{:?} is not a hex color", hex)),
+        };
+        Ok(Self::from_srgb_bytes(r, g, b))
+    }
+
+    pub fn to_hex(&self) -> String {
+        let srgb = self.to_srgb();
+        format!(
+            "#{:02x}{:02x}{:02x}",
+            Self::to_256(srgb.red),
+            Self::to_256(srgb.green),
+            Self::to_256(srgb.blue)
+        )
+    }
+
+    pub(crate) fn from_srgb_bytes(r: u8, g: u8, b: u8) -> Color {
+        Color::new(r as Float / 255.0, g as Float / 255.0, b as Float / 255.0).to_linear()
+    }
+
+    // The color of a black body at the given temperature, from Kim et al.'s fit of the
+    // Planckian locus, scaled so the brightest channel is 1. Temperatures outside 1667K to
+    // 25000K are clamped to that range.
+    pub fn from_kelvin(temperature: Float) -> Color {
+        let t = temperature.clamp(MIN_KELVIN, MAX_KELVIN);
+        let (t2, t3) = (t * t, t * t * t);
+        let x = if t <= 4000.0 {
+            -0.266_123_9e9 / t3 - 0.234_358_9e6 / t2 + 0.877_695_6e3 / t + 0.179_910
+        } else {
+            -3.025_846_9e9 / t3 + 2.107_038e6 / t2 + 0.222_634_7e3 / t + 0.240_390
+        };
+        let (x2, x3) = (x * x, x * x * x);
+        let y = if t <= 2222.0 {
+            -1.106_381_4 * x3 - 1.348_110_2 * x2 + 2.185_558_3 * x - 0.202_196_83
+        } else if t <= 4000.0 {
+            -0.954_947_6 * x3 - 1.374_185_9 * x2 + 2.091_37 * x - 0.167_488_67
+        } else {
+            3.081_758 * x3 - 5.873_387 * x2 + 3.751_13 * x - 0.370_014_83
+        };
+
+        let color = Self::from_xyz(Xyz {
+            x: x / y,
+            y: 1.0,
+            z: (1.0 - x - y) / y,
+        });
+        // The reddest temperatures are slightly outside the sRGB gamut
+        let color = Color::new(
+            color.red.max(0.0),
+            color.green.max(0.0),
+            color.blue.max(0.0),
+        );
+        color * (1.0 / color.red.max(color.green).max(color.blue))
+    }
+}
+
+// The hue in degrees along with the largest and smallest channels
+fn hue_max_min(c: Color) -> (Float, Float, Float) {
+    let max = c.red.max(c.green).max(c.blue);
+    let min = c.red.min(c.green).min(c.blue);
+    let delta = max - min;
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == c.red {
+        60.0 * ((c.green - c.blue) / delta).rem_euclid(6.0)
+    } else if max == c.green {
+        60.0 * ((c.blue - c.red) / delta + 2.0)
+    } else {
+        60.0 * ((c.red - c.green) / delta + 4.0)
+    };
+    (hue, max, min)
+}
+
+// Builds the sRGB encoded color for a hue and chroma, adding m to every channel, and returns
+// it as linear
+fn from_hue(hue: Float, chroma: Float, m: Float) -> Color {
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Color::new(r + m, g + m, b + m).to_linear()
+}
+
+fn lab_f(t: Float) -> Float {
+    if t > LAB_EPSILON.powi(3) {
+        t.cbrt()
+    } else {
+        t / (3.0 * LAB_EPSILON * LAB_EPSILON) + 4.0 / 29.0
+    }
+}
+
+fn lab_f_inverse(t: Float) -> Float {
+    if t > LAB_EPSILON {
+        t.powi(3)
+    } else {
+        3.0 * LAB_EPSILON * LAB_EPSILON * (t - 4.0 / 29.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(expected: Float, actual: Float, tolerance: Float) {
+        assert!(
+            (expected - actual).abs() <= tolerance,
+            "expected {} but got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn luminance() {
+        assert_feq!(1.0, Color::white().luminance());
+        assert_feq!(0.7152, Color::new(0.0, 1.0, 0.0).luminance());
+    }
+
+    #[test]
+    fn hsv() {
+        let orange = Color::from_hex("#ff8000").unwrap();
+        let hsv = orange.to_hsv();
+        assert_close(30.0, hsv.hue, 0.2);
+        assert_feq!(1.0, hsv.saturation);
+        assert_feq!(1.0, hsv.value);
+        assert_eq!(orange, Color::from_hsv(hsv));
+
+        let gray = Color::new(0.2, 0.2, 0.2).to_hsv();
+        assert_eq!(0.0, gray.hue);
+        assert_eq!(0.0, gray.saturation);
+
+        let blue = Hsv {
+            hue: 240.0,
+            saturation: 1.0,
+            value: 1.0,
+        };
+        assert_eq!(Color::new(0.0, 0.0, 1.0), Color::from_hsv(blue));
+    }
+
+    #[test]
+    fn hsl() {
+        let c = Color::from_hex("#408080").unwrap();
+        let hsl = c.to_hsl();
+        assert_close(180.0, hsl.hue, 0.01);
+        assert_close(0.333, hsl.saturation, 0.01);
+        assert_close(0.376, hsl.lightness, 0.01);
+        assert_eq!(c, Color::from_hsl(hsl));
+
+        let red = Hsl {
+            hue: 360.0,
+            saturation: 1.0,
+            lightness: 0.5,
+        };
+        assert_eq!(Color::new(1.0, 0.0, 0.0), Color::from_hsl(red));
+    }
+
+    #[test]
+    fn xyz() {
+        let white = Color::white().to_xyz();
+        assert_close(WHITE_X, white.x, 0.0001);
+        assert_close(WHITE_Y, white.y, 0.0001);
+        assert_close(WHITE_Z, white.z, 0.0001);
+
+        let c = Color::new(0.2, 0.5, 0.9);
+        let round_trip = Color::from_xyz(c.to_xyz());
+        assert_close(c.red(), round_trip.red(), 0.0001);
+        assert_close(c.blue(), round_trip.blue(), 0.0001);
+    }
+
+    #[test]
+    fn lab() {
+        let white = Color::white().to_lab();
+        assert_close(100.0, white.l, 0.01);
+        assert_close(0.0, white.a, 0.01);
+        assert_close(0.0, white.b, 0.01);
+
+        // sRGB red from the CIE reference tables
+        let red = Color::new(1.0, 0.0, 0.0).to_lab();
+        assert_close(53.24, red.l, 0.05);
+        assert_close(80.09, red.a, 0.05);
+        assert_close(67.20, red.b, 0.05);
+
+        let dark = Color::new(0.001, 0.002, 0.0005);
+        let round_trip = Color::from_lab(dark.to_lab());
+        assert_close(dark.green(), round_trip.green(), 0.0001);
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(Color::white(), Color::from_hex("#fff").unwrap());
+        assert_eq!(
+            Color::new(1.0, 0.0, 0.0),
+            Color::from_hex("FF0000").unwrap()
+        );
+        assert_eq!(
+            Color::new(0.0, Color::srgb_to_linear(128.0 / 255.0), 0.0),
+            Color::from_hex("#008000").unwrap()
+        );
+        assert_eq!("#ff8800", Color::from_hex("#ff8800").unwrap().to_hex());
+
+        assert!(Color::from_hex("#ff88").is_err());
+        assert!(Color::from_hex("#gg0000").is_err());
+        assert!(Color::from_hex("#ff€00").is_err());
+    }
+
+    #[test]
+    fn color_temperature() {
+        // Around 6500K the locus passes just on the magenta side of the D65 white point
+        let daylight = Color::from_kelvin(6504.0);
+        assert_feq!(1.0, daylight.red());
+        assert_close(1.0, daylight.green(), 0.08);
+        assert_close(1.0, daylight.blue(), 0.08);
+
+        // Warm white, a few steps from the (255, 180, 107) usually published for 3000K
+        assert_eq!("#ffb86d", Color::from_kelvin(3000.0).to_hex());
+
+        let candle = Color::from_kelvin(1900.0);
+        assert_feq!(1.0, candle.red());
+        assert!(candle.green() < 0.5 && candle.blue() < candle.green());
+
+        let sky = Color::from_kelvin(15000.0);
+        assert_feq!(1.0, sky.blue());
+        assert!(sky.red() < sky.green());
+
+        assert_eq!(Color::from_kelvin(MAX_KELVIN), Color::from_kelvin(40000.0));
+    }
+}
@@ -8,7 +8,7 @@ pub struct DimensionalIterator {
 impl DimensionalIterator {
     pub fn canvas(dimensions: (usize, usize)) -> DimensionalIterator {
         DimensionalIterator {
-            dimensions: dimensions,
+            dimensions,
             position: (0, 0),
             first_pass: true,
             dimensions_reversed: false,
@@ -17,7 +17,7 @@ impl DimensionalIterator {
 
     pub fn matrix(dimensions: (usize, usize)) -> DimensionalIterator {
         DimensionalIterator {
-            dimensions: dimensions,
+            dimensions,
             position: (0, 0),
             first_pass: true,
             dimensions_reversed: true,
@@ -27,9 +27,9 @@ impl DimensionalIterator {
 
 fn reverse_tuple(tuple: (usize, usize), reverse: bool) -> (usize, usize) {
     if reverse {
-        return (tuple.1, tuple.0);
+        (tuple.1, tuple.0)
     } else {
-        return tuple;
+        tuple
     }
 }
 
@@ -41,7 +41,12 @@ impl Iterator for DimensionalIterator {
 
         let (x, y) = self.position;
 
-        if self.first_pass && x < width {
+        // Nothing to visit when either side is empty, not even the first position
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        if self.first_pass {
             self.first_pass = false;
             return Some(reverse_tuple(self.position, self.dimensions_reversed));
         }
@@ -54,7 +59,7 @@ impl Iterator for DimensionalIterator {
             return None;
         }
 
-        return Some(reverse_tuple(self.position, self.dimensions_reversed));
+        Some(reverse_tuple(self.position, self.dimensions_reversed))
     }
 }
 
@@ -105,4 +110,11 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn iterate_nothing() {
+        assert_eq!(None, DimensionalIterator::canvas((3, 0)).next());
+        assert_eq!(None, DimensionalIterator::canvas((0, 3)).next());
+        assert_eq!(None, DimensionalIterator::matrix((0, 2)).next());
+    }
 }
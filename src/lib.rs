@@ -6,11 +6,19 @@ mod color;
 mod iterator;
 mod matrix;
 mod ray;
+mod sampler;
+pub mod simd;
 mod space;
+mod spectrum;
 mod util;
+mod world;
 
 pub use canvas::*;
 pub use color::*;
 pub use matrix::*;
 pub use ray::*;
+pub use sampler::*;
 pub use space::*;
+pub use spectrum::*;
+pub use util::{consts, Float};
+pub use world::*;
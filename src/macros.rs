@@ -5,7 +5,7 @@ macro_rules! matrix {
             $($x:expr),+
         );* $(;)?
     ) => (
-        crate::Matrix::with_values(
+        $crate::Matrix::with_values(
             vec![$(
                 vec![$($x),*],
             )*]
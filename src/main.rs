@@ -1,5 +1,3 @@
-use raytracer;
-
 fn main() {
     println!("Hello, world!");
     let c = raytracer::Color::new(0.5, 1.0, 1.0);
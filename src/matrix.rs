@@ -1,12 +1,25 @@
+mod decompose;
+mod error;
+mod lu;
+mod matrix4;
 mod ops;
+mod quaternion;
+mod transform;
 mod transformations;
 
-pub use ops::*;
+pub use decompose::*;
+pub use error::*;
+pub use lu::*;
+pub use matrix4::*;
+pub use quaternion::*;
+pub use transform::*;
 pub use transformations::*;
 
+use crate::util::Float;
+
 #[derive(Debug, Clone)]
 pub struct Matrix {
     height: usize,
     width: usize,
-    values: Vec<Vec<f32>>,
+    values: Vec<Vec<Float>>,
 }
@@ -0,0 +1,231 @@
+use super::*;
+use crate::space::Vector;
+use crate::util;
+
+use std::convert::TryFrom;
+
+// An affine transform split into the parts it was built from, applied in the order scale, shear,
+// rotation and then translation. Shears are named like Shear, xy is how far x moves in
+// proportion to y, and a reflection shows up as a negative scale.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Decomposition {
+    pub translation: Vector,
+    pub rotation: Quaternion,
+    pub scale: Vector,
+    pub xy: Float,
+    pub xz: Float,
+    pub yz: Float,
+}
+
+impl Decomposition {
+    pub fn recompose(&self) -> Matrix4 {
+        let Vector(tx, ty, tz) = self.translation;
+        let Vector(sx, sy, sz) = self.scale;
+        let shear = Matrix4::new([
+            [1.0, self.xy, self.xz, 0.0],
+            [0.0, 1.0, self.yz, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Matrix4::translation(tx, ty, tz)
+            * self.rotation.to_matrix4()
+            * shear
+            * Matrix4::scale(sx, sy, sz)
+    }
+
+    // Blends between two keyframes, t = 0 gives self and t = 1 gives other. Rotations are
+    // slerped so objects turn at a steady rate instead of shrinking through the middle.
+    pub fn interpolate<T: Into<Float>>(&self, other: &Decomposition, t: T) -> Decomposition {
+        let t = t.into();
+        let lerp = |a: Float, b: Float| a + (b - a) * t;
+        Decomposition {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+            xy: lerp(self.xy, other.xy),
+            xz: lerp(self.xz, other.xz),
+            yz: lerp(self.yz, other.yz),
+        }
+    }
+}
+
+impl Matrix4 {
+    // Splits the upper 3x3 into a rotation times an upper triangular matrix with Gram-Schmidt on
+    // its columns, then the triangle into shear and scale
+    pub fn decompose(&self) -> Result<Decomposition, MatrixError> {
+        let m = self;
+        let affine = [0.0, 0.0, 0.0, 1.0]
+            .iter()
+            .enumerate()
+            .all(|(x, expected)| util::feq(m[(3, x)], *expected));
+        if !affine {
+            return Err(MatrixError::NotAffine);
+        }
+
+        let column = |x: usize| Vector::new(m[(0, x)], m[(1, x)], m[(2, x)]);
+        let (c0, c1, c2) = (column(0), column(1), column(2));
+        // Whether a scale is nothing but rounding error depends on how big the columns it came
+        // from were, so a uniformly tiny transform still decomposes. NaN counts as negligible too.
+        let negligible =
+            |scale: Float, size: Float| scale.is_nan() || scale <= size * util::THRESHOLD;
+        let size = c0.magnitude().max(c1.magnitude()).max(c2.magnitude());
+
+        let sx = c0.magnitude();
+        if negligible(sx, size) {
+            return Err(MatrixError::NotInvertible);
+        }
+        let q0 = c0 / sx;
+
+        let r01 = q0.dot(&c1);
+        let sy = (c1 - q0 * r01).magnitude();
+        if negligible(sy, c1.magnitude()) {
+            return Err(MatrixError::NotInvertible);
+        }
+        let q1 = (c1 - q0 * r01) / sy;
+
+        let (r02, r12) = (q0.dot(&c2), q1.dot(&c2));
+        let remainder = c2 - q0 * r02 - q1 * r12;
+        let sz = remainder.magnitude();
+        if negligible(sz, c2.magnitude()) {
+            return Err(MatrixError::NotInvertible);
+        }
+        let q2 = remainder / sz;
+
+        // A reflection can't be a rotation, negating every column and scale turns it into one
+        // while leaving the shear ratios alone
+        let (mut scale, mut columns) = (Vector::new(sx, sy, sz), [q0, q1, q2]);
+        if q0.dot(&q1.cross(&q2)) < 0.0 {
+            scale = scale.negate();
+            columns = [q0.negate(), q1.negate(), q2.negate()];
+        }
+        let mut rotation = Matrix4::identity();
+        for (x, Vector(a, b, c)) in columns.iter().enumerate() {
+            rotation[(0, x)] = *a;
+            rotation[(1, x)] = *b;
+            rotation[(2, x)] = *c;
+        }
+
+        Ok(Decomposition {
+            translation: Vector::new(m[(0, 3)], m[(1, 3)], m[(2, 3)]),
+            rotation: Quaternion::from_rotation(&rotation),
+            scale,
+            xy: r01 / sy,
+            xz: r02 / sz,
+            yz: r12 / sz,
+        })
+    }
+}
+
+impl Matrix {
+    pub fn decompose(&self) -> Result<Decomposition, MatrixError> {
+        Matrix4::try_from(self)?.decompose()
+    }
+
+    pub fn recompose(decomposition: &Decomposition) -> Matrix {
+        decomposition.recompose().into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::consts::PI;
+
+    #[test]
+    fn separates_each_part() {
+        let m = Matrix::translation(1.0, 2.0, 3.0)
+            * Matrix::rotation_y(PI / 2.0)
+            * Matrix::shear(Shear::XY)
+            * Matrix::scale(2.0, 3.0, 4.0);
+        let d = m.unwrap().decompose().unwrap();
+        assert_eq!(Vector::new(1.0, 2.0, 3.0), d.translation);
+        assert_eq!(
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), PI / 2.0),
+            d.rotation
+        );
+        assert_eq!(Vector::new(2.0, 3.0, 4.0), d.scale);
+        assert_feq!(1.0, d.xy);
+        assert_feq!(0.0, d.xz);
+        assert_feq!(0.0, d.yz);
+    }
+
+    #[test]
+    fn recomposes_to_the_same_matrix() {
+        let transforms = [
+            Transform::identity().matrix(),
+            Transform::identity()
+                .scale(0.5, 2.0, 1.5)
+                .shear(Shear::YZ)
+                .shear(Shear::XZ)
+                .rotate(Quaternion::from_euler(0.4, -0.8, 2.5))
+                .translate(-3.0, 0.25, 8.0)
+                .matrix(),
+            // Shears in the other direction end up split between rotation and shear
+            Matrix4::shear(Shear::ZX) * Matrix4::rotation_x(1.0),
+            // A mirror image comes back as negative scale
+            Matrix4::scale(-1.0, 1.0, 1.0) * Matrix4::rotation_z(0.3),
+        ];
+        for m in transforms.iter() {
+            let d = m.decompose().unwrap();
+            assert_eq!(*m, d.recompose());
+        }
+
+        let m = Matrix::rotation_axis(Vector::new(1.0, 2.0, 3.0), 2.0);
+        assert_eq!(m, Matrix::recompose(&m.decompose().unwrap()));
+    }
+
+    #[test]
+    fn interpolating_keyframes() {
+        let from = Matrix4::identity().decompose().unwrap();
+        let to = Transform::identity()
+            .uniform_scale(3.0)
+            .rotate_z(PI / 2.0)
+            .translate(4.0, 0.0, 0.0)
+            .matrix()
+            .decompose()
+            .unwrap();
+        assert_eq!(from, from.interpolate(&to, 0.0));
+        assert_eq!(to, from.interpolate(&to, 1.0));
+
+        let halfway = Transform::identity()
+            .uniform_scale(2.0)
+            .rotate_z(PI / 4.0)
+            .translate(2.0, 0.0, 0.0)
+            .matrix();
+        assert_eq!(halfway, from.interpolate(&to, 0.5).recompose());
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_transform() {
+        assert_eq!(
+            Err(MatrixError::NotInvertible),
+            Matrix4::scale(1.0, 0.0, 1.0).decompose()
+        );
+
+        let mut projection = Matrix4::identity();
+        projection[(3, 2)] = 1.0;
+        assert_eq!(Err(MatrixError::NotAffine), projection.decompose());
+
+        assert!(Matrix::new(3, 3).decompose().is_err());
+
+        // The second column is the first one scaled, however big they both are
+        let mut flattened = Matrix4::scale(1e6, 1e6, 1e6);
+        flattened[(0, 1)] = 2e6;
+        flattened[(1, 1)] = 0.0;
+        assert_eq!(Err(MatrixError::NotInvertible), flattened.decompose());
+    }
+
+    #[test]
+    fn tiny_transforms_decompose() {
+        let m = Matrix4::translation(1.0, 2.0, 3.0)
+            * Matrix4::rotation_y(PI / 3.0)
+            * Matrix4::scale(1e-6, 2e-6, 3e-6);
+        let d = m.decompose().unwrap();
+        assert_eq!(Vector::new(1.0, 2.0, 3.0), d.scale * 1e6);
+        assert_eq!(
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), PI / 3.0),
+            d.rotation
+        );
+        assert_eq!(Vector::new(1.0, 2.0, 3.0), d.translation);
+    }
+}
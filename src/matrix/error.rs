@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::fmt;
+
+// Dimensions are (height, width) and indexes (y, x), like everywhere else in Matrix
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MatrixError {
+    // The width of the left matrix doesn't match the height of the right one
+    DimensionMismatch {
+        left: (usize, usize),
+        right: (usize, usize),
+    },
+    NotSquare((usize, usize)),
+    NotInvertible,
+    // The bottom row isn't 0 0 0 1, so it's a projection rather than a transform of space
+    NotAffine,
+    OutOfBounds {
+        index: (usize, usize),
+        dimensions: (usize, usize),
+    },
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixError::DimensionMismatch { left, right } => write!(
+                f,
+                "width of left ({}x{}) does not match height of right ({}x{})",
+                left.0, left.1, right.0, right.1
+            ),
+            MatrixError::NotSquare((height, width)) => {
+                write!(f, "matrix must be square: {}x{}", height, width)
+            }
+            MatrixError::NotInvertible => write!(f, "matrix is not invertible"),
+            MatrixError::NotAffine => write!(f, "matrix is not an affine transform"),
+            MatrixError::OutOfBounds { index, dimensions } => write!(
+                f,
+                "y:{} or x:{} is outside the matrix dimensions {}x{}",
+                index.0, index.1, dimensions.0, dimensions.1
+            ),
+        }
+    }
+}
+
+impl Error for MatrixError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn messages() {
+        let mismatch = MatrixError::DimensionMismatch {
+            left: (2, 2),
+            right: (1, 3),
+        };
+        assert_eq!(
+            "width of left (2x2) does not match height of right (1x3)",
+            mismatch.to_string()
+        );
+        assert_eq!(
+            "matrix must be square: 1x3",
+            MatrixError::NotSquare((1, 3)).to_string()
+        );
+
+        // Usable wherever a boxed error is expected
+        let boxed: Box<dyn Error> = Box::new(MatrixError::NotInvertible);
+        assert_eq!("matrix is not invertible", boxed.to_string());
+    }
+}
@@ -0,0 +1,276 @@
+use super::*;
+use crate::util;
+
+// A square matrix factored as PA = LU with partial pivoting. L (unit diagonal, below the
+// diagonal) and U (on and above it) share one matrix, and row i of PA is row permutation[i]
+// of A. Factoring takes O(n³) once, after which every solve is O(n²).
+#[derive(Debug, Clone)]
+pub struct Lu {
+    lu: Matrix,
+    permutation: Vec<usize>,
+    // -1 when the permutation swapped rows an odd number of times
+    sign: Float,
+    singular: bool,
+}
+
+impl Matrix {
+    pub fn lu(&self) -> Result<Lu, MatrixError> {
+        if self.height != self.width {
+            return Err(MatrixError::NotSquare(self.dimensions()));
+        }
+
+        let n = self.height;
+        let mut lu = self.clone();
+        let mut permutation: Vec<usize> = (0..n).collect();
+        let mut sign = 1.0;
+        let mut singular = false;
+
+        for column in 0..n {
+            // Swapping in the largest remaining value keeps the multipliers at or below 1
+            let pivot = (column..n)
+                .max_by(|a, b| lu[(*a, column)].abs().total_cmp(&lu[(*b, column)].abs()))
+                .unwrap();
+            if pivot != column {
+                lu.values.swap(pivot, column);
+                permutation.swap(pivot, column);
+                sign = -sign;
+            }
+
+            // Near zero is relative to the size of the column's values, so that a matrix isn't
+            // singular just for being small. NaN can't be divided by either.
+            let scale = (0..n).fold(0.0, |scale: Float, y| scale.max(self[(y, column)].abs()));
+            let diagonal = lu[(column, column)];
+            if diagonal.is_nan() || diagonal.abs() <= util::THRESHOLD * scale {
+                singular = true;
+                continue;
+            }
+            for y in column + 1..n {
+                let factor = lu[(y, column)] / diagonal;
+                lu[(y, column)] = factor;
+                for x in column + 1..n {
+                    lu[(y, x)] -= factor * lu[(column, x)];
+                }
+            }
+        }
+
+        Ok(Lu {
+            lu,
+            permutation,
+            sign,
+            singular,
+        })
+    }
+
+    // The determinant through LU decomposition. See determinate for cofactor expansion.
+    pub fn determinant(&self) -> Result<Float, MatrixError> {
+        self.lu().map(|lu| lu.determinant())
+    }
+
+    // Finds x where self * x = b, for a b with one column per system to solve
+    pub fn solve(&self, b: &Matrix) -> Result<Matrix, MatrixError> {
+        self.lu().and_then(|lu| lu.solve(b))
+    }
+}
+
+impl Lu {
+    pub fn is_singular(&self) -> bool {
+        self.singular
+    }
+
+    pub fn determinant(&self) -> Float {
+        if self.singular {
+            return 0.0;
+        }
+        (0..self.lu.height).fold(self.sign, |product, i| product * self.lu[(i, i)])
+    }
+
+    pub fn solve(&self, b: &Matrix) -> Result<Matrix, MatrixError> {
+        let n = self.lu.height;
+        if b.height != n {
+            return Err(MatrixError::DimensionMismatch {
+                left: self.lu.dimensions(),
+                right: b.dimensions(),
+            });
+        }
+        if self.singular {
+            return Err(MatrixError::NotInvertible);
+        }
+
+        let mut x = Matrix::new(n, b.width);
+        for column in 0..b.width {
+            // Forward substitution through L, then back substitution through U
+            for y in 0..n {
+                let mut sum = b[(self.permutation[y], column)];
+                for i in 0..y {
+                    sum -= self.lu[(y, i)] * x[(i, column)];
+                }
+                x[(y, column)] = sum;
+            }
+            for y in (0..n).rev() {
+                let mut sum = x[(y, column)];
+                for i in y + 1..n {
+                    sum -= self.lu[(y, i)] * x[(i, column)];
+                }
+                x[(y, column)] = sum / self.lu[(y, y)];
+            }
+        }
+        Ok(x)
+    }
+
+    pub fn inverse(&self) -> Result<Matrix, MatrixError> {
+        self.solve(&Matrix::new_identity(self.lu.height, self.lu.height))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn book_matrices() -> Vec<Matrix> {
+        vec![
+            matrix![
+                -5.0, 2.0, 6.0, -8.0;
+                1.0, -5.0, 1.0, 8.0;
+                7.0, 7.0, -6.0, -7.0;
+                1.0, -3.0, 7.0, 4.0
+            ],
+            matrix![
+                8.0, -5.0, 9.0, 2.0;
+                7.0, 5.0, 6.0, 1.0;
+                -6.0, 0.0, 9.0, 6.0;
+                -3.0, 0.0, -9.0, -4.0
+            ],
+            matrix![
+                9.0, 3.0, 0.0, 9.0;
+                -5.0, -2.0, -6.0, -3.0;
+                -4.0, 9.0, 6.0, 4.0;
+                -7.0, 6.0, 6.0, 2.0
+            ],
+            matrix![
+                1.0, 2.0, 6.0;
+                -5.0, 8.0, -4.0;
+                2.0, 6.0, 4.0
+            ],
+        ]
+    }
+
+    // A diagonally dominant matrix, which is always invertible
+    fn large_matrix(n: usize) -> Matrix {
+        let mut m = Matrix::new(n, n);
+        for (y, x) in m.iter() {
+            m[(y, x)] = if y == x {
+                n as Float * 2.0
+            } else {
+                ((y * 7 + x * 3) % 5) as Float - 2.0
+            };
+        }
+        m
+    }
+
+    #[test]
+    fn matches_cofactor_expansion() {
+        for m in book_matrices() {
+            let determinant = m.determinant().unwrap();
+            let determinate = m.determinate().unwrap();
+            assert!((determinant - determinate).abs() / determinate.abs() < 0.00001);
+            assert_eq!(m.cofactor_inverse(), m.inverse());
+        }
+    }
+
+    #[test]
+    fn pivoting() {
+        // The first pivot is zero, so rows have to be swapped
+        let m = matrix![
+            0.0, 1.0;
+            1.0, 0.0
+        ];
+        assert_eq!(Ok(-1.0), m.determinant());
+        assert_eq!(Ok(m.clone()), m.inverse());
+    }
+
+    #[test]
+    fn singular() {
+        let m = matrix![
+            -4.0, 2.0, -2.0, -3.0;
+            9.0, 6.0, 2.0, 6.0;
+            0.0, -5.0, 1.0, -5.0;
+            0.0, 0.0, 0.0, 0.0
+        ];
+        let lu = m.lu().unwrap();
+        assert!(lu.is_singular());
+        assert_eq!(0.0, lu.determinant());
+        assert_eq!(Err(MatrixError::NotInvertible), lu.inverse());
+        assert_eq!(
+            Err(MatrixError::NotInvertible),
+            m.solve(&tuple![1.0, 2.0, 3.0, 4.0])
+        );
+    }
+
+    #[test]
+    fn small_but_invertible() {
+        let m = Matrix::from(Matrix4::scale(1e-6, 1e-6, 1e-6));
+        let inverse = m.inverse().unwrap();
+        assert_feq!(1.0, inverse[(0, 0)] / 1e6);
+        assert_eq!(1.0, inverse[(3, 3)]);
+        assert!(!m.lu().unwrap().is_singular());
+
+        // Checking first agrees with trying it
+        let m = Matrix::from(Matrix4::scale(0.01, 0.01, 0.01));
+        assert!(m.is_invertable());
+        assert!(m.inverse().is_ok());
+    }
+
+    #[test]
+    fn not_a_number() {
+        let m = matrix![
+            Float::NAN, 1.0;
+            1.0, 0.0
+        ];
+        assert_eq!(Err(MatrixError::NotInvertible), m.inverse());
+        assert_eq!(Ok(0.0), m.determinant());
+    }
+
+    #[test]
+    fn solving() {
+        // 2x + y - z = 8, -3x - y + 2z = -11, -2x + y + 2z = -3
+        let a = matrix![
+            2.0, 1.0, -1.0;
+            -3.0, -1.0, 2.0;
+            -2.0, 1.0, 2.0
+        ];
+        let b = matrix![
+            8.0, 1.0;
+            -11.0, 0.0;
+            -3.0, 0.0
+        ];
+        let x = a.solve(&b).unwrap();
+        assert_feq!(2.0, x[(0, 0)]);
+        assert_feq!(3.0, x[(1, 0)]);
+        assert_feq!(-1.0, x[(2, 0)]);
+        // The second column solves for the first column of the inverse
+        assert_eq!(Ok(x[(1, 1)]), a.inverse().map(|inverse| inverse[(1, 0)]));
+
+        assert_eq!(
+            Err(MatrixError::DimensionMismatch {
+                left: (3, 3),
+                right: (2, 1)
+            }),
+            a.solve(&tuple![1.0, 2.0])
+        );
+    }
+
+    #[test]
+    fn large_matrices() {
+        let m = large_matrix(12);
+        let inverse = m.inverse().unwrap();
+        assert_eq!(Ok(Matrix::new_identity(12, 12)), &m * &inverse);
+        assert!(m.determinant().unwrap() > 0.0);
+    }
+
+    #[test]
+    fn not_square() {
+        let m = matrix![1.0, 2.0, 3.0];
+        assert_eq!(Err(MatrixError::NotSquare((1, 3))), m.determinant());
+        assert_eq!(Err(MatrixError::NotSquare((1, 3))), m.inverse());
+    }
+}
@@ -0,0 +1,421 @@
+use super::*;
+use crate::simd;
+use crate::space::{Point, Vector};
+use crate::util;
+
+use std::convert::TryFrom;
+use std::ops::{Index, IndexMut, Mul};
+
+// A 4x4 transform that lives on the stack. Multiplying it never allocates or fails, so it's
+// what rays and shapes should be transformed with; Matrix stays around for other sizes.
+#[derive(Debug, Copy, Clone)]
+pub struct Matrix4([[Float; 4]; 4]);
+
+impl Matrix4 {
+    pub fn new(rows: [[Float; 4]; 4]) -> Matrix4 {
+        Matrix4(rows)
+    }
+
+    pub fn identity() -> Matrix4 {
+        Matrix4([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rows(&self) -> &[[Float; 4]; 4] {
+        &self.0
+    }
+
+    pub fn translation<X, Y, Z>(x: X, y: Y, z: Z) -> Matrix4
+    where
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
+    {
+        Matrix4([
+            [1.0, 0.0, 0.0, x.into()],
+            [0.0, 1.0, 0.0, y.into()],
+            [0.0, 0.0, 1.0, z.into()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scale<X, Y, Z>(x: X, y: Y, z: Z) -> Matrix4
+    where
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
+    {
+        Matrix4([
+            [x.into(), 0.0, 0.0, 0.0],
+            [0.0, y.into(), 0.0, 0.0],
+            [0.0, 0.0, z.into(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_x<T: Into<Float>>(radians: T) -> Matrix4 {
+        let (sin, cos) = radians.into().sin_cos();
+        Matrix4([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos, -sin, 0.0],
+            [0.0, sin, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_y<T: Into<Float>>(radians: T) -> Matrix4 {
+        let (sin, cos) = radians.into().sin_cos();
+        Matrix4([
+            [cos, 0.0, sin, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin, 0.0, cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn rotation_z<T: Into<Float>>(radians: T) -> Matrix4 {
+        let (sin, cos) = radians.into().sin_cos();
+        Matrix4([
+            [cos, -sin, 0.0, 0.0],
+            [sin, cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // A rotation of radians around any axis through the origin
+    pub fn rotation_axis<T: Into<Float>>(axis: Vector, radians: T) -> Matrix4 {
+        Quaternion::from_axis_angle(axis, radians).to_matrix4()
+    }
+
+    pub fn shear(relation: Shear) -> Matrix4 {
+        let (xy, xz, yx, yz, zx, zy) = match relation {
+            Shear::XY => (1.0, 0.0, 0.0, 0.0, 0.0, 0.0),
+            Shear::XZ => (0.0, 1.0, 0.0, 0.0, 0.0, 0.0),
+            Shear::YX => (0.0, 0.0, 1.0, 0.0, 0.0, 0.0),
+            Shear::YZ => (0.0, 0.0, 0.0, 1.0, 0.0, 0.0),
+            Shear::ZX => (0.0, 0.0, 0.0, 0.0, 1.0, 0.0),
+            Shear::ZY => (0.0, 0.0, 0.0, 0.0, 0.0, 1.0),
+        };
+        Matrix4([
+            [1.0, xy, xz, 0.0],
+            [yx, 1.0, yz, 0.0],
+            [zx, zy, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (y, row) in m.iter_mut().enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = self.0[x][y];
+            }
+        }
+        Matrix4(m)
+    }
+
+    pub fn determinant(&self) -> Float {
+        let (s, c) = self.minors();
+        determinant_of_minors(&s, &c)
+    }
+
+    pub fn is_invertible(&self) -> bool {
+        !self.is_singular(self.determinant())
+    }
+
+    // Whether the determinant is near zero once every row, and then every column, has been
+    // scaled so that its largest value is 1. Scaling them doesn't change whether the matrix can
+    // be inverted, but it keeps small scales and large translations from looking singular.
+    fn is_singular(&self, determinant: Float) -> bool {
+        let mut m = self.0;
+        let mut scaled = determinant.abs();
+        for row in m.iter_mut() {
+            let largest = row
+                .iter()
+                .fold(0.0, |largest: Float, v| largest.max(v.abs()));
+            if largest == 0.0 {
+                return true;
+            }
+            row.iter_mut().for_each(|v| *v /= largest);
+            scaled /= largest;
+        }
+        for x in 0..4 {
+            let largest = m
+                .iter()
+                .fold(0.0, |largest: Float, row| largest.max(row[x].abs()));
+            if largest == 0.0 {
+                return true;
+            }
+            scaled /= largest;
+        }
+        scaled.is_nan() || scaled <= util::THRESHOLD
+    }
+
+    // The adjugate over the determinant, written out from the 2x2 minors of the top and
+    // bottom two rows instead of recursing through cofactors
+    pub fn inverse(&self) -> Result<Matrix4, MatrixError> {
+        let (s, c) = self.minors();
+        let determinant = determinant_of_minors(&s, &c);
+        if self.is_singular(determinant) {
+            return Err(MatrixError::NotInvertible);
+        }
+
+        let m = &self.0;
+        let inverse = [
+            [
+                m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3],
+                -m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3],
+                m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3],
+                -m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3],
+            ],
+            [
+                -m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1],
+                m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1],
+                -m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1],
+                m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1],
+            ],
+            [
+                m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0],
+                -m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0],
+                m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0],
+                -m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0],
+            ],
+            [
+                -m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0],
+                m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0],
+                -m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0],
+                m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0],
+            ],
+        ];
+        Ok(Matrix4(inverse.map(|row| row.map(|v| v / determinant))))
+    }
+
+    // The six 2x2 determinants of the top two rows and of the bottom two rows
+    fn minors(&self) -> ([Float; 6], [Float; 6]) {
+        let m = &self.0;
+        let pairs = [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)];
+        let mut top = [0.0; 6];
+        let mut bottom = [0.0; 6];
+        for (i, (a, b)) in pairs.iter().enumerate() {
+            top[i] = m[0][*a] * m[1][*b] - m[1][*a] * m[0][*b];
+            bottom[i] = m[2][*a] * m[3][*b] - m[3][*a] * m[2][*b];
+        }
+        (top, bottom)
+    }
+}
+
+// Laplace expansion along the top two rows, pairing each of their minors with the
+// complementary one of the bottom two rows
+fn determinant_of_minors(s: &[Float; 6], c: &[Float; 6]) -> Float {
+    s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Index<(usize, usize)> for Matrix4 {
+    type Output = Float;
+    fn index(&self, (y, x): (usize, usize)) -> &Float {
+        &self.0[y][x]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix4 {
+    fn index_mut(&mut self, (y, x): (usize, usize)) -> &mut Float {
+        &mut self.0[y][x]
+    }
+}
+
+impl PartialEq for Matrix4 {
+    fn eq(&self, rhs: &Matrix4) -> bool {
+        self.0
+            .iter()
+            .flatten()
+            .zip(rhs.0.iter().flatten())
+            .all(|(a, b)| util::feq(*a, *b))
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+    #[inline]
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        Matrix4(simd::matrix_mul(&self.0, &rhs.0))
+    }
+}
+
+impl Mul<Point> for Matrix4 {
+    type Output = Point;
+    #[inline]
+    fn mul(self, rhs: Point) -> Point {
+        Point::from_lanes(simd::matrix_transform(&self.0, rhs.lanes()))
+    }
+}
+
+// Vectors have no position, their w of 0 leaves out the translation
+impl Mul<Vector> for Matrix4 {
+    type Output = Vector;
+    #[inline]
+    fn mul(self, rhs: Vector) -> Vector {
+        Vector::from_lanes(simd::matrix_transform(&self.0, rhs.lanes()))
+    }
+}
+
+impl From<Matrix4> for Matrix {
+    fn from(m: Matrix4) -> Matrix {
+        Matrix::with_values(m.0.iter().map(|row| row.to_vec()).collect())
+    }
+}
+
+impl TryFrom<&Matrix> for Matrix4 {
+    type Error = MatrixError;
+    fn try_from(m: &Matrix) -> Result<Matrix4, MatrixError> {
+        if m.dimensions() != (4, 4) {
+            // The expected size goes on the right
+            return Err(MatrixError::DimensionMismatch {
+                left: m.dimensions(),
+                right: (4, 4),
+            });
+        }
+        let mut rows = [[0.0; 4]; 4];
+        for (y, x) in m.iter() {
+            rows[y][x] = m[(y, x)];
+        }
+        Ok(Matrix4(rows))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::consts::PI;
+
+    fn book_matrix() -> Matrix4 {
+        Matrix4::new([
+            [-5.0, 2.0, 6.0, -8.0],
+            [1.0, -5.0, 1.0, 8.0],
+            [7.0, 7.0, -6.0, -7.0],
+            [1.0, -3.0, 7.0, 4.0],
+        ])
+    }
+
+    #[test]
+    fn multiplying() {
+        let a = Matrix4::new([
+            [1.0, 2.0, 3.0, 4.0],
+            [5.0, 6.0, 7.0, 8.0],
+            [9.0, 8.0, 7.0, 6.0],
+            [5.0, 4.0, 3.0, 2.0],
+        ]);
+        let b = Matrix4::new([
+            [-2.0, 1.0, 2.0, 3.0],
+            [3.0, 2.0, 1.0, -1.0],
+            [4.0, 3.0, 6.0, 5.0],
+            [1.0, 2.0, 7.0, 8.0],
+        ]);
+        let expected = Matrix4::new([
+            [20.0, 22.0, 50.0, 48.0],
+            [44.0, 54.0, 114.0, 108.0],
+            [40.0, 58.0, 110.0, 102.0],
+            [16.0, 26.0, 46.0, 42.0],
+        ]);
+        assert_eq!(expected, a * b);
+        assert_eq!(a, a * Matrix4::identity());
+    }
+
+    #[test]
+    fn transforming_points_and_vectors() {
+        let t = Matrix4::translation(5.0, -3.0, 2.0);
+        assert_eq!(Point::new(2.0, 1.0, 7.0), t * Point::new(-3.0, 4.0, 5.0));
+        assert_eq!(Vector::new(-3.0, 4.0, 5.0), t * Vector::new(-3.0, 4.0, 5.0));
+
+        let combined = Matrix4::translation(10.0, 5.0, 7.0)
+            * Matrix4::scale(5.0, 5.0, 5.0)
+            * Matrix4::rotation_x(PI / 2.0);
+        assert_eq!(
+            Point::new(15.0, 0.0, 7.0),
+            combined * Point::new(1.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn matches_general_matrix() {
+        let transforms = [
+            (Matrix4::rotation_x(0.3), Matrix::rotation_x(0.3)),
+            (Matrix4::rotation_y(0.7), Matrix::rotation_y(0.7)),
+            (Matrix4::rotation_z(1.1), Matrix::rotation_z(1.1)),
+            (Matrix4::shear(Shear::YZ), Matrix::shear(Shear::YZ)),
+        ];
+        let p = Point::new(1.0, -2.0, 3.0);
+        for (fixed, general) in transforms.iter() {
+            assert_eq!(general, &Matrix::from(*fixed));
+            assert_eq!(Ok(*fixed * p), general.clone() * p);
+        }
+    }
+
+    #[test]
+    fn determinant_and_inverse() {
+        let a = book_matrix();
+        assert_feq!(532.0, a.determinant());
+        assert!(a.is_invertible());
+
+        let general = Matrix::from(a);
+        assert_eq!(
+            Ok(general.inverse().unwrap()),
+            a.inverse().map(Matrix::from)
+        );
+        assert_eq!(Matrix4::identity(), a * a.inverse().unwrap());
+    }
+
+    #[test]
+    fn singular_matrix() {
+        let a = Matrix4::new([
+            [-4.0, 2.0, -2.0, -3.0],
+            [9.0, 6.0, 2.0, 6.0],
+            [0.0, -5.0, 1.0, -5.0],
+            [0.0, 0.0, 0.0, 0.0],
+        ]);
+        assert_feq!(0.0, a.determinant());
+        assert!(!a.is_invertible());
+        assert!(a.inverse().is_err());
+    }
+
+    #[test]
+    fn small_and_large_scales_are_invertible() {
+        for factor in [0.01, 1e-6, 1e6].iter() {
+            let factor: Float = *factor;
+            let m = Matrix4::scale(factor, factor, factor);
+            assert!(m.is_invertible(), "{}", factor);
+            let inverse = m.inverse().unwrap();
+            assert_feq!(1.0, inverse[(0, 0)] * factor);
+            assert_eq!(Matrix4::identity(), m * inverse);
+
+            // Moving it far away doesn't make it any less invertible
+            let moved = Matrix4::translation(1e5, -2e5, 3e5) * m;
+            assert!(moved.is_invertible(), "{}", factor);
+            assert!(moved.inverse().is_ok());
+        }
+    }
+
+    #[test]
+    fn transposing() {
+        let a = book_matrix();
+        assert_feq!(a[(1, 2)], a.transpose()[(2, 1)]);
+        assert_eq!(a, a.transpose().transpose());
+    }
+
+    #[test]
+    fn converting() {
+        let a = book_matrix();
+        assert_eq!(Ok(a), Matrix4::try_from(&Matrix::from(a)));
+        assert!(Matrix4::try_from(&Matrix::new(3, 3)).is_err());
+    }
+}
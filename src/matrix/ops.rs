@@ -20,7 +20,7 @@ impl Matrix {
         }
     }
 
-    pub fn with_values(values: Vec<Vec<f32>>) -> Matrix {
+    pub fn with_values(values: Vec<Vec<Float>>) -> Matrix {
         let height = values.len();
         let width = values
             .first()
@@ -56,10 +56,8 @@ impl Matrix {
 
     pub fn identity(&self) -> Matrix {
         let mut id = Matrix::new(self.height, self.width);
-        let mut x = 0;
         for y in 0..self.height {
-            id[(y, x)] = 1.0;
-            x += 1;
+            id[(y, y)] = 1.0;
         }
         id
     }
@@ -77,12 +75,11 @@ impl Matrix {
         m
     }
 
-    pub fn determinate(&self) -> Result<f32, String> {
+    // Cofactor expansion as the book describes it, which takes factorial time. Use
+    // determinant for anything but small matrices.
+    pub fn determinate(&self) -> Result<Float, MatrixError> {
         if self.height != self.width {
-            return Err(format!(
-                "Matrix must be square: {}x{}",
-                self.height, self.width
-            ));
+            return Err(MatrixError::NotSquare(self.dimensions()));
         }
 
         if self.height == 2 && self.width == 2 {
@@ -112,21 +109,20 @@ impl Matrix {
                 if calculated_something {
                     Ok(x)
                 } else {
-                    Err(String::from(
-                        "Matrix is not invertable and can't calculate determinate",
-                    ))
+                    // Only an empty matrix has no cofactors to sum
+                    Err(MatrixError::NotInvertible)
                 }
             }
-            err @ _ => err,
+            err => err,
         }
     }
 
-    pub fn submatrix(&self, skip_y: usize, skip_x: usize) -> Result<Matrix, String> {
+    pub fn submatrix(&self, skip_y: usize, skip_x: usize) -> Result<Matrix, MatrixError> {
         if skip_y >= self.height || skip_x >= self.width {
-            return Err(format!(
-                "y:{} or x:{} is outside the matrix dimensions {}x{}",
-                skip_y, skip_x, self.height, self.width
-            ));
+            return Err(MatrixError::OutOfBounds {
+                index: (skip_y, skip_x),
+                dimensions: self.dimensions(),
+            });
         }
         let mut m = Matrix::new(self.height - 1, self.width - 1);
         for (y, x) in self.iter() {
@@ -140,30 +136,28 @@ impl Matrix {
         Ok(m)
     }
 
-    pub fn minor(&self, y: usize, x: usize) -> Result<f32, String> {
-        return self.submatrix(y, x).and_then(|m| m.determinate());
+    pub fn minor(&self, y: usize, x: usize) -> Result<Float, MatrixError> {
+        self.submatrix(y, x).and_then(|m| m.determinate())
     }
 
-    pub fn cofactor(&self, y: usize, x: usize) -> Result<f32, String> {
+    pub fn cofactor(&self, y: usize, x: usize) -> Result<Float, MatrixError> {
         let sign = if (y + x) % 2 == 1 { -1.0 } else { 1.0 };
         self.minor(y, x).map(|v| v * sign)
     }
 
+    // Asks the same LU decomposition that inverse uses, so the two always agree
     pub fn is_invertable(&self) -> bool {
-        match self.determinate() {
-            Ok(x) => {
-                if util::feq(x, 0.0) {
-                    false
-                } else {
-                    true
-                }
-            }
-            _ => false,
-        }
+        self.lu().map(|lu| !lu.is_singular()).unwrap_or(false)
     }
 
-    pub fn inverse(&self) -> Result<Matrix, String> {
+    pub fn inverse(&self) -> Result<Matrix, MatrixError> {
+        self.lu().and_then(|lu| lu.inverse())
+    }
+
+    // The book's inverse, dividing each cofactor by the determinate
+    pub fn cofactor_inverse(&self) -> Result<Matrix, MatrixError> {
         match self.determinate() {
+            Ok(determinate) if util::feq(determinate, 0.0) => Err(MatrixError::NotInvertible),
             Ok(determinate) => {
                 let mut inverse = Self::new(self.height, self.width);
                 for (y, x) in self.iter() {
@@ -182,14 +176,14 @@ impl Matrix {
 }
 
 impl Index<(usize, usize)> for Matrix {
-    type Output = f32;
-    fn index(&self, (y, x): (usize, usize)) -> &f32 {
+    type Output = Float;
+    fn index(&self, (y, x): (usize, usize)) -> &Float {
         &self.values[y][x]
     }
 }
 
 impl IndexMut<(usize, usize)> for Matrix {
-    fn index_mut(&mut self, (y, x): (usize, usize)) -> &mut f32 {
+    fn index_mut(&mut self, (y, x): (usize, usize)) -> &mut Float {
         &mut self.values[y][x]
     }
 }
@@ -205,12 +199,12 @@ impl PartialEq for Matrix {
 }
 impl Eq for Matrix {}
 
-fn matrix_mul_helper(lhs: &Matrix, rhs: &Matrix) -> Result<Matrix, String> {
+fn matrix_mul_helper(lhs: &Matrix, rhs: &Matrix) -> Result<Matrix, MatrixError> {
     if lhs.width != rhs.height {
-        return Err(format!(
-            "width of left ({}x{}) does not match height of rhs ({}x{})",
-            lhs.height, lhs.width, rhs.height, rhs.width
-        ));
+        return Err(MatrixError::DimensionMismatch {
+            left: lhs.dimensions(),
+            right: rhs.dimensions(),
+        });
     }
 
     let mut result = Matrix::new(lhs.height, rhs.width);
@@ -225,42 +219,42 @@ fn matrix_mul_helper(lhs: &Matrix, rhs: &Matrix) -> Result<Matrix, String> {
 }
 
 impl Mul<Matrix> for Matrix {
-    type Output = Result<Self, String>;
+    type Output = Result<Self, MatrixError>;
     fn mul(self, rhs: Self) -> Self::Output {
         matrix_mul_helper(&self, &rhs)
     }
 }
 
 impl Mul<&Matrix> for &Matrix {
-    type Output = Result<Matrix, String>;
+    type Output = Result<Matrix, MatrixError>;
     fn mul(self, rhs: &Matrix) -> Self::Output {
         matrix_mul_helper(self, rhs)
     }
 }
 
-impl Mul<Result<Matrix, String>> for Matrix {
-    type Output = Result<Matrix, String>;
-    fn mul(self, rhs: Result<Matrix, String>) -> Self::Output {
+impl Mul<Result<Matrix, MatrixError>> for Matrix {
+    type Output = Result<Matrix, MatrixError>;
+    fn mul(self, rhs: Result<Matrix, MatrixError>) -> Self::Output {
         rhs.and_then(|rhs| self * rhs)
     }
 }
 
-impl Mul<Result<&Matrix, String>> for &Matrix {
-    type Output = Result<Matrix, String>;
-    fn mul(self, rhs: Result<&Matrix, String>) -> Self::Output {
+impl Mul<Result<&Matrix, MatrixError>> for &Matrix {
+    type Output = Result<Matrix, MatrixError>;
+    fn mul(self, rhs: Result<&Matrix, MatrixError>) -> Self::Output {
         rhs.and_then(|rhs| self * rhs)
     }
 }
 
-impl Mul<Matrix> for Result<Matrix, String> {
-    type Output = Result<Matrix, String>;
+impl Mul<Matrix> for Result<Matrix, MatrixError> {
+    type Output = Result<Matrix, MatrixError>;
     fn mul(self, rhs: Matrix) -> Self::Output {
         self.and_then(|lhs| lhs * rhs)
     }
 }
 
-impl Mul<&Matrix> for Result<&Matrix, String> {
-    type Output = Result<Matrix, String>;
+impl Mul<&Matrix> for Result<&Matrix, MatrixError> {
+    type Output = Result<Matrix, MatrixError>;
     fn mul(self, rhs: &Matrix) -> Self::Output {
         self.and_then(|lhs| lhs * rhs)
     }
@@ -396,7 +390,13 @@ mod test {
         let m2 = matrix![
             1.0, 2.0, 3.0;
         ];
-        assert!((m1 * m2).is_err());
+        assert_eq!(
+            Err(MatrixError::DimensionMismatch {
+                left: (2, 2),
+                right: (1, 3)
+            }),
+            m1 * m2
+        );
     }
 
     #[test]
@@ -505,7 +505,7 @@ mod test {
             -2.0, -8.0, 5.0;
             -3.0, 1.0, 3.0;
         ];
-        assert!(a.determinate().is_err());
+        assert_eq!(Err(MatrixError::NotSquare((2, 3))), a.determinate());
     }
 
     #[test]
@@ -551,7 +551,13 @@ mod test {
             -3.0, 2.0, 7.0;
             0.0, 6.0, -3.0
         ];
-        assert!(m.submatrix(3, 2).is_err());
+        assert_eq!(
+            Err(MatrixError::OutOfBounds {
+                index: (3, 2),
+                dimensions: (3, 3)
+            }),
+            m.submatrix(3, 2)
+        );
         assert!(m.submatrix(0, 3).is_err());
     }
 
@@ -607,6 +613,7 @@ mod test {
         ];
         assert_eq!(Ok(0.0), a.determinate());
         assert!(!a.is_invertable());
+        assert_eq!(Err(MatrixError::NotInvertible), a.inverse());
     }
 
     #[test]
@@ -624,12 +631,13 @@ mod test {
 
         assert_eq!(Ok(105.0), a.cofactor(3, 2));
         assert_feq!(105.0 / 532.0, b[(2, 3)]);
+        // The book's rounded values as exact fractions, so they hold at any precision
         assert_eq!(
             matrix![
-                0.21805, 0.45113, 0.24060, -0.04511;
-                -0.80827, -1.45677, -0.44361, 0.52068;
-                -0.07895, -0.22368, -0.05263, 0.19737;
-                -0.52256, -0.81391, -0.30075, 0.30639
+                116.0 / 532.0, 240.0 / 532.0, 128.0 / 532.0, -24.0 / 532.0;
+                -430.0 / 532.0, -775.0 / 532.0, -236.0 / 532.0, 277.0 / 532.0;
+                -42.0 / 532.0, -119.0 / 532.0, -28.0 / 532.0, 105.0 / 532.0;
+                -278.0 / 532.0, -433.0 / 532.0, -160.0 / 532.0, 163.0 / 532.0
             ],
             b
         );
@@ -646,10 +654,10 @@ mod test {
 
         assert_eq!(
             Ok(matrix![
-                -0.15385, -0.15385, -0.28205, -0.53846;
-                -0.07692, 0.12308, 0.02564, 0.03077;
-                0.35897, 0.35897, 0.43590, 0.92308;
-                -0.69231, -0.69231, -0.76923, -1.92308
+                -90.0 / 585.0, -90.0 / 585.0, -165.0 / 585.0, -315.0 / 585.0;
+                -45.0 / 585.0, 72.0 / 585.0, 15.0 / 585.0, 18.0 / 585.0;
+                210.0 / 585.0, 210.0 / 585.0, 255.0 / 585.0, 540.0 / 585.0;
+                -405.0 / 585.0, -405.0 / 585.0, -450.0 / 585.0, -1125.0 / 585.0
             ]),
             a.inverse()
         );
@@ -666,10 +674,10 @@ mod test {
 
         assert_eq!(
             Ok(matrix![
-                -0.04074, -0.07778, 0.14444, -0.22222;
-                -0.07778, 0.03333, 0.36667, -0.33333;
-                -0.02901, -0.14630, -0.10926, 0.12963;
-                0.17778, 0.06667, -0.26667, 0.33333
+                -66.0 / 1620.0, -126.0 / 1620.0, 234.0 / 1620.0, -360.0 / 1620.0;
+                -126.0 / 1620.0, 54.0 / 1620.0, 594.0 / 1620.0, -540.0 / 1620.0;
+                -47.0 / 1620.0, -237.0 / 1620.0, -177.0 / 1620.0, 210.0 / 1620.0;
+                288.0 / 1620.0, 108.0 / 1620.0, -432.0 / 1620.0, 540.0 / 1620.0
             ]),
             a.inverse()
         );
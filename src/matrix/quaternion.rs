@@ -0,0 +1,327 @@
+use super::*;
+use crate::space::Vector;
+use crate::util;
+
+use std::ops::Mul;
+
+// A rotation stored as a unit quaternion. Unlike chained Euler rotations they can be
+// interpolated smoothly and never lose an axis to gimbal lock.
+#[derive(Debug, Copy, Clone)]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+// When the cosine between two rotations is above this they're nearly the same, and slerp
+// falls back to a normalized lerp since the sine it divides by gets too small to trust
+const SLERP_THRESHOLD: Float = 0.9995;
+
+impl Quaternion {
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Self::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // A rotation of radians around axis, turning the same way as rotation_x/y/z do around
+    // theirs. The axis doesn't need to be normalized.
+    pub fn from_axis_angle<T: Into<Float>>(axis: Vector, radians: T) -> Quaternion {
+        let Vector(x, y, z) = axis.normalize();
+        let (sin, cos) = (radians.into() / 2.0).sin_cos();
+        Self::new(cos, x * sin, y * sin, z * sin)
+    }
+
+    // The same rotation as rotation_z(z) * rotation_y(y) * rotation_x(x), so x is applied first
+    pub fn from_euler<X, Y, Z>(x: X, y: Y, z: Z) -> Quaternion
+    where
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
+    {
+        Self::from_axis_angle(Vector::new(0.0, 0.0, 1.0), z)
+            * Self::from_axis_angle(Vector::new(0.0, 1.0, 0.0), y)
+            * Self::from_axis_angle(Vector::new(1.0, 0.0, 0.0), x)
+    }
+
+    // The rotation in the upper 3x3 of a matrix, which must be a pure rotation without scale,
+    // shear or reflection. Starts from the largest of w, x, y and z to stay accurate near
+    // half turns where the trace alone gives a tiny w.
+    pub fn from_rotation(m: &Matrix4) -> Quaternion {
+        let trace = m[(0, 0)] + m[(1, 1)] + m[(2, 2)];
+        if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::new(
+                s / 4.0,
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(1, 0)] - m[(0, 1)]) / s,
+            )
+        } else if m[(0, 0)] > m[(1, 1)] && m[(0, 0)] > m[(2, 2)] {
+            let s = (1.0 + m[(0, 0)] - m[(1, 1)] - m[(2, 2)]).sqrt() * 2.0;
+            Self::new(
+                (m[(2, 1)] - m[(1, 2)]) / s,
+                s / 4.0,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+            )
+        } else if m[(1, 1)] > m[(2, 2)] {
+            let s = (1.0 + m[(1, 1)] - m[(0, 0)] - m[(2, 2)]).sqrt() * 2.0;
+            Self::new(
+                (m[(0, 2)] - m[(2, 0)]) / s,
+                (m[(0, 1)] + m[(1, 0)]) / s,
+                s / 4.0,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+            )
+        } else {
+            let s = (1.0 + m[(2, 2)] - m[(0, 0)] - m[(1, 1)]).sqrt() * 2.0;
+            Self::new(
+                (m[(1, 0)] - m[(0, 1)]) / s,
+                (m[(0, 2)] + m[(2, 0)]) / s,
+                (m[(1, 2)] + m[(2, 1)]) / s,
+                s / 4.0,
+            )
+        }
+    }
+
+    pub fn dot(&self, rhs: &Quaternion) -> Float {
+        self.w * rhs.w + self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn magnitude(&self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        let magnitude = self.magnitude();
+        Self::new(
+            self.w / magnitude,
+            self.x / magnitude,
+            self.y / magnitude,
+            self.z / magnitude,
+        )
+    }
+
+    // The opposite rotation, for unit quaternions this is also the inverse
+    pub fn conjugate(&self) -> Quaternion {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    // Spherical linear interpolation, turning at a constant speed from self at t = 0 to rhs at
+    // t = 1 along the shortest way around
+    pub fn slerp<T: Into<Float>>(&self, rhs: &Quaternion, t: T) -> Quaternion {
+        let t = t.into();
+        let mut end = *rhs;
+        let mut cos = self.dot(rhs);
+        // q and -q are the same rotation, but only one of them is the short way around
+        if cos < 0.0 {
+            end = Self::new(-end.w, -end.x, -end.y, -end.z);
+            cos = -cos;
+        }
+
+        let (from, to) = if cos > SLERP_THRESHOLD {
+            (1.0 - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Self::new(
+            self.w * from + end.w * to,
+            self.x * from + end.x * to,
+            self.y * from + end.y * to,
+            self.z * from + end.z * to,
+        )
+        .normalize()
+    }
+
+    pub fn to_matrix4(&self) -> Matrix4 {
+        let Quaternion { w, x, y, z } = self.normalize();
+        Matrix4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn to_matrix(&self) -> Matrix {
+        self.to_matrix4().into()
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl PartialEq for Quaternion {
+    fn eq(&self, rhs: &Quaternion) -> bool {
+        util::feq(self.w, rhs.w)
+            && util::feq(self.x, rhs.x)
+            && util::feq(self.y, rhs.y)
+            && util::feq(self.z, rhs.z)
+    }
+}
+
+// Combines two rotations, like matrices the one on the right is applied first
+impl Mul for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, rhs: Quaternion) -> Quaternion {
+        Self::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+impl Mul<Vector> for Quaternion {
+    type Output = Vector;
+    fn mul(self, Vector(x, y, z): Vector) -> Vector {
+        let rotated = self * Self::new(0.0, x, y, z) * self.conjugate();
+        Vector::new(rotated.x, rotated.y, rotated.z)
+    }
+}
+
+impl From<Quaternion> for Matrix4 {
+    fn from(q: Quaternion) -> Matrix4 {
+        q.to_matrix4()
+    }
+}
+
+impl From<Quaternion> for Matrix {
+    fn from(q: Quaternion) -> Matrix {
+        q.to_matrix()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::space::Point;
+    use crate::util::consts::{FRAC_1_SQRT_2, PI};
+
+    #[test]
+    fn matches_axis_rotations() {
+        let axes = [
+            (Vector::new(1.0, 0.0, 0.0), Matrix4::rotation_x(0.8)),
+            (Vector::new(0.0, 1.0, 0.0), Matrix4::rotation_y(0.8)),
+            (Vector::new(0.0, 0.0, 1.0), Matrix4::rotation_z(0.8)),
+        ];
+        for (axis, expected) in axes.iter() {
+            assert_eq!(
+                *expected,
+                Quaternion::from_axis_angle(*axis, 0.8).to_matrix4()
+            );
+        }
+    }
+
+    #[test]
+    fn rotating_vectors() {
+        let q = Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 2.0), PI / 2.0);
+        assert_eq!(Vector::new(-1.0, 0.0, 0.0), q * Vector::new(0.0, 1.0, 0.0));
+
+        // A third of a turn around the diagonal cycles the axes
+        let q = Quaternion::from_axis_angle(Vector::new(1.0, 1.0, 1.0), 2.0 * PI / 3.0);
+        assert_eq!(Vector::new(0.0, 1.0, 0.0), q * Vector::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            q.to_matrix4() * Vector::new(0.3, -2.0, 5.0),
+            q * Vector::new(0.3, -2.0, 5.0)
+        );
+    }
+
+    #[test]
+    fn euler_angles() {
+        let q = Quaternion::from_euler(0.3, -1.2, 2.0);
+        let expected =
+            Matrix4::rotation_z(2.0) * Matrix4::rotation_y(-1.2) * Matrix4::rotation_x(0.3);
+        assert_eq!(expected, q.to_matrix4());
+        assert_eq!(
+            Quaternion::identity(),
+            Quaternion::from_euler(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn conjugate_undoes_rotation() {
+        let q = Quaternion::from_euler(0.5, 0.25, -1.0);
+        let v = Vector::new(1.0, 2.0, 3.0);
+        assert_eq!(v, q.conjugate() * (q * v));
+        assert_eq!(Quaternion::identity(), q * q.conjugate());
+    }
+
+    #[test]
+    fn slerp() {
+        let z = Vector::new(0.0, 0.0, 1.0);
+        let from = Quaternion::identity();
+        let to = Quaternion::from_axis_angle(z, PI / 2.0);
+        assert_eq!(from, from.slerp(&to, 0.0));
+        assert_eq!(to, from.slerp(&to, 1.0));
+        assert_eq!(
+            Quaternion::from_axis_angle(z, PI / 4.0),
+            from.slerp(&to, 0.5)
+        );
+        assert_eq!(
+            Vector::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0),
+            from.slerp(&to, 0.5) * Vector::new(1.0, 0.0, 0.0)
+        );
+
+        // The negated quaternion is the same rotation, slerp still takes the short way
+        let negated = Quaternion::new(-to.w, -to.x, -to.y, -to.z);
+        assert_eq!(
+            Quaternion::from_axis_angle(z, PI / 4.0),
+            from.slerp(&negated, 0.5)
+        );
+
+        // Nearly identical rotations don't divide by a vanishing sine
+        let close = Quaternion::from_axis_angle(z, 0.0001);
+        assert_eq!(
+            Quaternion::from_axis_angle(z, 0.00005),
+            from.slerp(&close, 0.5)
+        );
+    }
+
+    #[test]
+    fn from_rotation_matrix() {
+        let rotations = [
+            Quaternion::from_euler(0.3, -1.2, 2.0),
+            // Half turns, where w is zero and only the other branches work
+            Quaternion::from_axis_angle(Vector::new(1.0, 0.0, 0.0), PI),
+            Quaternion::from_axis_angle(Vector::new(0.0, 1.0, 0.0), PI),
+            Quaternion::from_axis_angle(Vector::new(0.0, 0.0, 1.0), PI),
+        ];
+        for q in rotations.iter() {
+            let recovered = Quaternion::from_rotation(&q.to_matrix4());
+            assert_eq!(q.to_matrix4(), recovered.to_matrix4());
+        }
+    }
+
+    #[test]
+    fn converting_to_matrices() {
+        let q = Quaternion::from_axis_angle(Vector::new(1.0, -1.0, 0.5), 1.3);
+        let p = Point::new(1.0, 2.0, 3.0);
+        assert_eq!(Matrix::from(q), Matrix::from(Matrix4::from(q)));
+        assert_eq!(Ok(q.to_matrix4() * p), q.to_matrix() * p);
+    }
+}
@@ -0,0 +1,214 @@
+use super::*;
+use crate::space::{Point, Vector};
+
+use std::ops::Mul;
+
+// Builds up a transform in reading order, so
+//   Transform::identity().rotate_x(a).scale(2.0, 2.0, 2.0).translate(0.0, 1.0, 0.0)
+// rotates first and translates last. The inverse of every step is known in closed form, so it's
+// kept up to date alongside the matrix instead of being solved for later.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        self.matrix
+    }
+
+    pub fn inverse(&self) -> Matrix4 {
+        self.inverse
+    }
+
+    // The transform that undoes this one
+    pub fn inverted(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    // Applies other after everything already in this transform
+    pub fn then(&self, other: &Transform) -> Transform {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    fn apply(&self, step: Matrix4, inverse: Matrix4) -> Transform {
+        Transform {
+            matrix: step * self.matrix,
+            inverse: self.inverse * inverse,
+        }
+    }
+
+    pub fn translate<X, Y, Z>(&self, x: X, y: Y, z: Z) -> Transform
+    where
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
+    {
+        let (x, y, z) = (x.into(), y.into(), z.into());
+        self.apply(
+            Matrix4::translation(x, y, z),
+            Matrix4::translation(-x, -y, -z),
+        )
+    }
+
+    // Scaling an axis by zero flattens everything onto a plane, which can't be undone, so the
+    // inverse ends up with infinities in it
+    pub fn scale<X, Y, Z>(&self, x: X, y: Y, z: Z) -> Transform
+    where
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
+    {
+        let (x, y, z) = (x.into(), y.into(), z.into());
+        self.apply(
+            Matrix4::scale(x, y, z),
+            Matrix4::scale(1.0 / x, 1.0 / y, 1.0 / z),
+        )
+    }
+
+    pub fn uniform_scale<T: Into<Float>>(&self, factor: T) -> Transform {
+        let factor = factor.into();
+        self.scale(factor, factor, factor)
+    }
+
+    pub fn rotate_x<T: Into<Float>>(&self, radians: T) -> Transform {
+        let radians = radians.into();
+        self.apply(Matrix4::rotation_x(radians), Matrix4::rotation_x(-radians))
+    }
+
+    pub fn rotate_y<T: Into<Float>>(&self, radians: T) -> Transform {
+        let radians = radians.into();
+        self.apply(Matrix4::rotation_y(radians), Matrix4::rotation_y(-radians))
+    }
+
+    pub fn rotate_z<T: Into<Float>>(&self, radians: T) -> Transform {
+        let radians = radians.into();
+        self.apply(Matrix4::rotation_z(radians), Matrix4::rotation_z(-radians))
+    }
+
+    pub fn rotate_axis<T: Into<Float>>(&self, axis: Vector, radians: T) -> Transform {
+        self.rotate(Quaternion::from_axis_angle(axis, radians))
+    }
+
+    pub fn rotate(&self, rotation: Quaternion) -> Transform {
+        let rotation = rotation.normalize();
+        self.apply(rotation.to_matrix4(), rotation.conjugate().to_matrix4())
+    }
+
+    // A single shear only moves one axis in proportion to another, so moving it back the same
+    // amount undoes it
+    pub fn shear(&self, relation: Shear) -> Transform {
+        let shear = Matrix4::shear(relation);
+        let mut inverse = shear;
+        for y in 0..3 {
+            for x in (0..3).filter(|x| *x != y) {
+                inverse[(y, x)] = -shear[(y, x)];
+            }
+        }
+        self.apply(shear, inverse)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl From<Transform> for Matrix4 {
+    fn from(t: Transform) -> Matrix4 {
+        t.matrix
+    }
+}
+
+impl From<Transform> for Matrix {
+    fn from(t: Transform) -> Matrix {
+        t.matrix.into()
+    }
+}
+
+impl Mul<Point> for Transform {
+    type Output = Point;
+    fn mul(self, rhs: Point) -> Point {
+        self.matrix * rhs
+    }
+}
+
+impl Mul<Vector> for Transform {
+    type Output = Vector;
+    fn mul(self, rhs: Vector) -> Vector {
+        self.matrix * rhs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::consts::PI;
+
+    #[test]
+    fn applies_in_reading_order() {
+        // The book's chained transformation, written the way it happens
+        let t = Transform::identity()
+            .rotate_x(PI / 2.0)
+            .uniform_scale(5.0)
+            .translate(10.0, 5.0, 7.0);
+        assert_eq!(Point::new(15.0, 0.0, 7.0), t * Point::new(1.0, 0.0, 1.0));
+        assert_eq!(
+            Matrix4::translation(10.0, 5.0, 7.0)
+                * Matrix4::scale(5.0, 5.0, 5.0)
+                * Matrix4::rotation_x(PI / 2.0),
+            t.matrix()
+        );
+    }
+
+    #[test]
+    fn inverse_is_kept_up_to_date() {
+        let t = Transform::identity()
+            .translate(1.0, -2.0, 3.0)
+            .scale(2.0, 0.5, 4.0)
+            .rotate_y(0.7)
+            .shear(Shear::XZ)
+            .rotate_axis(Vector::new(1.0, 1.0, 0.0), -1.2)
+            .rotate_z(0.3);
+        assert_eq!(Ok(t.inverse()), t.matrix().inverse());
+        assert_eq!(Matrix4::identity(), t.matrix() * t.inverse());
+
+        let p = Point::new(0.5, 4.0, -3.0);
+        assert_eq!(p, t.inverted() * (t * p));
+    }
+
+    #[test]
+    fn composing_transforms() {
+        let first = Transform::identity().rotate_z(PI / 2.0);
+        let second = Transform::identity().translate(1.0, 0.0, 0.0);
+        let both = first.then(&second);
+        assert_eq!(Point::new(1.0, 1.0, 0.0), both * Point::new(1.0, 0.0, 0.0));
+        assert_eq!(first.rotate_z(0.0).translate(1.0, 0.0, 0.0), both);
+        assert_eq!(
+            Vector::new(0.0, 1.0, 0.0),
+            both * Vector::new(1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn converting() {
+        let t = Transform::identity().rotate_y(1.0).translate(0.0, 2.0, 0.0);
+        assert_eq!(Matrix::from(t.matrix()), Matrix::from(t));
+        assert_eq!(Transform::identity(), Transform::default());
+    }
+}
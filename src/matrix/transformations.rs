@@ -12,92 +12,49 @@ pub enum Shear {
     ZY,
 }
 
+// The general matrix versions of the Matrix4 transformations
 impl Matrix {
     pub fn translation<X, Y, Z>(x: X, y: Y, z: Z) -> Matrix
     where
-        X: Into<f32>,
-        Y: Into<f32>,
-        Z: Into<f32>,
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
     {
-        matrix![
-            1.0, 0.0, 0.0, x.into();
-            0.0, 1.0, 0.0, y.into();
-            0.0, 0.0, 1.0, z.into();
-            0.0, 0.0, 0.0, 1.0
-        ]
+        Matrix4::translation(x, y, z).into()
     }
 
     pub fn scale<X, Y, Z>(x: X, y: Y, z: Z) -> Matrix
     where
-        X: Into<f32>,
-        Y: Into<f32>,
-        Z: Into<f32>,
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
     {
-        matrix![
-            x.into(), 0.0,      0.0,      0.0;
-            0.0,      y.into(), 0.0,      0.0;
-            0.0,      0.0,      z.into(), 0.0;
-            0.0,      0.0,      0.0,      1.0
-        ]
-    }
-
-    pub fn rotation_x<T: Into<f32>>(radians: T) -> Matrix {
-        let radians = radians.into();
-        let sin = radians.sin();
-        let cos = radians.cos();
-        matrix![
-            1.0, 0.0, 0.0,        0.0;
-            0.0, cos, -1.0 * sin, 0.0;
-            0.0, sin, cos,        0.0;
-            0.0, 0.0, 0.0,        1.0
-        ]
-    }
-
-    pub fn rotation_y<T: Into<f32>>(radians: T) -> Matrix {
-        let radians = radians.into();
-        let sin = radians.sin();
-        let cos = radians.cos();
-        matrix![
-            cos,        0.0, sin, 0.0;
-            0.0,        1.0, 0.0, 0.0;
-            -1.0 * sin, 0.0, cos, 0.0;
-            0.0,        0.0, 0.0, 1.0
-        ]
-    }
-
-    pub fn rotation_z<T: Into<f32>>(radians: T) -> Matrix {
-        let radians = radians.into();
-        let sin = radians.sin();
-        let cos = radians.cos();
-        matrix![
-            cos, -1.0 * sin, 0.0, 0.0;
-            sin, cos,        0.0, 0.0;
-            0.0, 0.0,        1.0, 0.0;
-            0.0, 0.0,        0.0, 1.0
-        ]
+        Matrix4::scale(x, y, z).into()
+    }
+
+    pub fn rotation_x<T: Into<Float>>(radians: T) -> Matrix {
+        Matrix4::rotation_x(radians).into()
+    }
+
+    pub fn rotation_y<T: Into<Float>>(radians: T) -> Matrix {
+        Matrix4::rotation_y(radians).into()
+    }
+
+    pub fn rotation_z<T: Into<Float>>(radians: T) -> Matrix {
+        Matrix4::rotation_z(radians).into()
+    }
+
+    pub fn rotation_axis<T: Into<Float>>(axis: Vector, radians: T) -> Matrix {
+        Matrix4::rotation_axis(axis, radians).into()
     }
 
     pub fn shear(relation: Shear) -> Matrix {
-        let (xy, xz, yx, yz, zx, zy) = match relation {
-            Shear::XY => (1.0, 0.0, 0.0, 0.0, 0.0, 0.0),
-            Shear::XZ => (0.0, 1.0, 0.0, 0.0, 0.0, 0.0),
-            Shear::YX => (0.0, 0.0, 1.0, 0.0, 0.0, 0.0),
-            Shear::YZ => (0.0, 0.0, 0.0, 1.0, 0.0, 0.0),
-            Shear::ZX => (0.0, 0.0, 0.0, 0.0, 1.0, 0.0),
-            Shear::ZY => (0.0, 0.0, 0.0, 0.0, 0.0, 1.0),
-        };
-
-        matrix![
-            1.0, xy,  xz,  0.0;
-            yx,  1.0, yz,  0.0;
-            zx,  zy,  1.0, 0.0;
-            0.0, 0.0, 0.0, 1.0
-        ]
+        Matrix4::shear(relation).into()
     }
 }
 
 impl Mul<Point> for Matrix {
-    type Output = Result<Point, String>;
+    type Output = Result<Point, MatrixError>;
     fn mul(self, rhs: Point) -> Self::Output {
         match self * rhs.matrix() {
             Err(e) => Err(e),
@@ -106,22 +63,22 @@ impl Mul<Point> for Matrix {
     }
 }
 
-impl Mul<Point> for Result<Matrix, String> {
-    type Output = Result<Point, String>;
+impl Mul<Point> for Result<Matrix, MatrixError> {
+    type Output = Result<Point, MatrixError>;
     fn mul(self, rhs: Point) -> Self::Output {
         self.and_then(|lhs| lhs * rhs)
     }
 }
 
-impl Mul<Result<Point, String>> for Matrix {
-    type Output = Result<Point, String>;
-    fn mul(self, rhs: Result<Point, String>) -> Self::Output {
+impl Mul<Result<Point, MatrixError>> for Matrix {
+    type Output = Result<Point, MatrixError>;
+    fn mul(self, rhs: Result<Point, MatrixError>) -> Self::Output {
         rhs.and_then(|rhs| self * rhs)
     }
 }
 
 impl Mul<Vector> for Matrix {
-    type Output = Result<Vector, String>;
+    type Output = Result<Vector, MatrixError>;
     fn mul(self, rhs: Vector) -> Self::Output {
         match self * rhs.matrix() {
             Err(e) => Err(e),
@@ -129,16 +86,16 @@ impl Mul<Vector> for Matrix {
         }
     }
 }
-impl Mul<Vector> for Result<Matrix, String> {
-    type Output = Result<Vector, String>;
+impl Mul<Vector> for Result<Matrix, MatrixError> {
+    type Output = Result<Vector, MatrixError>;
     fn mul(self, rhs: Vector) -> Self::Output {
         self.and_then(|lhs| lhs * rhs)
     }
 }
 
-impl Mul<Result<Vector, String>> for Matrix {
-    type Output = Result<Vector, String>;
-    fn mul(self, rhs: Result<Vector, String>) -> Self::Output {
+impl Mul<Result<Vector, MatrixError>> for Matrix {
+    type Output = Result<Vector, MatrixError>;
+    fn mul(self, rhs: Result<Vector, MatrixError>) -> Self::Output {
         rhs.and_then(|rhs| self * rhs)
     }
 }
@@ -146,7 +103,7 @@ impl Mul<Result<Vector, String>> for Matrix {
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::f32::consts::PI;
+    use crate::util::consts::PI;
 
     #[test]
     fn translation() {
@@ -220,7 +177,11 @@ mod test {
         let half_quarter = Matrix::rotation_x(PI / 4.0);
         let full_quarter = Matrix::rotation_x(PI / 2.0);
         assert_eq!(
-            Ok(Point::new(0.0, 2.0_f32.sqrt() / 2.0, 2.0_f32.sqrt() / 2.0)),
+            Ok(Point::new(
+                0.0,
+                (2.0 as Float).sqrt() / 2.0,
+                (2.0 as Float).sqrt() / 2.0
+            )),
             half_quarter * p
         );
         assert_eq!(Ok(Point::new(0.0, 0.0, 1.0)), full_quarter * p);
@@ -233,8 +194,8 @@ mod test {
         assert_eq!(
             Ok(Point::new(
                 0.0,
-                2.0_f32.sqrt() / 2.0,
-                -1.0 * (2.0_f32.sqrt() / 2.0)
+                (2.0 as Float).sqrt() / 2.0,
+                -((2.0 as Float).sqrt() / 2.0)
             )),
             half_quarter.inverse() * p
         );
@@ -246,7 +207,11 @@ mod test {
         let half_quarter = Matrix::rotation_y(PI / 4.0);
         let full_quarter = Matrix::rotation_y(PI / 2.0);
         assert_eq!(
-            Ok(Point::new(2.0_f32.sqrt() / 2.0, 0.0, 2.0_f32.sqrt() / 2.0)),
+            Ok(Point::new(
+                (2.0 as Float).sqrt() / 2.0,
+                0.0,
+                (2.0 as Float).sqrt() / 2.0
+            )),
             half_quarter * p
         );
         assert_eq!(Ok(Point::new(1.0, 0.0, 0.0)), full_quarter * p);
@@ -259,8 +224,8 @@ mod test {
         let full_quarter = Matrix::rotation_z(PI / 2.0);
         assert_eq!(
             Ok(Point::new(
-                -1.0 * (2.0_f32.sqrt() / 2.0),
-                2.0_f32.sqrt() / 2.0,
+                -((2.0 as Float).sqrt() / 2.0),
+                (2.0 as Float).sqrt() / 2.0,
                 0.0
             )),
             half_quarter * p
@@ -268,6 +233,16 @@ mod test {
         assert_eq!(Ok(Point::new(-1.0, 0.0, 0.0)), full_quarter * p);
     }
 
+    #[test]
+    fn rotating_point_around_any_axis() {
+        let p = Point::new(1.0, 0.0, 0.0);
+        let r = Matrix::rotation_axis(Vector::new(1.0, 1.0, 0.0), PI);
+        assert_eq!(Ok(Point::new(0.0, 1.0, 0.0)), r * p);
+
+        let around_x = Matrix::rotation_axis(Vector::new(3.0, 0.0, 0.0), PI / 4.0);
+        assert_eq!(Matrix::rotation_x(PI / 4.0), around_x);
+    }
+
     #[test]
     fn shearing_x_by_y() {
         let p = Point::new(2.0, 3.0, 4.0);
@@ -1,12 +1,21 @@
-extern crate rand;
+mod bounds;
+mod bvh;
+mod packet;
 
+pub use bounds::*;
+pub use bvh::*;
+pub use packet::*;
+
+use crate::matrix::Matrix4;
+use crate::simd;
 use crate::space::{Point, Vector};
-use crate::util;
+use crate::util::{self, Float};
+use crate::world::ShapeHandle;
 
-use rand::Rng;
+use std::iter::FromIterator;
 use std::ops::Index;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ray {
     pub origin: Point,
     pub direction: Vector,
@@ -17,25 +26,27 @@ impl Ray {
         Ray { origin, direction }
     }
 
-    pub fn position<T: Into<f32>>(&self, t: T) -> Point {
+    pub fn position<T: Into<Float>>(&self, t: T) -> Point {
         self.origin + (self.direction * t.into())
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Sphere {
-    id: i32,
+    pub fn transform(&self, m: &Matrix4) -> Ray {
+        Ray::new(*m * self.origin, *m * self.direction)
+    }
 }
 
+// The unit sphere at the origin. Shapes don't know who they are, the World that owns them hands
+// out a ShapeHandle for each one and that's what intersections refer to.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Sphere {}
+
 impl Sphere {
     pub fn new() -> Sphere {
-        let mut rng = rand::thread_rng();
-        Sphere {
-            id: rng.gen::<i32>(),
-        }
+        Sphere {}
     }
 
-    pub fn intersect(&self, ray: Ray) -> Option<Intersections> {
+    // Where the ray enters and leaves the sphere, nearest first
+    pub fn intersect(&self, ray: &Ray) -> Option<[Float; 2]> {
         let sphere_to_ray = ray.origin - Point::new(0.0, 0.0, 0.0);
         let a = ray.direction.dot(&ray.direction);
         let b = 2.0 * (ray.direction.dot(&sphere_to_ray));
@@ -46,62 +57,145 @@ impl Sphere {
         }
         let t1 = (-b - discriminant.sqrt()) / (2.0 * a);
         let t2 = (-b + discriminant.sqrt()) / (2.0 * a);
-        Some(Intersections(vec![
-            Intersection {
-                t: t1,
-                object: self,
-            },
-            Intersection {
-                t: t2,
-                object: self,
-            },
-        ]))
+        Some([t1, t2])
+    }
+
+    // The nearest intersection in front of the ray
+    pub fn hit(&self, ray: &Ray) -> Option<Float> {
+        self.intersect(ray)?.iter().copied().find(|t| *t > 0.0)
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub struct Intersections<'a>(Vec<Intersection<'a>>);
+impl Sphere {
+    pub fn bounds(&self) -> Bounds {
+        Bounds::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0))
+    }
+}
+
+// Always sorted nearest first, ties keeping the order they were added in
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Intersections(Vec<Intersection>);
 
-#[derive(Debug, Clone)]
-pub struct Intersection<'a> {
-    t: f32,
-    object: &'a Sphere,
+#[derive(Debug, Copy, Clone)]
+pub struct Intersection {
+    pub t: Float,
+    pub object: ShapeHandle,
+}
+
+impl Intersection {
+    pub fn new<T: Into<Float>>(t: T, object: ShapeHandle) -> Intersection {
+        Intersection {
+            t: t.into(),
+            object,
+        }
+    }
 }
 
-impl<'a> PartialEq for Intersection<'a> {
+impl PartialEq for Intersection {
     fn eq(&self, rhs: &Self) -> bool {
-        util::feq(self.t, rhs.t) && self.object.id == rhs.object.id
+        util::feq(self.t, rhs.t) && self.object == rhs.object
     }
 }
 
-impl<'a> Intersections<'a> {
+impl Intersections {
+    // NaN and infinite t aren't anywhere along the ray, and a NaN would sort to one end and
+    // break the binary searches below, so they are dropped
+    pub fn new(mut intersections: Vec<Intersection>) -> Intersections {
+        intersections.retain(|i| i.t.is_finite());
+        intersections.sort_by(|a, b| a.t.total_cmp(&b.t));
+        Intersections(intersections)
+    }
+
     pub fn len(&self) -> usize {
         self.0.len()
     }
 
-    pub fn hit(&self) -> Option<&'a Intersection> {
-        let mut ret = None;
-        for intersection in &self.0 {
-            if intersection.t > 0.0 {
-                ret = match ret {
-                    None => Some(intersection),
-                    Some(i) => {
-                        if i.t < intersection.t {
-                            Some(i)
-                        } else {
-                            Some(intersection)
-                        }
-                    }
-                };
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Intersection> {
+        self.0.iter()
+    }
+
+    pub fn as_slice(&self) -> &[Intersection] {
+        &self.0
+    }
+
+    // Both sets together, still sorted. Where t ties, self's come first.
+    pub fn merge(self, other: Intersections) -> Intersections {
+        let mut merged = Vec::with_capacity(self.len() + other.len());
+        let (mut a, mut b) = (
+            self.0.into_iter().peekable(),
+            other.0.into_iter().peekable(),
+        );
+        while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+            if y.t < x.t {
+                merged.extend(b.next());
+            } else {
+                merged.extend(a.next());
             }
         }
-        ret
+        merged.extend(a);
+        merged.extend(b);
+        Intersections(merged)
+    }
+
+    // Only the intersections strictly between t_min and t_max
+    pub fn in_range(&self, t_min: Float, t_max: Float) -> Intersections {
+        Intersections(self.range(t_min, t_max).to_vec())
+    }
+
+    // The nearest intersection in front of the ray
+    pub fn hit(&self) -> Option<&Intersection> {
+        self.hit_in_range(0.0, Float::INFINITY)
+    }
+
+    // The nearest intersection strictly between t_min and t_max, e.g. past a small offset to
+    // keep a surface from shadowing itself, or nearer than a light
+    pub fn hit_in_range(&self, t_min: Float, t_max: Float) -> Option<&Intersection> {
+        self.range(t_min, t_max).first()
+    }
+
+    fn range(&self, t_min: Float, t_max: Float) -> &[Intersection] {
+        let start = self.0.partition_point(|i| i.t <= t_min);
+        let end = self.0.partition_point(|i| i.t < t_max).max(start);
+        &self.0[start..end]
     }
 }
 
-impl<'a> Index<usize> for Intersections<'a> {
-    type Output = Intersection<'a>;
-    fn index(&self, x: usize) -> &Intersection<'a> {
+impl FromIterator<Intersection> for Intersections {
+    fn from_iter<I: IntoIterator<Item = Intersection>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+// Collects each shape's intersections into one set
+impl FromIterator<Intersections> for Intersections {
+    fn from_iter<I: IntoIterator<Item = Intersections>>(iter: I) -> Self {
+        iter.into_iter().fold(Self::default(), Self::merge)
+    }
+}
+
+impl IntoIterator for Intersections {
+    type Item = Intersection;
+    type IntoIter = std::vec::IntoIter<Intersection>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Intersections {
+    type Item = &'a Intersection;
+    type IntoIter = std::slice::Iter<'a, Intersection>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Index<usize> for Intersections {
+    type Output = Intersection;
+    fn index(&self, x: usize) -> &Intersection {
         &self.0[x]
     }
 }
@@ -125,27 +219,25 @@ mod test {
     fn ray_intersects_sphere_at_two_points() {
         let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let sphere = Sphere::new();
-        let intersections = sphere.intersect(ray).unwrap();
-        assert_eq!(2, intersections.len());
-        assert_eq!(4.0, intersections[0].t);
-        assert_eq!(6.0, intersections[1].t);
+        let intersections = sphere.intersect(&ray).unwrap();
+        assert_eq!(4.0, intersections[0]);
+        assert_eq!(6.0, intersections[1]);
     }
 
     #[test]
     fn ray_intersects_sphere_at_tangent() {
         let ray = Ray::new(Point::new(0.0, 1.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let sphere = Sphere::new();
-        let intersections = sphere.intersect(ray).unwrap();
-        assert_eq!(2, intersections.len());
-        assert_eq!(5.0, intersections[0].t);
-        assert_eq!(5.0, intersections[1].t);
+        let intersections = sphere.intersect(&ray).unwrap();
+        assert_eq!(5.0, intersections[0]);
+        assert_eq!(5.0, intersections[1]);
     }
 
     #[test]
     fn ray_misses_sphere() {
         let ray = Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0));
         let sphere = Sphere::new();
-        let intersections = sphere.intersect(ray);
+        let intersections = sphere.intersect(&ray);
         assert_eq!(None, intersections);
     }
 
@@ -153,73 +245,153 @@ mod test {
     fn ray_originates_inside_sphere() {
         let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
         let sphere = Sphere::new();
-        let intersections = sphere.intersect(ray).unwrap();
-        assert_eq!(2, intersections.len());
-        assert_eq!(-1.0, intersections[0].t);
-        assert_eq!(1.0, intersections[1].t);
+        let intersections = sphere.intersect(&ray).unwrap();
+        assert_eq!(-1.0, intersections[0]);
+        assert_eq!(1.0, intersections[1]);
+        assert_eq!(Some(1.0), sphere.hit(&ray));
     }
 
     #[test]
     fn ray_infront_of_sphere() {
         let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Vector::new(0.0, 0.0, 1.0));
         let sphere = Sphere::new();
-        let intersections = sphere.intersect(ray).unwrap();
-        assert_eq!(2, intersections.len());
-        assert_eq!(-6.0, intersections[0].t);
-        assert_eq!(-4.0, intersections[1].t);
+        let intersections = sphere.intersect(&ray).unwrap();
+        assert_eq!(-6.0, intersections[0]);
+        assert_eq!(-4.0, intersections[1]);
+        assert_eq!(None, sphere.hit(&ray));
     }
 
     #[test]
     fn hit_with_all_positive() {
-        let s = Sphere::new();
-        let intersections = Intersections(vec![
-            Intersection { t: 1.0, object: &s },
-            Intersection { t: 2.0, object: &s },
+        let s = ShapeHandle::new(0);
+        let intersections = Intersections::new(vec![
+            Intersection { t: 1.0, object: s },
+            Intersection { t: 2.0, object: s },
         ]);
         assert_eq!(1.0, intersections.hit().unwrap().t);
     }
 
     #[test]
     fn hit_with_some_negative() {
-        let s = Sphere::new();
-        let intersections = Intersections(vec![
-            Intersection {
-                t: -1.0,
-                object: &s,
-            },
-            Intersection { t: 2.0, object: &s },
+        let s = ShapeHandle::new(0);
+        let intersections = Intersections::new(vec![
+            Intersection { t: -1.0, object: s },
+            Intersection { t: 2.0, object: s },
         ]);
         assert_eq!(2.0, intersections.hit().unwrap().t);
     }
 
     #[test]
     fn hit_with_all_negative() {
-        let s = Sphere::new();
-        let intersections = Intersections(vec![
-            Intersection {
-                t: -1.0,
-                object: &s,
-            },
-            Intersection {
-                t: -2.0,
-                object: &s,
-            },
+        let s = ShapeHandle::new(0);
+        let intersections = Intersections::new(vec![
+            Intersection { t: -1.0, object: s },
+            Intersection { t: -2.0, object: s },
         ]);
         assert_eq!(None, intersections.hit());
     }
 
     #[test]
     fn hit_always_lowest_nonnegative_intersection() {
-        let s = Sphere::new();
-        let intersections = Intersections(vec![
-            Intersection { t: 5.0, object: &s },
-            Intersection { t: 7.0, object: &s },
-            Intersection {
-                t: -3.0,
-                object: &s,
-            },
-            Intersection { t: 2.0, object: &s },
+        let s = ShapeHandle::new(0);
+        let intersections = Intersections::new(vec![
+            Intersection { t: 5.0, object: s },
+            Intersection { t: 7.0, object: s },
+            Intersection { t: -3.0, object: s },
+            Intersection { t: 2.0, object: s },
         ]);
         assert_eq!(2.0, intersections.hit().unwrap().t);
     }
+
+    fn ts(intersections: &Intersections) -> Vec<Float> {
+        intersections.iter().map(|i| i.t).collect()
+    }
+
+    #[test]
+    fn sorted_nearest_first() {
+        let s = ShapeHandle::new(0);
+        let intersections: Intersections = [3.0, -1.0, 2.0, 0.5]
+            .iter()
+            .map(|t| Intersection::new(*t, s))
+            .collect();
+        assert_eq!(vec![-1.0, 0.5, 2.0, 3.0], ts(&intersections));
+        assert_eq!(
+            intersections,
+            Intersections::new(intersections.as_slice().to_vec())
+        );
+        assert!(Intersections::default().is_empty());
+    }
+
+    #[test]
+    fn merging_shapes() {
+        let (a, b, c) = (
+            ShapeHandle::new(0),
+            ShapeHandle::new(1),
+            ShapeHandle::new(2),
+        );
+        let first = Intersections::new(vec![Intersection::new(1.0, a), Intersection::new(4.0, a)]);
+        let second = Intersections::new(vec![Intersection::new(2.0, b), Intersection::new(4.0, b)]);
+        let merged = first.clone().merge(second.clone());
+        assert_eq!(vec![1.0, 2.0, 4.0, 4.0], ts(&merged));
+        assert_eq!(a, merged[2].object);
+        assert_eq!(b, merged[3].object);
+
+        let third = Intersections::new(vec![Intersection::new(-2.0, c)]);
+        let all: Intersections = vec![first, second, third].into_iter().collect();
+        assert_eq!(vec![-2.0, 1.0, 2.0, 4.0, 4.0], ts(&all));
+        assert_eq!(merged, merged.clone().merge(Intersections::default()));
+    }
+
+    #[test]
+    fn iterating() {
+        let s = ShapeHandle::new(0);
+        let intersections =
+            Intersections::new(vec![Intersection::new(2.0, s), Intersection::new(1.0, s)]);
+        let mut total = 0.0;
+        for intersection in &intersections {
+            total += intersection.t;
+        }
+        assert_eq!(3.0, total);
+        let owned: Vec<Intersection> = intersections.into_iter().collect();
+        assert_eq!(
+            vec![Intersection::new(1.0, s), Intersection::new(2.0, s)],
+            owned
+        );
+    }
+
+    #[test]
+    fn hits_in_a_range() {
+        let s = ShapeHandle::new(0);
+        let intersections: Intersections = [-1.0, 0.0, 0.001, 2.0, 5.0, 9.0]
+            .iter()
+            .map(|t| Intersection::new(*t, s))
+            .collect();
+        assert_eq!(0.001, intersections.hit().unwrap().t);
+        // Past a shadow offset, and nearer than a light
+        assert_eq!(2.0, intersections.hit_in_range(0.01, 5.0).unwrap().t);
+        assert_eq!(None, intersections.hit_in_range(2.0, 5.0));
+        assert_eq!(None, intersections.hit_in_range(5.0, 2.0));
+
+        assert_eq!(vec![2.0, 5.0], ts(&intersections.in_range(0.01, 9.0)));
+        assert_eq!(
+            ts(&intersections),
+            ts(&intersections.in_range(Float::NEG_INFINITY, Float::INFINITY))
+        );
+    }
+
+    #[test]
+    fn non_finite_ts_are_dropped() {
+        let s = ShapeHandle::new(0);
+        let zero: Float = 0.0;
+        let nan = -(zero / zero);
+        let intersections: Intersections = [nan, 1.0, 2.0, Float::INFINITY, Float::NAN]
+            .iter()
+            .map(|t| Intersection::new(*t, s))
+            .collect();
+        assert_eq!(vec![1.0, 2.0], ts(&intersections));
+        assert_eq!(1.0, intersections.hit().unwrap().t);
+
+        let merged = intersections.merge(Intersections::new(vec![Intersection::new(nan, s)]));
+        assert_eq!(1.0, merged.hit().unwrap().t);
+    }
 }
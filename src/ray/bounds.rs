@@ -0,0 +1,158 @@
+use super::*;
+
+// An axis aligned box, the cheap stand in for a shape's extent that the BVH sorts and tests rays
+// against before trying the shape itself. The empty box has min above max so that any union
+// with it gives the other box back.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Bounds {
+    // The corners can be any two opposite corners
+    pub fn new(a: Point, b: Point) -> Bounds {
+        let (a, b) = (a.lanes(), b.lanes());
+        Bounds {
+            min: Point::from_lanes(simd::scalar::min(a, b)),
+            max: Point::from_lanes(simd::scalar::max(a, b)),
+        }
+    }
+
+    pub fn empty() -> Bounds {
+        let (low, high) = (Float::NEG_INFINITY, Float::INFINITY);
+        Bounds {
+            min: Point::new(high, high, high),
+            max: Point::new(low, low, low),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let (Point(x1, y1, z1), Point(x2, y2, z2)) = (self.min, self.max);
+        x1 > x2 || y1 > y2 || z1 > z2
+    }
+
+    pub fn union(&self, rhs: &Bounds) -> Bounds {
+        let (a, b) = (self.min.lanes(), rhs.min.lanes());
+        let (c, d) = (self.max.lanes(), rhs.max.lanes());
+        Bounds {
+            min: Point::from_lanes(simd::scalar::min(a, b)),
+            max: Point::from_lanes(simd::scalar::max(c, d)),
+        }
+    }
+
+    pub fn include(&self, point: &Point) -> Bounds {
+        self.union(&Bounds {
+            min: *point,
+            max: *point,
+        })
+    }
+
+    pub fn extent(&self) -> Vector {
+        self.max - self.min
+    }
+
+    pub fn centroid(&self) -> Point {
+        self.min + self.extent() * 0.5
+    }
+
+    // 0, 1 or 2 for x, y or z, whichever the box is longest along
+    pub fn largest_axis(&self) -> usize {
+        let Vector(x, y, z) = self.extent();
+        if x >= y && x >= z {
+            0
+        } else if y >= z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> Float {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let Vector(x, y, z) = self.extent();
+        2.0 * (x * y + y * z + z * x)
+    }
+
+    // The slab test, clipping the ray against each pair of planes in turn. Gives the distances
+    // the ray enters and leaves the box between 0 and t_max, or None if it misses in that span.
+    pub fn intersect(&self, ray: &Ray, t_max: Float) -> Option<(Float, Float)> {
+        let (origin, direction) = (ray.origin.lanes(), ray.direction.lanes());
+        let (min, max) = (self.min.lanes(), self.max.lanes());
+        let (mut enter, mut leave) = (0.0, t_max);
+        for axis in 0..3 {
+            let inverse = 1.0 / direction[axis];
+            let a = (min[axis] - origin[axis]) * inverse;
+            let b = (max[axis] - origin[axis]) * inverse;
+            // Written like simd::min and max so that a ray along a face, where a or b is NaN,
+            // is treated the same way by the packet version
+            let near = if a < b { a } else { b };
+            let far = if a > b { a } else { b };
+            enter = if near > enter { near } else { enter };
+            leave = if far < leave { far } else { leave };
+            if enter > leave {
+                return None;
+            }
+        }
+        Some((enter, leave))
+    }
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn building_from_corners_and_points() {
+        let b = Bounds::new(Point::new(1.0, -2.0, 3.0), Point::new(-1.0, 2.0, 0.0));
+        assert_eq!(Point::new(-1.0, -2.0, 0.0), b.min);
+        assert_eq!(Point::new(1.0, 2.0, 3.0), b.max);
+
+        assert!(Bounds::empty().is_empty());
+        let b = Bounds::empty()
+            .include(&Point::new(1.0, 0.0, 0.0))
+            .include(&Point::new(0.0, 4.0, -2.0));
+        assert_eq!(
+            Bounds::new(Point::new(0.0, 0.0, -2.0), Point::new(1.0, 4.0, 0.0)),
+            b
+        );
+        assert_eq!(b, b.union(&Bounds::empty()));
+    }
+
+    #[test]
+    fn measuring() {
+        let b = Bounds::new(Point::new(0.0, 0.0, 0.0), Point::new(1.0, 2.0, 3.0));
+        assert_eq!(Point::new(0.5, 1.0, 1.5), b.centroid());
+        assert_eq!(2, b.largest_axis());
+        assert_eq!(22.0, b.surface_area());
+        assert_eq!(0.0, Bounds::empty().surface_area());
+    }
+
+    #[test]
+    fn slab_test() {
+        let b = Bounds::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Point::new(0.5, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(Some((4.0, 6.0)), b.intersect(&ray, Float::INFINITY));
+        // Stopping short of the box, or an earlier hit, rules it out
+        assert_eq!(None, b.intersect(&ray, 3.0));
+
+        let ray = Ray::new(Point::new(2.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(None, b.intersect(&ray, Float::INFINITY));
+
+        // From inside it enters at 0
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(-2.0, 0.0, 0.0));
+        assert_eq!(Some((0.0, 0.5)), b.intersect(&ray, Float::INFINITY));
+
+        // Diagonals past a corner miss
+        let ray = Ray::new(Point::new(-3.0, 0.0, 0.0), Vector::new(1.0, 1.0, 0.0));
+        assert_eq!(None, b.intersect(&ray, Float::INFINITY));
+    }
+}
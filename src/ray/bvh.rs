@@ -0,0 +1,331 @@
+use super::*;
+use crate::simd::Lanes;
+
+use std::cmp::Ordering;
+
+// Leaves hold up to this many primitives, below it testing them all is cheaper than more boxes
+const LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        bounds: Bounds,
+        first: usize,
+        count: usize,
+    },
+    // The left child is always the next node, so only the right one needs an index
+    Interior {
+        bounds: Bounds,
+        right: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Bounds {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+// A bounding volume hierarchy over anything with bounds. It only knows primitives by their
+// index in the slice it was built from, and asks the caller to intersect them, so the same
+// tree works for spheres, triangles or whole objects.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    // Primitive indices, ordered so that every leaf covers a contiguous run
+    order: Vec<usize>,
+}
+
+impl Bvh {
+    // Splits each node at the median centroid along its widest axis
+    pub fn build(bounds: &[Bounds]) -> Bvh {
+        let mut order: Vec<usize> = (0..bounds.len()).collect();
+        let mut bvh = Bvh::default();
+        if !bounds.is_empty() {
+            bvh.build_node(bounds, &mut order, 0);
+        }
+        bvh.order = order;
+        bvh
+    }
+
+    fn build_node(&mut self, bounds: &[Bounds], order: &mut [usize], first: usize) -> usize {
+        let index = self.nodes.len();
+        let total = order
+            .iter()
+            .fold(Bounds::empty(), |total, i| total.union(&bounds[*i]));
+        if order.len() <= LEAF_SIZE {
+            self.nodes.push(Node::Leaf {
+                bounds: total,
+                first,
+                count: order.len(),
+            });
+            return index;
+        }
+
+        let centroids = order.iter().fold(Bounds::empty(), |all, i| {
+            all.include(&bounds[*i].centroid())
+        });
+        let axis = centroids.largest_axis();
+        let centroid = |i: &usize| bounds[*i].centroid().lanes()[axis];
+        let middle = order.len() / 2;
+        order.select_nth_unstable_by(middle, |a, b| {
+            centroid(a)
+                .partial_cmp(&centroid(b))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        self.nodes.push(Node::Interior {
+            bounds: total,
+            right: 0,
+        });
+        let (left, right) = order.split_at_mut(middle);
+        self.build_node(bounds, left, first);
+        let right_index = self.build_node(bounds, right, first + middle);
+        if let Node::Interior { right, .. } = &mut self.nodes[index] {
+            *right = right_index;
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn bounds(&self) -> Bounds {
+        self.nodes
+            .first()
+            .map_or_else(Bounds::empty, |node| *node.bounds())
+    }
+
+    // The closest primitive in front of the ray and how far along it is. intersect gets a
+    // primitive's index and gives back its nearest positive t, if the ray hits it.
+    pub fn intersect<F>(&self, ray: &Ray, mut intersect: F) -> Option<(usize, Float)>
+    where
+        F: FnMut(usize, &Ray) -> Option<Float>,
+    {
+        let mut closest: Option<(usize, Float)> = None;
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds().intersect(ray, t_max_of(closest)).is_none() {
+                continue;
+            }
+            match node {
+                Node::Leaf { first, count, .. } => {
+                    for primitive in &self.order[*first..*first + *count] {
+                        match intersect(*primitive, ray) {
+                            Some(t) if t > 0.0 && t < t_max_of(closest) => {
+                                closest = Some((*primitive, t))
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                Node::Interior { right, .. } => {
+                    stack.push(*right);
+                    stack.push(index + 1);
+                }
+            }
+        }
+        closest
+    }
+
+    // Like intersect for every ray in the packet. Lanes go down the tree together, a node is
+    // visited if any of them hits its box, and only those lanes carry on below it. When the
+    // rays aren't coherent they'd rarely agree on a node, so each is traced on its own instead,
+    // with intersect handed single ray packets.
+    pub fn intersect_packet<F>(
+        &self,
+        packet: &RayPacket,
+        mut intersect: F,
+    ) -> [Option<(usize, Float)>; PACKET_WIDTH]
+    where
+        F: FnMut(usize, &RayPacket) -> [Option<Float>; PACKET_WIDTH],
+    {
+        let mut closest = [None; PACKET_WIDTH];
+        if !packet.is_coherent() {
+            for (lane, closest) in closest.iter_mut().enumerate() {
+                if let Some(ray) = packet.ray(lane) {
+                    *closest = self.intersect(&ray, |primitive, ray| {
+                        intersect(primitive, &RayPacket::new(std::slice::from_ref(ray)))[0]
+                    });
+                }
+            }
+            return closest;
+        }
+
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push((0, packet.active()));
+        }
+        while let Some((index, mask)) = stack.pop() {
+            let mut t_max: Lanes = [Float::INFINITY; PACKET_WIDTH];
+            for (t_max, closest) in t_max.iter_mut().zip(closest.iter()) {
+                *t_max = t_max_of(*closest);
+            }
+            let node = &self.nodes[index];
+            let mut hits = node.bounds().intersect_packet(packet, t_max);
+            for (hit, active) in hits.iter_mut().zip(mask.iter()) {
+                *hit &= *active;
+            }
+            if !hits.iter().any(|hit| *hit) {
+                continue;
+            }
+            match node {
+                Node::Leaf { first, count, .. } => {
+                    for primitive in &self.order[*first..*first + *count] {
+                        let ts = intersect(*primitive, packet);
+                        for lane in 0..PACKET_WIDTH {
+                            match ts[lane] {
+                                Some(t) if hits[lane] && t > 0.0 && t < t_max_of(closest[lane]) => {
+                                    closest[lane] = Some((*primitive, t))
+                                }
+                                _ => (),
+                            }
+                        }
+                    }
+                }
+                Node::Interior { right, .. } => {
+                    stack.push((*right, hits));
+                    stack.push((index + 1, hits));
+                }
+            }
+        }
+        closest
+    }
+}
+
+fn t_max_of(closest: Option<(usize, Float)>) -> Float {
+    closest.map_or(Float::INFINITY, |(_, t)| t)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::matrix::Matrix4;
+
+    // A grid of unit spheres, spread out along x and y and staggered in z
+    fn spheres() -> Vec<Matrix4> {
+        let mut transforms = vec![];
+        for y in -3..=3 {
+            for x in -3..=3 {
+                let (x, y) = (x as Float * 2.5, y as Float * 2.5);
+                let z = ((x + y) * 0.7).sin() * 3.0;
+                transforms.push(Matrix4::translation(x, y, z).inverse().unwrap());
+            }
+        }
+        transforms
+    }
+
+    fn bounds(inverses: &[Matrix4]) -> Vec<Bounds> {
+        inverses
+            .iter()
+            .map(|inverse| {
+                let center = inverse.inverse().unwrap() * Point::new(0.0, 0.0, 0.0);
+                let radius = Vector::new(1.0, 1.0, 1.0);
+                Bounds::new(center - radius, center + radius)
+            })
+            .collect()
+    }
+
+    // Tests every sphere, the answer the tree has to agree with
+    fn brute_force(sphere: &Sphere, inverses: &[Matrix4], ray: &Ray) -> Option<(usize, Float)> {
+        let mut closest: Option<(usize, Float)> = None;
+        for (i, inverse) in inverses.iter().enumerate() {
+            if let Some(t) = sphere.hit(&ray.transform(inverse)) {
+                if t < t_max_of(closest) {
+                    closest = Some((i, t));
+                }
+            }
+        }
+        closest
+    }
+
+    fn camera_rays() -> Vec<Ray> {
+        let origin = Point::new(0.0, 0.0, -20.0);
+        let mut rays = vec![];
+        for y in 0..16 {
+            for x in 0..16 {
+                let target = Point::new(x as Float - 7.5, y as Float - 7.5, 0.0);
+                rays.push(Ray::new(origin, (target - origin).normalize()));
+            }
+        }
+        rays
+    }
+
+    #[test]
+    fn building() {
+        let inverses = spheres();
+        let bounds = bounds(&inverses);
+        let bvh = Bvh::build(&bounds);
+        assert_eq!(49, bvh.len());
+        let all = bounds.iter().fold(Bounds::empty(), |all, b| all.union(b));
+        assert_eq!(all, bvh.bounds());
+
+        // Every primitive ends up in exactly one leaf
+        let mut order = bvh.order.clone();
+        order.sort_unstable();
+        assert_eq!((0..49).collect::<Vec<_>>(), order);
+
+        let empty = Bvh::build(&[]);
+        assert!(empty.is_empty());
+        assert!(empty.bounds().is_empty());
+        let ray = Ray::new(Point::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0));
+        assert_eq!(None, empty.intersect(&ray, |_, _| Some(1.0)));
+    }
+
+    #[test]
+    fn single_rays_match_brute_force() {
+        let (sphere, inverses) = (Sphere::new(), spheres());
+        let bvh = Bvh::build(&bounds(&inverses));
+        let mut hits = 0;
+        for ray in camera_rays() {
+            let closest = bvh.intersect(&ray, |i, ray| sphere.hit(&ray.transform(&inverses[i])));
+            assert_eq!(brute_force(&sphere, &inverses, &ray), closest);
+            hits += closest.is_some() as usize;
+        }
+        // Enough of both to mean something
+        assert!(hits > 50 && hits < 200, "{} hits", hits);
+    }
+
+    #[test]
+    fn packets_match_single_rays() {
+        let (sphere, inverses) = (Sphere::new(), spheres());
+        let bvh = Bvh::build(&bounds(&inverses));
+        let mut rays = camera_rays();
+        // Bounce a few, so some packets have to fall back to single rays
+        for ray in rays.iter_mut().step_by(7) {
+            ray.direction = -ray.direction;
+            ray.origin = Point::new(0.0, 0.0, 0.0);
+        }
+        // One ray too few for whole packets, so the last one leaves lanes empty
+        rays.pop();
+
+        let (mut coherent, mut divergent) = (0, 0);
+        for chunk in rays.chunks(PACKET_WIDTH) {
+            let packet = RayPacket::new(chunk);
+            if packet.is_coherent() {
+                coherent += 1;
+            } else {
+                divergent += 1;
+            }
+            let hits = bvh.intersect_packet(&packet, |i, packet| {
+                sphere.intersect_packet(&packet.transform(&inverses[i]))
+            });
+            for (lane, ray) in chunk.iter().enumerate() {
+                assert_eq!(brute_force(&sphere, &inverses, ray), hits[lane]);
+            }
+            assert!(hits[chunk.len()..].iter().all(Option::is_none));
+        }
+        assert!(coherent > 0 && divergent > 0);
+    }
+}
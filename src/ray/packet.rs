@@ -0,0 +1,247 @@
+use super::*;
+use crate::matrix::Matrix4;
+use crate::simd::{self, Lanes};
+
+pub const PACKET_WIDTH: usize = 4;
+
+// Up to four rays stored a component per register, so each simd operation works on the same
+// component of every ray at once. Camera rays through neighbouring pixels start at the same
+// place and point almost the same way, so they tend to hit the same boxes and shapes and can be
+// traced together. Lanes without a ray are switched off and never report a hit.
+#[derive(Debug, Clone)]
+pub struct RayPacket {
+    origin: [Lanes; 3],
+    direction: [Lanes; 3],
+    active: [bool; PACKET_WIDTH],
+}
+
+impl RayPacket {
+    pub fn new(rays: &[Ray]) -> RayPacket {
+        assert!(
+            rays.len() <= PACKET_WIDTH,
+            "a packet holds at most {} rays",
+            PACKET_WIDTH
+        );
+        let mut packet = RayPacket {
+            origin: [[0.0; PACKET_WIDTH]; 3],
+            direction: [[0.0; PACKET_WIDTH]; 3],
+            active: [false; PACKET_WIDTH],
+        };
+        for (lane, ray) in rays.iter().enumerate() {
+            let (origin, direction) = (ray.origin.lanes(), ray.direction.lanes());
+            for axis in 0..3 {
+                packet.origin[axis][lane] = origin[axis];
+                packet.direction[axis][lane] = direction[axis];
+            }
+            packet.active[lane] = true;
+        }
+        packet
+    }
+
+    pub fn len(&self) -> usize {
+        self.active.iter().filter(|active| **active).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn active(&self) -> [bool; PACKET_WIDTH] {
+        self.active
+    }
+
+    pub fn ray(&self, lane: usize) -> Option<Ray> {
+        if !self.active[lane] {
+            return None;
+        }
+        let [x, y, z] = self.origin;
+        let [dx, dy, dz] = self.direction;
+        Some(Ray::new(
+            Point(x[lane], y[lane], z[lane]),
+            Vector(dx[lane], dy[lane], dz[lane]),
+        ))
+    }
+
+    // True when every ray heads into the same octant, so they all cross a box's planes in the
+    // same order and mostly agree on which parts of a BVH to visit. Bounced rays scatter and
+    // are better off traced one at a time.
+    pub fn is_coherent(&self) -> bool {
+        self.direction.iter().all(|component| {
+            let mut signs = component
+                .iter()
+                .zip(self.active.iter())
+                .filter(|(_, active)| **active)
+                .map(|(value, _)| value.is_sign_negative());
+            match signs.next() {
+                Some(first) => signs.all(|sign| sign == first),
+                None => true,
+            }
+        })
+    }
+
+    // Each lane comes out exactly as Ray::transform would give it
+    pub fn transform(&self, m: &Matrix4) -> RayPacket {
+        let rows = m.rows();
+        let row = |r: &[Float; 4], [x, y, z]: &[Lanes; 3], w: Float| {
+            let sum = simd::add(simd::scale(*x, r[0]), simd::scale(*y, r[1]));
+            let sum = simd::add(sum, simd::scale(*z, r[2]));
+            simd::add(sum, [r[3] * w; PACKET_WIDTH])
+        };
+        RayPacket {
+            origin: [
+                row(&rows[0], &self.origin, 1.0),
+                row(&rows[1], &self.origin, 1.0),
+                row(&rows[2], &self.origin, 1.0),
+            ],
+            direction: [
+                row(&rows[0], &self.direction, 0.0),
+                row(&rows[1], &self.direction, 0.0),
+                row(&rows[2], &self.direction, 0.0),
+            ],
+            active: self.active,
+        }
+    }
+
+    fn dot(a: &[Lanes; 3], b: &[Lanes; 3]) -> Lanes {
+        let sum = simd::add(simd::mul(a[0], b[0]), simd::mul(a[1], b[1]));
+        simd::add(sum, simd::mul(a[2], b[2]))
+    }
+}
+
+impl Sphere {
+    // The closest hit in front of each ray, the same t that hit(ray) finds
+    pub fn intersect_packet(&self, packet: &RayPacket) -> [Option<Float>; PACKET_WIDTH] {
+        // The sphere sits at the origin, so the origins are already the sphere to ray vectors
+        let (origin, direction) = (&packet.origin, &packet.direction);
+        let a = RayPacket::dot(direction, direction);
+        let b = simd::scale(RayPacket::dot(direction, origin), 2.0);
+        let c = simd::sub(RayPacket::dot(origin, origin), [1.0; PACKET_WIDTH]);
+        let discriminant = simd::sub(simd::mul(b, b), simd::mul(simd::scale(a, 4.0), c));
+
+        let mut hits = [None; PACKET_WIDTH];
+        for (lane, hit) in hits.iter_mut().enumerate() {
+            if !packet.active[lane] || discriminant[lane] < 0.0 {
+                continue;
+            }
+            let root = discriminant[lane].sqrt();
+            let t1 = (-b[lane] - root) / (2.0 * a[lane]);
+            let t2 = (-b[lane] + root) / (2.0 * a[lane]);
+            *hit = if t1 > 0.0 {
+                Some(t1)
+            } else if t2 > 0.0 {
+                Some(t2)
+            } else {
+                None
+            };
+        }
+        hits
+    }
+}
+
+impl Bounds {
+    // Bounds::intersect for every lane at once, with each lane stopping at its own t_max
+    pub fn intersect_packet(&self, packet: &RayPacket, t_max: Lanes) -> [bool; PACKET_WIDTH] {
+        let (min, max) = (self.min.lanes(), self.max.lanes());
+        let (mut enter, mut leave) = ([0.0; PACKET_WIDTH], t_max);
+        for axis in 0..3 {
+            let (origin, direction) = (packet.origin[axis], packet.direction[axis]);
+            let inverse = [
+                1.0 / direction[0],
+                1.0 / direction[1],
+                1.0 / direction[2],
+                1.0 / direction[3],
+            ];
+            let a = simd::mul(simd::sub([min[axis]; PACKET_WIDTH], origin), inverse);
+            let b = simd::mul(simd::sub([max[axis]; PACKET_WIDTH], origin), inverse);
+            let (near, far) = (simd::min(a, b), simd::max(a, b));
+            enter = simd::max(near, enter);
+            leave = simd::min(far, leave);
+        }
+
+        let mut hits = [false; PACKET_WIDTH];
+        for (lane, hit) in hits.iter_mut().enumerate() {
+            *hit = packet.active[lane] && enter[lane] <= leave[lane];
+        }
+        hits
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rays() -> Vec<Ray> {
+        vec![
+            Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0)),
+            Ray::new(Point::new(0.0, 1.0, -5.0), Vector::new(0.0, 0.0, 1.0)),
+            Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.1, 0.15, 1.0)),
+            Ray::new(Point::new(0.0, 2.0, -5.0), Vector::new(0.0, 0.0, 1.0)),
+        ]
+    }
+
+    #[test]
+    fn packing_rays() {
+        let rays = rays();
+        let packet = RayPacket::new(&rays[..3]);
+        assert_eq!(3, packet.len());
+        assert_eq!([true, true, true, false], packet.active());
+        assert_eq!(Some(rays[2].clone()), packet.ray(2));
+        assert_eq!(None, packet.ray(3));
+        assert!(RayPacket::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn coherence() {
+        assert!(RayPacket::new(&rays()).is_coherent());
+        let mut rays = rays();
+        rays[1].direction = Vector::new(0.0, -0.1, 1.0);
+        assert!(!RayPacket::new(&rays).is_coherent());
+        // Switched off lanes don't count
+        assert!(RayPacket::new(&rays[..1]).is_coherent());
+    }
+
+    #[test]
+    fn packet_hits_match_single_rays() {
+        let sphere = Sphere::new();
+        let transform = Matrix4::scale(2.0, 1.0, 1.0) * Matrix4::translation(0.5, 0.0, 1.0);
+        let inverse = transform.inverse().unwrap();
+        let mut rays = rays();
+        // Off to the side, and from inside the sphere where the hit is the far side
+        rays[1] = Ray::new(Point::new(2.5, 0.5, -5.0), Vector::new(0.0, 0.0, 1.0));
+        rays[3] = Ray::new(Point::new(1.0, 0.0, 1.0), Vector::new(0.0, 0.0, 1.0));
+
+        let packet = RayPacket::new(&rays).transform(&inverse);
+        let hits = sphere.intersect_packet(&packet);
+        for (ray, hit) in rays.iter().zip(hits.iter()) {
+            let ray = ray.transform(&inverse);
+            let single = sphere.hit(&ray);
+            assert_eq!(single, *hit);
+        }
+        assert!(hits.iter().all(Option::is_some));
+
+        let hits = sphere.intersect_packet(&RayPacket::new(&self::rays()));
+        assert_eq!([Some(4.0), Some(5.0)], hits[..2]);
+        assert_eq!(None, hits[3]);
+    }
+
+    #[test]
+    fn packet_against_bounds() {
+        let b = Bounds::new(Point::new(-1.0, -1.0, -1.0), Point::new(1.0, 1.0, 1.0));
+        let rays = rays();
+        let packet = RayPacket::new(&rays);
+        let hits = b.intersect_packet(&packet, [Float::INFINITY; PACKET_WIDTH]);
+        assert_eq!([true, true, true, false], hits);
+        for (ray, hit) in rays.iter().zip(hits.iter()) {
+            assert_eq!(b.intersect(ray, Float::INFINITY).is_some(), *hit);
+        }
+
+        // Each lane has its own limit
+        let hits = b.intersect_packet(&packet, [3.0, 5.0, Float::INFINITY, 10.0]);
+        assert_eq!([false, true, true, false], hits);
+        let packet = RayPacket::new(&rays[..2]);
+        assert_eq!(
+            [true, true, false, false],
+            b.intersect_packet(&packet, [Float::INFINITY; PACKET_WIDTH])
+        );
+    }
+}
@@ -0,0 +1,313 @@
+mod random;
+mod sequence;
+
+pub use random::*;
+pub use sequence::*;
+
+use crate::util::Float;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SampleSequence {
+    // Independent uniform numbers
+    Random,
+    // Each dimension split into one stratum per sample, visited in a shuffled order so that
+    // dimensions don't line up, with a random offset inside the stratum. Pairs use a square grid,
+    // so the sample count should be a square.
+    Stratified,
+    // Radical inverses in successive prime bases, rotated by a random offset per pixel. Past the
+    // 16th dimension the primes get too large to help and it falls back to random numbers.
+    Halton,
+    // Pairs of Sobol dimensions, scrambled per pixel and each pair shuffled against the others,
+    // best with a power of two sample count
+    Sobol,
+}
+
+// The settings every sample of a render is drawn with. A sample's numbers depend only on these,
+// its pixel and its index within the pixel, never on which thread took it or in what order, so
+// the same settings always give the same image.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Sampler {
+    pub sequence: SampleSequence,
+    // Private so that it can't be set to 0, the sequences divide by it
+    samples_per_pixel: u32,
+    pub seed: u64,
+}
+
+impl Sampler {
+    pub fn new(sequence: SampleSequence, samples_per_pixel: u32) -> Sampler {
+        Sampler {
+            sequence,
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed: 0,
+        }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Sampler {
+        Sampler { seed, ..self }
+    }
+
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.samples_per_pixel
+    }
+
+    pub fn sample(&self, (x, y): (usize, usize), index: u32) -> PixelSample {
+        let pixel = hash(&[self.seed, x as u64, y as u64]);
+        PixelSample {
+            sampler: *self,
+            pixel,
+            index,
+            dimension: 0,
+            rng: Pcg32::new(hash(&[pixel, u64::from(index)]), 0),
+        }
+    }
+
+    // Every sample of a pixel in order
+    pub fn samples(&self, pixel: (usize, usize)) -> impl Iterator<Item = PixelSample> + '_ {
+        (0..self.samples_per_pixel).map(move |index| self.sample(pixel, index))
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new(SampleSequence::Random, 1)
+    }
+}
+
+// The numbers for one sample of one pixel, drawn a dimension at a time in [0, 1). Uses of them
+// should always ask in the same order, e.g. the lens, then the wavelength, then each bounce,
+// so that a dimension means the same thing in every sample.
+#[derive(Debug, Clone)]
+pub struct PixelSample {
+    sampler: Sampler,
+    pixel: u64,
+    index: u32,
+    dimension: u32,
+    rng: Pcg32,
+}
+
+impl PixelSample {
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    // The same for every sample of the pixel, the sequences use it to decorrelate pixels
+    fn scramble(&self, dimension: u32) -> u32 {
+        hash(&[self.pixel, u64::from(dimension)]) as u32
+    }
+
+    pub fn next_1d(&mut self) -> Float {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let count = self.sampler.samples_per_pixel;
+        match self.sampler.sequence {
+            SampleSequence::Random => self.rng.next_float(),
+            SampleSequence::Stratified => {
+                let stratum = permute(self.index % count, count, self.scramble(dimension));
+                ((stratum as Float + self.rng.next_float()) / count as Float).min(ONE_MINUS_EPSILON)
+            }
+            SampleSequence::Halton => match halton(dimension, self.index) {
+                Some(value) => {
+                    let offset = unit_float(self.scramble(dimension));
+                    let rotated = value + offset;
+                    let rotated = if rotated >= 1.0 {
+                        rotated - 1.0
+                    } else {
+                        rotated
+                    };
+                    rotated.min(ONE_MINUS_EPSILON)
+                }
+                None => self.rng.next_float(),
+            },
+            SampleSequence::Sobol => {
+                // Each pair takes the points in its own shuffled order, otherwise every pair
+                // would be the first one xored with a constant
+                let shuffle = hash(&[self.pixel, u64::from(dimension / 2), u64::MAX]) as u32;
+                let (x, y) = sobol_2d(permute(self.index % count, count, shuffle));
+                let bits = if dimension % 2 == 0 { x } else { y };
+                unit_float(bits ^ self.scramble(dimension))
+            }
+        }
+    }
+
+    pub fn next_2d(&mut self) -> (Float, Float) {
+        match self.sampler.sequence {
+            SampleSequence::Stratified => {
+                let dimension = self.dimension;
+                self.dimension += 2;
+                let count = self.sampler.samples_per_pixel;
+                let side = (count as Float).sqrt() as u32;
+                let cells = side * side;
+                let cell = permute(self.index % count, count, self.scramble(dimension)) % cells;
+                let jitter = |cell: u32, u: Float| {
+                    ((cell as Float + u) / side as Float).min(ONE_MINUS_EPSILON)
+                };
+                let (u, v) = (self.rng.next_float(), self.rng.next_float());
+                (jitter(cell % side, u), jitter(cell / side, v))
+            }
+            SampleSequence::Sobol => {
+                // Keeps both halves of the pair from the same two Sobol dimensions
+                self.dimension += self.dimension % 2;
+                (self.next_1d(), self.next_1d())
+            }
+            SampleSequence::Random | SampleSequence::Halton => (self.next_1d(), self.next_1d()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util;
+
+    const SEQUENCES: [SampleSequence; 4] = [
+        SampleSequence::Random,
+        SampleSequence::Stratified,
+        SampleSequence::Halton,
+        SampleSequence::Sobol,
+    ];
+
+    fn draw(sample: &mut PixelSample) -> Vec<Float> {
+        let mut values = vec![sample.next_1d()];
+        let (u, v) = sample.next_2d();
+        values.extend_from_slice(&[u, v, sample.next_1d()]);
+        let (u, v) = sample.next_2d();
+        values.extend_from_slice(&[u, v]);
+        values
+    }
+
+    // Everything a small render would draw, pixel by pixel
+    fn render(sampler: &Sampler, pixels: &[(usize, usize)]) -> Vec<Vec<Float>> {
+        pixels
+            .iter()
+            .flat_map(|pixel| sampler.samples(*pixel).map(|mut sample| draw(&mut sample)))
+            .collect()
+    }
+
+    #[test]
+    fn values_are_in_the_unit_interval() {
+        for sequence in SEQUENCES.iter() {
+            let sampler = Sampler::new(*sequence, 16).with_seed(5);
+            for values in render(&sampler, &[(0, 0), (3, 7)]) {
+                assert!(
+                    values.iter().all(|v| (0.0..1.0).contains(v)),
+                    "{:?}",
+                    sequence
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn at_least_one_sample() {
+        let sampler = Sampler::new(SampleSequence::Sobol, 0);
+        assert_eq!(1, sampler.samples_per_pixel());
+        let mut samples: Vec<PixelSample> = sampler.samples((1, 1)).collect();
+        assert_eq!(1, samples.len());
+        assert!((0.0..1.0).contains(&samples[0].next_1d()));
+    }
+
+    #[test]
+    fn same_settings_same_numbers() {
+        let pixels: Vec<(usize, usize)> =
+            (0..4).flat_map(|y| (0..4).map(move |x| (x, y))).collect();
+        for sequence in SEQUENCES.iter() {
+            let sampler = Sampler::new(*sequence, 4).with_seed(11);
+            let expected = render(&sampler, &pixels);
+
+            // Backwards, and split across threads, gives exactly the same numbers per pixel
+            let reversed: Vec<(usize, usize)> = pixels.iter().rev().copied().collect();
+            let backwards: Vec<Vec<Float>> = render(&sampler, &reversed)
+                .chunks(4)
+                .rev()
+                .flatten()
+                .cloned()
+                .collect();
+            assert_eq!(expected, backwards);
+
+            let threaded: Vec<Vec<Float>> = std::thread::scope(|scope| {
+                let handles: Vec<_> = pixels
+                    .chunks(3)
+                    .map(|chunk| scope.spawn(move || render(&sampler, chunk)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().unwrap())
+                    .collect()
+            });
+            assert_eq!(expected, threaded);
+
+            let other_seed = render(&sampler.with_seed(12), &pixels);
+            assert_ne!(expected, other_seed);
+        }
+    }
+
+    #[test]
+    fn pixels_are_decorrelated() {
+        for sequence in SEQUENCES.iter() {
+            let sampler = Sampler::new(*sequence, 4);
+            let (a, b) = (render(&sampler, &[(0, 0)]), render(&sampler, &[(1, 0)]));
+            assert_ne!(a, b, "{:?}", sequence);
+        }
+    }
+
+    #[test]
+    fn stratified_covers_every_stratum() {
+        let sampler = Sampler::new(SampleSequence::Stratified, 16).with_seed(3);
+        let (mut strata, mut cells) = (vec![0; 16], vec![0; 16]);
+        for mut sample in sampler.samples((2, 5)) {
+            strata[(sample.next_1d() * 16.0) as usize] += 1;
+            let (u, v) = sample.next_2d();
+            cells[(v * 4.0) as usize * 4 + (u * 4.0) as usize] += 1;
+        }
+        assert_eq!(vec![1; 16], strata);
+        assert_eq!(vec![1; 16], cells);
+    }
+
+    #[test]
+    fn sobol_pairs_stay_nets() {
+        let sampler = Sampler::new(SampleSequence::Sobol, 16).with_seed(8);
+        let mut cells = vec![0; 16];
+        for mut sample in sampler.samples((9, 1)) {
+            // An odd number of dimensions first, the pair still lines up
+            sample.next_1d();
+            let (u, v) = sample.next_2d();
+            cells[(v * 4.0) as usize * 4 + (u * 4.0) as usize] += 1;
+        }
+        assert_eq!(vec![1; 16], cells);
+    }
+
+    #[test]
+    fn sobol_pairs_are_independent() {
+        // The first dimensions of two different pairs, which used to land on a single diagonal
+        // of a 4x4 grid, are spread over it
+        let sampler = Sampler::new(SampleSequence::Sobol, 16).with_seed(8);
+        for pixel in [(0, 0), (9, 1), (4, 7)].iter() {
+            let mut cells = vec![0; 16];
+            for mut sample in sampler.samples(*pixel) {
+                let (u, _) = sample.next_2d();
+                let (v, _) = sample.next_2d();
+                cells[(v * 4.0) as usize * 4 + (u * 4.0) as usize] += 1;
+            }
+            assert!(
+                cells.iter().filter(|count| **count > 0).count() > 8,
+                "{:?}",
+                cells
+            );
+        }
+    }
+
+    #[test]
+    fn halton_follows_the_sequence() {
+        // Consecutive samples in a dimension step through the radical inverse, only shifted
+        let sampler = Sampler::new(SampleSequence::Halton, 4);
+        let firsts: Vec<Float> = sampler
+            .samples((0, 0))
+            .map(|mut sample| sample.next_1d())
+            .collect();
+        let shift = firsts[0];
+        for (i, first) in firsts.iter().enumerate() {
+            let expected = (radical_inverse(2, i as u32) + shift) % 1.0;
+            assert_feq!(expected, *first);
+        }
+    }
+}
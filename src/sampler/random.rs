@@ -0,0 +1,133 @@
+use super::*;
+
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const XORSHIFT_MULTIPLIER: u64 = 0x2545_f491_4f6c_dd1d;
+
+// PCG32, the XSH RR variant from O'Neill's paper: 64 bits of state and 32 bit outputs. The
+// stream picks one of 2^63 different sequences, so generators with the same seed can still be
+// kept apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pcg32 {
+    state: u64,
+    increment: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(self.increment);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_float(&mut self) -> Float {
+        unit_float(self.next_u32())
+    }
+}
+
+// Vigna's xorshift64*, a little faster than Pcg32 but with only the one sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // An all zero state would only ever give zeros
+        XorShift {
+            state: mix(seed).max(1),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(XORSHIFT_MULTIPLIER)
+    }
+
+    // The high bits, the low ones of xorshift64* are its weakest
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    pub fn next_float(&mut self) -> Float {
+        unit_float(self.next_u32())
+    }
+}
+
+// The SplitMix64 finalizer, every bit of the input affects every bit of the output
+pub fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Combines several values into one well mixed seed, order matters
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |hash, value| mix(hash ^ value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pcg32_reference_output() {
+        // From the reference implementation's pcg32-demo, seeded with 42 on stream 54
+        let mut rng = Pcg32::new(42, 54);
+        let expected = [
+            0xa15c_02b7,
+            0x7b47_f409,
+            0xba1d_3330,
+            0x83d2_f293,
+            0xbfa4_784b,
+        ];
+        for value in expected.iter() {
+            assert_eq!(*value, rng.next_u32());
+        }
+    }
+
+    #[test]
+    fn streams_and_seeds_differ() {
+        let first = |mut rng: Pcg32| rng.next_u32();
+        assert_ne!(first(Pcg32::new(1, 0)), first(Pcg32::new(1, 1)));
+        assert_ne!(first(Pcg32::new(1, 0)), first(Pcg32::new(2, 0)));
+        assert_eq!(Pcg32::new(7, 3), Pcg32::new(7, 3));
+    }
+
+    #[test]
+    fn xorshift() {
+        let (mut a, mut b) = (XorShift::new(0), XorShift::new(0));
+        for _ in 0..100 {
+            let value = a.next_float();
+            assert!((0.0..1.0).contains(&value));
+            assert_eq!(value, b.next_float());
+        }
+        assert_ne!(XorShift::new(1).next_u64(), XorShift::new(2).next_u64());
+    }
+
+    #[test]
+    fn hashing() {
+        assert_ne!(hash(&[1, 2]), hash(&[2, 1]));
+        assert_ne!(hash(&[0]), hash(&[0, 0]));
+        assert_eq!(hash(&[3, 4, 5]), hash(&[3, 4, 5]));
+    }
+}
@@ -0,0 +1,148 @@
+use super::*;
+
+// Bits of a u32 that fit in a Float's mantissa, so every value below converts exactly
+const UNIT_BITS: u32 = if Float::MANTISSA_DIGITS < 32 {
+    Float::MANTISSA_DIGITS
+} else {
+    32
+};
+
+// The largest Float below 1
+pub const ONE_MINUS_EPSILON: Float = 1.0 - Float::EPSILON / 2.0;
+
+const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+// Turns random bits into a Float in [0, 1), using the high bits and rounding down so that it
+// can never come out as 1
+pub fn unit_float(bits: u32) -> Float {
+    (bits >> (32 - UNIT_BITS)) as Float / (1u64 << UNIT_BITS) as Float
+}
+
+// Mirrors the digits of index in base around the decimal point, so 1, 2, 3 in base 2 are
+// 0.1, 0.01, 0.11 or 1/2, 1/4, 3/4. The digits and the power of base are kept as integers
+// for a single rounding at the end.
+pub fn radical_inverse(base: u32, mut index: u32) -> Float {
+    let (mut reversed, mut denominator) = (0u64, 1u64);
+    while index > 0 {
+        let next = index / base;
+        reversed = reversed * u64::from(base) + u64::from(index - next * base);
+        denominator *= u64::from(base);
+        index = next;
+    }
+    (reversed as Float / denominator as Float).min(ONE_MINUS_EPSILON)
+}
+
+// The index'th point of the Halton sequence in one dimension, or None past the last prime
+pub fn halton(dimension: u32, index: u32) -> Option<Float> {
+    let base = PRIMES.get(dimension as usize)?;
+    Some(radical_inverse(*base, index))
+}
+
+// The first two dimensions of the Sobol sequence as 32 bit fractions. Every power of two run
+// of points starting at a multiple of itself is a (0, 2) net, one point in each of any grid of
+// that many equal rectangles, and xoring either dimension with a constant keeps it that way.
+pub fn sobol_2d(index: u32) -> (u32, u32) {
+    let (mut y, mut direction, mut i) = (0u32, 1u32 << 31, index);
+    while i != 0 {
+        if i & 1 == 1 {
+            y ^= direction;
+        }
+        i >>= 1;
+        direction ^= direction >> 1;
+    }
+    (index.reverse_bits(), y)
+}
+
+// Shuffles 0..length without making a table, Kensler's hashed permutation from Correlated
+// Multi-Jittered Sampling. Each step is invertible on the low bits, and values that land past
+// length go round again until they don't, so every index comes out exactly once per seed.
+pub fn permute(mut index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length.wrapping_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170_893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929_eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935_fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dc_b303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e50_1cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860_a3df);
+        index &= mask;
+        index ^= index >> 5;
+        if index < length {
+            break;
+        }
+    }
+    (index + seed % length) % length
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn unit_floats_stay_below_one() {
+        assert_eq!(0.0, unit_float(0));
+        assert_eq!(0.5, unit_float(1 << 31));
+        assert!(unit_float(u32::MAX) < 1.0);
+    }
+
+    #[test]
+    fn radical_inverses() {
+        let base_2: Vec<Float> = (0..5).map(|i| radical_inverse(2, i)).collect();
+        assert_eq!(vec![0.0, 0.5, 0.25, 0.75, 0.125], base_2);
+        assert_eq!(1.0 / 9.0, radical_inverse(3, 3));
+        assert_eq!(Some(0.5), halton(0, 1));
+        assert_eq!(Some(2.0 / 3.0), halton(1, 2));
+        assert_eq!(None, halton(16, 1));
+    }
+
+    #[test]
+    fn sobol_points_are_nets() {
+        let unit = |(x, y): (u32, u32)| (unit_float(x), unit_float(y));
+        assert_eq!((0.0, 0.0), unit(sobol_2d(0)));
+        assert_eq!((0.5, 0.5), unit(sobol_2d(1)));
+        assert_eq!((0.25, 0.75), unit(sobol_2d(2)));
+        assert_eq!((0.75, 0.25), unit(sobol_2d(3)));
+
+        // The second run of 16 points, scrambled, has one point in every cell of a 4x4, 2x8,
+        // 8x2, 16x1 and 1x16 grid
+        for (columns, rows) in [(4, 4), (2, 8), (8, 2), (16, 1), (1, 16)].iter() {
+            let mut cells = vec![0; 16];
+            for i in 16..32 {
+                let (x, y) = sobol_2d(i);
+                let (x, y) = (unit_float(x ^ 0x1234_5678), unit_float(y ^ 0x9abc_def0));
+                let (column, row) = (
+                    (x * *columns as Float) as usize,
+                    (y * *rows as Float) as usize,
+                );
+                cells[row * columns + column] += 1;
+            }
+            assert!(cells.iter().all(|count| *count == 1), "{:?}", cells);
+        }
+    }
+
+    #[test]
+    fn permutations() {
+        for (length, seed) in [(1, 7), (10, 0), (16, 1234), (100, 0xdead_beef)].iter() {
+            let mut seen: Vec<u32> = (0..*length).map(|i| permute(i, *length, *seed)).collect();
+            seen.sort_unstable();
+            assert_eq!((0..*length).collect::<Vec<_>>(), seen);
+        }
+        let shuffled: Vec<u32> = (0..8).map(|i| permute(i, 8, 99)).collect();
+        assert_ne!((0..8).collect::<Vec<_>>(), shuffled);
+    }
+}
@@ -0,0 +1,455 @@
+// Four wide arithmetic for Matrix4 and ray packets, where every lane holds real work. Points,
+// vectors and colors only fill three lanes, and packing them into a register for every
+// operation costs more than it saves, so they always use the scalar versions.
+//
+// With the simd feature on x86_64 this uses SSE, or with the f64 feature SSE2's double
+// precision instructions two lanes to a register. Both are part of x86_64 itself so there's
+// nothing to detect at runtime. AVX would need that detection and isn't used, and every other
+// architecture gets the scalar versions. All of them add and multiply in the same order with no
+// fused operations, so they give exactly the same results.
+
+use crate::util::Float;
+
+pub type Lanes = [Float; 4];
+pub type Rows = [Lanes; 4];
+
+#[cfg(all(feature = "simd", target_arch = "x86_64", not(feature = "f64")))]
+pub use sse::*;
+
+#[cfg(all(feature = "simd", target_arch = "x86_64", feature = "f64"))]
+pub use sse2::*;
+
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+pub use scalar::*;
+
+pub mod scalar {
+    use super::*;
+
+    pub fn backend() -> &'static str {
+        "scalar"
+    }
+
+    #[inline]
+    pub fn add(a: Lanes, b: Lanes) -> Lanes {
+        [a[0] + b[0], a[1] + b[1], a[2] + b[2], a[3] + b[3]]
+    }
+
+    #[inline]
+    pub fn sub(a: Lanes, b: Lanes) -> Lanes {
+        [a[0] - b[0], a[1] - b[1], a[2] - b[2], a[3] - b[3]]
+    }
+
+    #[inline]
+    pub fn mul(a: Lanes, b: Lanes) -> Lanes {
+        [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
+    }
+
+    #[inline]
+    pub fn scale(a: Lanes, factor: Float) -> Lanes {
+        [a[0] * factor, a[1] * factor, a[2] * factor, a[3] * factor]
+    }
+
+    #[inline]
+    pub fn div(a: Lanes, divisor: Float) -> Lanes {
+        [
+            a[0] / divisor,
+            a[1] / divisor,
+            a[2] / divisor,
+            a[3] / divisor,
+        ]
+    }
+
+    // Like minps and maxps these give b when either is NaN, which is not what Float::min does
+    #[inline]
+    pub fn min(a: Lanes, b: Lanes) -> Lanes {
+        let min = |a: Float, b: Float| if a < b { a } else { b };
+        [
+            min(a[0], b[0]),
+            min(a[1], b[1]),
+            min(a[2], b[2]),
+            min(a[3], b[3]),
+        ]
+    }
+
+    #[inline]
+    pub fn max(a: Lanes, b: Lanes) -> Lanes {
+        let max = |a: Float, b: Float| if a > b { a } else { b };
+        [
+            max(a[0], b[0]),
+            max(a[1], b[1]),
+            max(a[2], b[2]),
+            max(a[3], b[3]),
+        ]
+    }
+
+    // Of the first three lanes
+    #[inline]
+    pub fn dot3(a: Lanes, b: Lanes) -> Float {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+    }
+
+    #[inline]
+    pub fn cross(a: Lanes, b: Lanes) -> Lanes {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+            0.0,
+        ]
+    }
+
+    #[inline]
+    pub fn matrix_mul(a: &Rows, b: &Rows) -> Rows {
+        let mut m = [[0.0; 4]; 4];
+        for (row, a) in m.iter_mut().zip(a.iter()) {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = a[0] * b[0][x] + a[1] * b[1][x] + a[2] * b[2][x] + a[3] * b[3][x];
+            }
+        }
+        m
+    }
+
+    // The matrix times a column, with w = 1 for points and w = 0 for vectors
+    #[inline]
+    pub fn matrix_transform(m: &Rows, v: Lanes) -> Lanes {
+        let mut result = [0.0; 4];
+        for (value, row) in result.iter_mut().zip(m.iter()) {
+            *value = row[0] * v[0] + row[1] * v[1] + row[2] * v[2] + row[3] * v[3];
+        }
+        result
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64", not(feature = "f64")))]
+pub mod sse {
+    // The intrinsics are unsafe to call because the processor might not support them, but SSE
+    // is part of x86_64 itself so every processor this compiles for does
+    use super::*;
+
+    use std::arch::x86_64::*;
+
+    pub fn backend() -> &'static str {
+        "sse"
+    }
+
+    #[inline]
+    fn load(a: Lanes) -> __m128 {
+        unsafe { _mm_set_ps(a[3], a[2], a[1], a[0]) }
+    }
+
+    #[inline]
+    fn store(a: __m128) -> Lanes {
+        let mut lanes = [0.0; 4];
+        unsafe { _mm_storeu_ps(lanes.as_mut_ptr(), a) };
+        lanes
+    }
+
+    #[inline]
+    pub fn add(a: Lanes, b: Lanes) -> Lanes {
+        store(unsafe { _mm_add_ps(load(a), load(b)) })
+    }
+
+    #[inline]
+    pub fn sub(a: Lanes, b: Lanes) -> Lanes {
+        store(unsafe { _mm_sub_ps(load(a), load(b)) })
+    }
+
+    #[inline]
+    pub fn mul(a: Lanes, b: Lanes) -> Lanes {
+        store(unsafe { _mm_mul_ps(load(a), load(b)) })
+    }
+
+    #[inline]
+    pub fn scale(a: Lanes, factor: Float) -> Lanes {
+        store(unsafe { _mm_mul_ps(load(a), _mm_set1_ps(factor)) })
+    }
+
+    #[inline]
+    pub fn div(a: Lanes, divisor: Float) -> Lanes {
+        store(unsafe { _mm_div_ps(load(a), _mm_set1_ps(divisor)) })
+    }
+
+    #[inline]
+    pub fn min(a: Lanes, b: Lanes) -> Lanes {
+        store(unsafe { _mm_min_ps(load(a), load(b)) })
+    }
+
+    #[inline]
+    pub fn max(a: Lanes, b: Lanes) -> Lanes {
+        store(unsafe { _mm_max_ps(load(a), load(b)) })
+    }
+
+    #[inline]
+    pub fn dot3(a: Lanes, b: Lanes) -> Float {
+        unsafe {
+            let products = _mm_mul_ps(load(a), load(b));
+            let y = _mm_shuffle_ps::<0b01>(products, products);
+            let z = _mm_shuffle_ps::<0b10>(products, products);
+            _mm_cvtss_f32(_mm_add_ss(_mm_add_ss(products, y), z))
+        }
+    }
+
+    #[inline]
+    pub fn cross(a: Lanes, b: Lanes) -> Lanes {
+        let (a, b) = (load(a), load(b));
+        let mut result = store(unsafe {
+            // Rotates x, y, z to y, z, x and to z, x, y
+            let a_yzx = _mm_shuffle_ps::<0b11_00_10_01>(a, a);
+            let a_zxy = _mm_shuffle_ps::<0b11_01_00_10>(a, a);
+            let b_yzx = _mm_shuffle_ps::<0b11_00_10_01>(b, b);
+            let b_zxy = _mm_shuffle_ps::<0b11_01_00_10>(b, b);
+            _mm_sub_ps(_mm_mul_ps(a_yzx, b_zxy), _mm_mul_ps(a_zxy, b_yzx))
+        });
+        result[3] = 0.0;
+        result
+    }
+
+    // Each row of the result is a sum of the rows of b, weighted by that row of a
+    #[inline]
+    pub fn matrix_mul(a: &Rows, b: &Rows) -> Rows {
+        let b = [load(b[0]), load(b[1]), load(b[2]), load(b[3])];
+        let mut m = [[0.0; 4]; 4];
+        for (row, a) in m.iter_mut().zip(a.iter()) {
+            *row = store(unsafe {
+                let mut sum = _mm_mul_ps(_mm_set1_ps(a[0]), b[0]);
+                sum = _mm_add_ps(sum, _mm_mul_ps(_mm_set1_ps(a[1]), b[1]));
+                sum = _mm_add_ps(sum, _mm_mul_ps(_mm_set1_ps(a[2]), b[2]));
+                _mm_add_ps(sum, _mm_mul_ps(_mm_set1_ps(a[3]), b[3]))
+            });
+        }
+        m
+    }
+
+    // Multiplies every row by v, then transposes the products so that adding them up column by
+    // column sums each row in the same order as the scalar version
+    #[inline]
+    pub fn matrix_transform(m: &Rows, v: Lanes) -> Lanes {
+        let v = load(v);
+        store(unsafe {
+            let r0 = _mm_mul_ps(load(m[0]), v);
+            let r1 = _mm_mul_ps(load(m[1]), v);
+            let r2 = _mm_mul_ps(load(m[2]), v);
+            let r3 = _mm_mul_ps(load(m[3]), v);
+            let low01 = _mm_unpacklo_ps(r0, r1);
+            let low23 = _mm_unpacklo_ps(r2, r3);
+            let high01 = _mm_unpackhi_ps(r0, r1);
+            let high23 = _mm_unpackhi_ps(r2, r3);
+            let x = _mm_movelh_ps(low01, low23);
+            let y = _mm_movehl_ps(low23, low01);
+            let z = _mm_movelh_ps(high01, high23);
+            let w = _mm_movehl_ps(high23, high01);
+            _mm_add_ps(_mm_add_ps(_mm_add_ps(x, y), z), w)
+        })
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64", feature = "f64"))]
+pub mod sse2 {
+    // Four f64 lanes take a pair of registers, the first two lanes and the last two
+    use super::*;
+
+    use std::arch::x86_64::*;
+
+    type Pair = (__m128d, __m128d);
+
+    pub fn backend() -> &'static str {
+        "sse2"
+    }
+
+    #[inline]
+    fn load(a: Lanes) -> Pair {
+        unsafe { (_mm_loadu_pd(a.as_ptr()), _mm_loadu_pd(a[2..].as_ptr())) }
+    }
+
+    #[inline]
+    fn store((low, high): Pair) -> Lanes {
+        let mut lanes = [0.0; 4];
+        unsafe {
+            _mm_storeu_pd(lanes.as_mut_ptr(), low);
+            _mm_storeu_pd(lanes[2..].as_mut_ptr(), high);
+        }
+        lanes
+    }
+
+    #[inline]
+    fn each<F: Fn(__m128d, __m128d) -> __m128d>(a: Pair, b: Pair, f: F) -> Pair {
+        (f(a.0, b.0), f(a.1, b.1))
+    }
+
+    #[inline]
+    fn splat(value: Float) -> Pair {
+        let value = unsafe { _mm_set1_pd(value) };
+        (value, value)
+    }
+
+    #[inline]
+    pub fn add(a: Lanes, b: Lanes) -> Lanes {
+        store(each(load(a), load(b), |a, b| unsafe { _mm_add_pd(a, b) }))
+    }
+
+    #[inline]
+    pub fn sub(a: Lanes, b: Lanes) -> Lanes {
+        store(each(load(a), load(b), |a, b| unsafe { _mm_sub_pd(a, b) }))
+    }
+
+    #[inline]
+    pub fn mul(a: Lanes, b: Lanes) -> Lanes {
+        store(each(load(a), load(b), |a, b| unsafe { _mm_mul_pd(a, b) }))
+    }
+
+    #[inline]
+    pub fn scale(a: Lanes, factor: Float) -> Lanes {
+        store(each(load(a), splat(factor), |a, b| unsafe {
+            _mm_mul_pd(a, b)
+        }))
+    }
+
+    #[inline]
+    pub fn div(a: Lanes, divisor: Float) -> Lanes {
+        store(each(load(a), splat(divisor), |a, b| unsafe {
+            _mm_div_pd(a, b)
+        }))
+    }
+
+    #[inline]
+    pub fn min(a: Lanes, b: Lanes) -> Lanes {
+        store(each(load(a), load(b), |a, b| unsafe { _mm_min_pd(a, b) }))
+    }
+
+    #[inline]
+    pub fn max(a: Lanes, b: Lanes) -> Lanes {
+        store(each(load(a), load(b), |a, b| unsafe { _mm_max_pd(a, b) }))
+    }
+
+    #[inline]
+    pub fn dot3(a: Lanes, b: Lanes) -> Float {
+        let products = mul(a, b);
+        products[0] + products[1] + products[2]
+    }
+
+    #[inline]
+    pub fn cross(a: Lanes, b: Lanes) -> Lanes {
+        let yzx = |v: Lanes| [v[1], v[2], v[0], 0.0];
+        let zxy = |v: Lanes| [v[2], v[0], v[1], 0.0];
+        sub(mul(yzx(a), zxy(b)), mul(zxy(a), yzx(b)))
+    }
+
+    // Each row of the result is a sum of the rows of b, weighted by that row of a
+    #[inline]
+    pub fn matrix_mul(a: &Rows, b: &Rows) -> Rows {
+        let b = [load(b[0]), load(b[1]), load(b[2]), load(b[3])];
+        let mul = |a, b| unsafe { _mm_mul_pd(a, b) };
+        let add = |a, b| unsafe { _mm_add_pd(a, b) };
+        let mut m = [[0.0; 4]; 4];
+        for (row, a) in m.iter_mut().zip(a.iter()) {
+            let mut sum = each(splat(a[0]), b[0], mul);
+            for (a, b) in a[1..].iter().zip(b[1..].iter()) {
+                sum = each(sum, each(splat(*a), *b, mul), add);
+            }
+            *row = store(sum);
+        }
+        m
+    }
+
+    // A sum of the columns of m, weighted by v, which adds up each row in the same order as the
+    // scalar version. Unpacking the halves of two rows gives the halves of two columns.
+    #[inline]
+    pub fn matrix_transform(m: &Rows, v: Lanes) -> Lanes {
+        let [r0, r1, r2, r3] = [load(m[0]), load(m[1]), load(m[2]), load(m[3])];
+        let columns = unsafe {
+            [
+                (_mm_unpacklo_pd(r0.0, r1.0), _mm_unpacklo_pd(r2.0, r3.0)),
+                (_mm_unpackhi_pd(r0.0, r1.0), _mm_unpackhi_pd(r2.0, r3.0)),
+                (_mm_unpacklo_pd(r0.1, r1.1), _mm_unpacklo_pd(r2.1, r3.1)),
+                (_mm_unpackhi_pd(r0.1, r1.1), _mm_unpackhi_pd(r2.1, r3.1)),
+            ]
+        };
+        let mul = |a, b| unsafe { _mm_mul_pd(a, b) };
+        let add = |a, b| unsafe { _mm_add_pd(a, b) };
+        let mut sum = each(columns[0], splat(v[0]), mul);
+        for (column, v) in columns[1..].iter().zip(v[1..].iter()) {
+            sum = each(sum, each(*column, splat(*v), mul), add);
+        }
+        store(sum)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const A: Lanes = [1.5, -2.25, 3.0, 0.0];
+    const B: Lanes = [0.1, 7.0, -0.3, 0.0];
+
+    fn rows() -> (Rows, Rows) {
+        (
+            [
+                [1.0, 2.0, 3.0, 4.0],
+                [5.0, 6.0, 7.0, 8.0],
+                [9.0, 8.0, 7.0, 6.0],
+                [5.0, 4.0, 3.0, 2.0],
+            ],
+            [
+                [-2.0, 1.0, 2.0, 3.0],
+                [3.0, 2.0, 1.0, -1.0],
+                [4.0, 3.0, 6.0, 5.0],
+                [1.0, 2.0, 7.0, 8.0],
+            ],
+        )
+    }
+
+    // Whichever backend is selected has to match the scalar one bit for bit
+    #[test]
+    fn matches_scalar() {
+        assert_eq!(scalar::add(A, B), add(A, B));
+        assert_eq!(scalar::sub(A, B), sub(A, B));
+        assert_eq!(scalar::mul(A, B), mul(A, B));
+        assert_eq!(scalar::scale(A, 0.7), scale(A, 0.7));
+        assert_eq!(scalar::div(A, 0.7), div(A, 0.7));
+        assert_eq!(scalar::min(A, B), min(A, B));
+        assert_eq!(scalar::max(A, B), max(A, B));
+        assert_eq!(scalar::dot3(A, B), dot3(A, B));
+        assert_eq!(scalar::cross(A, B), cross(A, B));
+
+        let (a, b) = rows();
+        assert_eq!(scalar::matrix_mul(&a, &b), matrix_mul(&a, &b));
+        let point = [0.3, -1.7, 2.9, 1.0];
+        assert_eq!(
+            scalar::matrix_transform(&a, point),
+            matrix_transform(&a, point)
+        );
+    }
+
+    #[test]
+    fn scalar_math() {
+        assert_eq!(
+            [1.5, 0.5, 2.0, 0.0],
+            scalar::add([1.0, 2.0, 3.0, 0.0], [0.5, -1.5, -1.0, 0.0])
+        );
+        assert_eq!(
+            20.0,
+            scalar::dot3([1.0, 2.0, 3.0, 0.0], [2.0, 3.0, 4.0, 0.0])
+        );
+        assert_eq!(
+            [-1.0, 2.0, -1.0, 0.0],
+            scalar::cross([1.0, 2.0, 3.0, 0.0], [2.0, 3.0, 4.0, 0.0])
+        );
+        // A NaN in either lane gives back the second argument
+        let nan = Float::NAN;
+        assert_eq!(
+            [1.0, 2.0, 0.0, -4.0],
+            scalar::min([nan, 3.0, 0.0, nan], [1.0, 2.0, 5.0, -4.0])
+        );
+        assert_eq!(
+            [1.0, 3.0, 5.0, -4.0],
+            scalar::max([nan, 3.0, 0.0, nan], [1.0, 2.0, 5.0, -4.0])
+        );
+
+        let (a, b) = rows();
+        assert_eq!([20.0, 22.0, 50.0, 48.0], scalar::matrix_mul(&a, &b)[0]);
+        assert_eq!(
+            [18.0, 46.0, 52.0, 24.0],
+            scalar::matrix_transform(&a, [1.0, 2.0, 3.0, 1.0])
+        );
+    }
+}
@@ -1,5 +1,11 @@
+mod normal;
+mod ops;
+
+pub use normal::*;
+
 use crate::matrix;
-use crate::util;
+use crate::simd::{scalar, Lanes};
+use crate::util::{self, Float};
 
 use std::ops::Add;
 use std::ops::Div;
@@ -7,17 +13,17 @@ use std::ops::Mul;
 use std::ops::Sub;
 
 #[derive(Debug, Copy, Clone)]
-pub struct Point(pub f32, pub f32, pub f32);
+pub struct Point(pub Float, pub Float, pub Float);
 
 #[derive(Debug, Copy, Clone)]
-pub struct Vector(pub f32, pub f32, pub f32);
+pub struct Vector(pub Float, pub Float, pub Float);
 
 impl Point {
     pub fn new<X, Y, Z>(x: X, y: Y, z: Z) -> Point
     where
-        X: Into<f32>,
-        Y: Into<f32>,
-        Z: Into<f32>,
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
     {
         Point(x.into(), y.into(), z.into())
     }
@@ -26,18 +32,33 @@ impl Point {
         *self * -1.0
     }
 
+    pub fn distance(&self, rhs: &Point) -> Float {
+        (*rhs - *self).magnitude()
+    }
+
     pub fn matrix(self) -> matrix::Matrix {
         let Point(x, y, z) = self;
         tuple![x, y, z, 1.0]
     }
+
+    #[inline]
+    pub(crate) fn lanes(&self) -> Lanes {
+        let Point(x, y, z) = *self;
+        [x, y, z, 1.0]
+    }
+
+    #[inline]
+    pub(crate) fn from_lanes([x, y, z, _]: Lanes) -> Point {
+        Point(x, y, z)
+    }
 }
 
 impl Vector {
     pub fn new<X, Y, Z>(x: X, y: Y, z: Z) -> Vector
     where
-        X: Into<f32>,
-        Y: Into<f32>,
-        Z: Into<f32>,
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
     {
         Vector(x.into(), y.into(), z.into())
     }
@@ -51,136 +72,181 @@ impl Vector {
         tuple![x, y, z, 0.0]
     }
 
+    #[inline]
+    pub(crate) fn lanes(&self) -> Lanes {
+        let Vector(x, y, z) = *self;
+        [x, y, z, 0.0]
+    }
+
+    #[inline]
+    pub(crate) fn from_lanes([x, y, z, _]: Lanes) -> Vector {
+        Vector(x, y, z)
+    }
+
     // magnitude is the distance traveled if you were to walk the vector
-    pub fn magnitude(&self) -> f32 {
-        let Vector(x, y, z) = self;
-        let v = (x.powi(2) + y.powi(2) + z.powi(2)).sqrt();
-        v
+    #[inline]
+    pub fn magnitude(&self) -> Float {
+        scalar::dot3(self.lanes(), self.lanes()).sqrt()
     }
 
     // converts the vector into a unit vector
+    #[inline]
     pub fn normalize(&self) -> Vector {
-        let Vector(x, y, z) = self;
-        let magnitude = self.magnitude();
-        Self::new(x / magnitude, y / magnitude, z / magnitude)
+        Self::from_lanes(scalar::div(self.lanes(), self.magnitude()))
     }
 
     // The smaller the output, the larger the angle between vectors
-    pub fn dot(&self, rhs: &Vector) -> f32 {
-        let Vector(x1, y1, z1) = self;
-        let Vector(x2, y2, z2) = rhs;
-        return (x1 * x2) + (y1 * y2) + (z1 * z2);
+    #[inline]
+    pub fn dot(&self, rhs: &Vector) -> Float {
+        scalar::dot3(self.lanes(), rhs.lanes())
     }
 
     // Finds new vector perpendicular to two vectors
+    #[inline]
     pub fn cross(&self, rhs: &Vector) -> Vector {
-        let Vector(x1, y1, z1) = self;
-        let Vector(x2, y2, z2) = rhs;
-        Self::new(
-            (y1 * z2) - (z1 * y2),
-            (z1 * x2) - (x1 * z2),
-            (x1 * y2) - (y1 * x2),
-        )
+        Self::from_lanes(scalar::cross(self.lanes(), rhs.lanes()))
+    }
+
+    // Bounces the vector off a surface, like light off a mirror
+    pub fn reflect(&self, normal: &Normal) -> Vector {
+        let normal = normal.vector();
+        *self - normal * 2.0 * self.dot(&normal)
+    }
+
+    // Bends the vector through a surface by Snell's law, where eta_ratio is the refractive index
+    // being left over the one being entered. The vector and normal both need to be unit
+    // length and face each other, and there's no direction out when all of it is reflected.
+    pub fn refract(&self, normal: &Normal, eta_ratio: Float) -> Option<Vector> {
+        let normal = normal.vector();
+        let cos_i = -self.dot(&normal);
+        let sin2_t = eta_ratio * eta_ratio * (1.0 - cos_i * cos_i);
+        if sin2_t > 1.0 {
+            return None;
+        }
+        let cos_t = (1.0 - sin2_t).sqrt();
+        Some(*self * eta_ratio + normal * (eta_ratio * cos_i - cos_t))
+    }
+
+    // t = 0 gives self and t = 1 gives rhs
+    pub fn lerp<T: Into<Float>>(&self, rhs: &Vector, t: T) -> Vector {
+        *self + (*rhs - *self) * t.into()
+    }
+
+    // In radians, from 0 for vectors pointing the same way to pi for opposite ones
+    pub fn angle_between(&self, rhs: &Vector) -> Float {
+        let cos = self.dot(rhs) / (self.magnitude() * rhs.magnitude());
+        // Rounding can push parallel vectors just past 1, where acos has no answer
+        cos.clamp(-1.0, 1.0).acos()
+    }
+
+    // The part of the vector that points along onto
+    pub fn project_onto(&self, onto: &Vector) -> Vector {
+        *onto * (self.dot(onto) / onto.dot(onto))
+    }
+
+    pub fn min(&self, rhs: &Vector) -> Vector {
+        let (Vector(x1, y1, z1), Vector(x2, y2, z2)) = (self, rhs);
+        Self::new(x1.min(*x2), y1.min(*y2), z1.min(*z2))
+    }
+
+    pub fn max(&self, rhs: &Vector) -> Vector {
+        let (Vector(x1, y1, z1), Vector(x2, y2, z2)) = (self, rhs);
+        Self::new(x1.max(*x2), y1.max(*y2), z1.max(*z2))
+    }
+
+    pub fn abs(&self) -> Vector {
+        let Vector(x, y, z) = self;
+        Self::new(x.abs(), y.abs(), z.abs())
     }
 }
 
 impl Add<Vector> for Vector {
     type Output = Vector;
 
+    #[inline]
     fn add(self, rhs: Vector) -> Vector {
-        let Vector(x1, y1, z1) = self;
-        let Vector(x2, y2, z2) = rhs;
-        Self::new(x1 + x2, y1 + y2, z1 + z2)
+        Vector::from_lanes(scalar::add(self.lanes(), rhs.lanes()))
     }
 }
 
 impl Add<Point> for Vector {
     type Output = Point;
 
+    #[inline]
     fn add(self, rhs: Point) -> Point {
-        let Vector(x1, y1, z1) = self;
-        let Point(x2, y2, z2) = rhs;
-        Point::new(x1 + x2, y1 + y2, z1 + z2)
+        Point::from_lanes(scalar::add(self.lanes(), rhs.lanes()))
     }
 }
 
 impl Add<Vector> for Point {
     type Output = Point;
 
+    #[inline]
     fn add(self, rhs: Vector) -> Point {
-        let Point(x1, y1, z1) = self;
-        let Vector(x2, y2, z2) = rhs;
-        Self::new(x1 + x2, y1 + y2, z1 + z2)
+        Point::from_lanes(scalar::add(self.lanes(), rhs.lanes()))
     }
 }
 
 impl Sub<Vector> for Vector {
     type Output = Vector;
 
+    #[inline]
     fn sub(self, rhs: Vector) -> Vector {
-        let Vector(x1, y1, z1) = self;
-        let Vector(x2, y2, z2) = rhs;
-        Self::new(x1 - x2, y1 - y2, z1 - z2)
+        Vector::from_lanes(scalar::sub(self.lanes(), rhs.lanes()))
     }
 }
 
 impl Sub<Point> for Point {
     type Output = Vector;
 
+    #[inline]
     fn sub(self, rhs: Point) -> Vector {
-        let Point(x1, y1, z1) = self;
-        let Point(x2, y2, z2) = rhs;
-        Vector::new(x1 - x2, y1 - y2, z1 - z2)
+        Vector::from_lanes(scalar::sub(self.lanes(), rhs.lanes()))
     }
 }
 
 impl Sub<Vector> for Point {
     type Output = Point;
 
+    #[inline]
     fn sub(self, rhs: Vector) -> Point {
-        let Point(x1, y1, z1) = self;
-        let Vector(x2, y2, z2) = rhs;
-        Point::new(x1 - x2, y1 - y2, z1 - z2)
+        Point::from_lanes(scalar::sub(self.lanes(), rhs.lanes()))
     }
 }
 
-impl<T: Into<f32>> Mul<T> for Point {
+impl<T: Into<Float>> Mul<T> for Point {
     type Output = Point;
 
+    #[inline]
     fn mul(self, rhs: T) -> Point {
-        let Point(x, y, z) = self;
-        let factor = rhs.into();
-        Self::new(x * factor, y * factor, z * factor)
+        Self::from_lanes(scalar::scale(self.lanes(), rhs.into()))
     }
 }
 
-impl<T: Into<f32>> Mul<T> for Vector {
+impl<T: Into<Float>> Mul<T> for Vector {
     type Output = Vector;
 
+    #[inline]
     fn mul(self, rhs: T) -> Vector {
-        let Vector(x, y, z) = self;
-        let factor = rhs.into();
-        Self::new(x * factor, y * factor, z * factor)
+        Self::from_lanes(scalar::scale(self.lanes(), rhs.into()))
     }
 }
 
-impl<T: Into<f32>> Div<T> for Point {
+impl<T: Into<Float>> Div<T> for Point {
     type Output = Point;
 
+    #[inline]
     fn div(self, rhs: T) -> Point {
-        let Point(x, y, z) = self;
-        let factor = rhs.into();
-        Self::new(x / factor, y / factor, z / factor)
+        Self::from_lanes(scalar::div(self.lanes(), rhs.into()))
     }
 }
 
-impl<T: Into<f32>> Div<T> for Vector {
+impl<T: Into<Float>> Div<T> for Vector {
     type Output = Vector;
 
+    #[inline]
     fn div(self, rhs: T) -> Vector {
-        let Vector(x, y, z) = self;
-        let factor = rhs.into();
-        Self::new(x / factor, y / factor, z / factor)
+        Self::from_lanes(scalar::div(self.lanes(), rhs.into()))
     }
 }
 
@@ -302,7 +368,7 @@ mod test_magnitude_and_normalization {
     #[test]
     fn magnitude_of_vector() {
         let vector = Vector::new(1.0, 2.0, 3.0);
-        let x: f32 = 14.0;
+        let x: Float = 14.0;
         assert_eq!(vector.magnitude(), x.sqrt());
     }
 
@@ -312,7 +378,11 @@ mod test_magnitude_and_normalization {
         assert_eq!(vector.normalize(), Vector::new(1.0, 0.0, 0.0));
 
         let vector = Vector::new(1.0, 2.0, 3.0);
-        assert_eq!(vector.normalize(), Vector::new(0.26726, 0.53452, 0.80178))
+        let length = (14.0 as Float).sqrt();
+        assert_eq!(
+            vector.normalize(),
+            Vector::new(1.0 / length, 2.0 / length, 3.0 / length)
+        )
     }
 
     #[test]
@@ -359,3 +429,69 @@ mod test_as_matrix {
         assert_eq!(tuple![-3.0, 4.0, 5.0, 0.0], v.matrix());
     }
 }
+
+#[cfg(test)]
+mod test_geometry {
+    use super::*;
+    use crate::util::consts::{FRAC_1_SQRT_2, FRAC_PI_2, FRAC_PI_4, PI};
+
+    #[test]
+    fn distance_between_points() {
+        let a = Point::new(1.0, 2.0, 3.0);
+        assert_feq!(5.0, a.distance(&Point::new(1.0, 5.0, 7.0)));
+        assert_feq!(0.0, a.distance(&a));
+    }
+
+    #[test]
+    fn reflecting() {
+        let v = Vector::new(1.0, -1.0, 0.0);
+        let n = Normal::new(0.0, 1.0, 0.0);
+        assert_eq!(Vector::new(1.0, 1.0, 0.0), v.reflect(&n));
+
+        let v = Vector::new(0.0, -1.0, 0.0);
+        let n = Normal::new(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0);
+        assert_eq!(Vector::new(1.0, 0.0, 0.0), v.reflect(&n));
+    }
+
+    #[test]
+    fn refracting() {
+        let n = Normal::new(0.0, 1.0, 0.0);
+        // Straight through doesn't bend
+        let down = Vector::new(0.0, -1.0, 0.0);
+        assert_eq!(Some(down), down.refract(&n, 1.0 / 1.5));
+
+        // Snell's law, sin of the angles in proportion to the refractive indices
+        let v = Vector::new(1.0, -1.0, 0.0).normalize();
+        let t = v.refract(&n, 1.0 / 1.5).unwrap();
+        assert_feq!(1.0, t.magnitude());
+        assert_feq!(FRAC_1_SQRT_2 / 1.5, t.0);
+
+        // Leaving glass at a shallow angle reflects everything
+        assert_eq!(None, v.refract(&n, 1.5));
+    }
+
+    #[test]
+    fn lerp_angle_and_projection() {
+        let a = Vector::new(1.0, 0.0, 0.0);
+        let b = Vector::new(0.0, 2.0, 0.0);
+        assert_eq!(Vector::new(0.5, 1.0, 0.0), a.lerp(&b, 0.5));
+        assert_eq!(b, a.lerp(&b, 1.0));
+
+        assert_feq!(FRAC_PI_2, a.angle_between(&b));
+        assert_feq!(FRAC_PI_4, a.angle_between(&Vector::new(3.0, 3.0, 0.0)));
+        assert_feq!(PI, a.angle_between(&a.negate()));
+        assert_feq!(0.0, a.angle_between(&(a * 7.0)));
+
+        let v = Vector::new(2.0, 3.0, 4.0);
+        assert_eq!(Vector::new(0.0, 3.0, 0.0), v.project_onto(&b));
+    }
+
+    #[test]
+    fn min_max_and_abs() {
+        let a = Vector::new(1.0, -5.0, 3.0);
+        let b = Vector::new(-2.0, 4.0, 3.0);
+        assert_eq!(Vector::new(-2.0, -5.0, 3.0), a.min(&b));
+        assert_eq!(Vector::new(1.0, 4.0, 3.0), a.max(&b));
+        assert_eq!(Vector::new(1.0, 5.0, 3.0), a.abs());
+    }
+}
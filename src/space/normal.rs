@@ -0,0 +1,107 @@
+use super::*;
+use crate::matrix::{Matrix4, Transform};
+
+use std::ops::Neg;
+
+// A unit vector perpendicular to a surface. Normals don't transform like other vectors, a
+// stretched sphere's normals have to tilt the other way to stay perpendicular, so they're kept
+// as their own type that only transforms by the inverse transpose.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Normal(Vector);
+
+impl Normal {
+    pub fn new<X, Y, Z>(x: X, y: Y, z: Z) -> Normal
+    where
+        X: Into<Float>,
+        Y: Into<Float>,
+        Z: Into<Float>,
+    {
+        Self::from_vector(Vector::new(x, y, z))
+    }
+
+    pub fn from_vector(vector: Vector) -> Normal {
+        Normal(vector.normalize())
+    }
+
+    pub fn vector(&self) -> Vector {
+        self.0
+    }
+
+    pub fn dot(&self, rhs: &Vector) -> Float {
+        self.0.dot(rhs)
+    }
+
+    // Flips the normal to the side the incoming direction comes from, for shading the inside of
+    // a surface as well as the outside
+    pub fn face_forward(&self, incoming: &Vector) -> Normal {
+        if self.dot(incoming) > 0.0 {
+            -*self
+        } else {
+            *self
+        }
+    }
+
+    // Moves an object space normal into world space given the inverse of the object's transform
+    pub fn transform(&self, inverse: &Matrix4) -> Normal {
+        Self::from_vector(inverse.transpose() * self.0)
+    }
+}
+
+impl Neg for Normal {
+    type Output = Normal;
+    fn neg(self) -> Normal {
+        Normal(-self.0)
+    }
+}
+
+impl From<Normal> for Vector {
+    fn from(normal: Normal) -> Vector {
+        normal.0
+    }
+}
+
+// Transform already has its inverse, so normals can go through it directly
+impl Mul<Normal> for Transform {
+    type Output = Normal;
+    fn mul(self, rhs: Normal) -> Normal {
+        rhs.transform(&self.inverse())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::consts::{FRAC_1_SQRT_2, PI};
+
+    #[test]
+    fn normals_are_unit_length() {
+        let n = Normal::new(0.0, 3.0, 4.0);
+        assert_eq!(Vector::new(0.0, 0.6, 0.8), n.vector());
+        assert_eq!(Normal::new(0.0, -3.0, -4.0), -n);
+    }
+
+    #[test]
+    fn transforming_by_inverse_transpose() {
+        // The book's normal on a translated sphere, translation doesn't move a normal
+        let t = Transform::identity().translate(0.0, 1.0, 0.0);
+        let n = Normal::new(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2);
+        assert_eq!(n, t * n);
+
+        // Squashing y tilts the normal towards y instead of away from it
+        let t = Transform::identity().scale(1.0, 0.5, 1.0);
+        let n = Normal::new(0.0, 1.0, 1.0);
+        assert_eq!(Normal::new(0.0, 2.0, 1.0), t * n);
+        assert_eq!(t * n, n.transform(&t.inverse()));
+
+        // Rotations turn normals like any other direction
+        let t = Transform::identity().rotate_z(PI / 2.0);
+        assert_eq!(Normal::new(0.0, 1.0, 0.0), t * Normal::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn facing_forward() {
+        let n = Normal::new(0.0, 1.0, 0.0);
+        assert_eq!(n, n.face_forward(&Vector::new(0.0, -1.0, 0.0)));
+        assert_eq!(-n, n.face_forward(&Vector::new(0.3, 1.0, 0.0)));
+    }
+}
@@ -0,0 +1,163 @@
+use super::*;
+
+use std::ops::{AddAssign, MulAssign, Neg, SubAssign};
+
+// Lets the owned impls in space.rs also take references on either side, the values are Copy
+// so this just dereferences and forwards
+macro_rules! forward_ref_binop {
+    ($trait:ident, $method:ident, $lhs:ty, $rhs:ty) => {
+        impl<'a> $trait<$rhs> for &'a $lhs {
+            type Output = <$lhs as $trait<$rhs>>::Output;
+            fn $method(self, rhs: $rhs) -> Self::Output {
+                $trait::$method(*self, rhs)
+            }
+        }
+
+        impl<'a> $trait<&'a $rhs> for $lhs {
+            type Output = <$lhs as $trait<$rhs>>::Output;
+            fn $method(self, rhs: &'a $rhs) -> Self::Output {
+                $trait::$method(self, *rhs)
+            }
+        }
+
+        impl<'a, 'b> $trait<&'a $rhs> for &'b $lhs {
+            type Output = <$lhs as $trait<$rhs>>::Output;
+            fn $method(self, rhs: &'a $rhs) -> Self::Output {
+                $trait::$method(*self, *rhs)
+            }
+        }
+    };
+}
+
+forward_ref_binop!(Add, add, Vector, Vector);
+forward_ref_binop!(Add, add, Vector, Point);
+forward_ref_binop!(Add, add, Point, Vector);
+forward_ref_binop!(Sub, sub, Vector, Vector);
+forward_ref_binop!(Sub, sub, Point, Point);
+forward_ref_binop!(Sub, sub, Point, Vector);
+forward_ref_binop!(Mul, mul, Vector, Vector);
+
+// Scaling a borrowed value, the scalar is always owned
+impl<T: Into<Float>> Mul<T> for &Vector {
+    type Output = Vector;
+    #[inline]
+    fn mul(self, rhs: T) -> Vector {
+        *self * rhs
+    }
+}
+
+impl<T: Into<Float>> Div<T> for &Vector {
+    type Output = Vector;
+    fn div(self, rhs: T) -> Vector {
+        *self / rhs
+    }
+}
+
+// Multiplies each component by its match in rhs, like colors do
+impl Mul<Vector> for Vector {
+    type Output = Vector;
+    #[inline]
+    fn mul(self, rhs: Vector) -> Vector {
+        Vector::from_lanes(scalar::mul(self.lanes(), rhs.lanes()))
+    }
+}
+
+impl Neg for Vector {
+    type Output = Vector;
+    fn neg(self) -> Vector {
+        self.negate()
+    }
+}
+
+impl Neg for &Vector {
+    type Output = Vector;
+    fn neg(self) -> Vector {
+        self.negate()
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+    fn neg(self) -> Point {
+        self.negate()
+    }
+}
+
+impl AddAssign<Vector> for Vector {
+    fn add_assign(&mut self, rhs: Vector) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign<Vector> for Vector {
+    fn sub_assign(&mut self, rhs: Vector) {
+        *self = *self - rhs;
+    }
+}
+
+impl<T: Into<Float>> MulAssign<T> for Vector {
+    fn mul_assign(&mut self, rhs: T) {
+        *self = *self * rhs;
+    }
+}
+
+impl AddAssign<Vector> for Point {
+    fn add_assign(&mut self, rhs: Vector) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign<Vector> for Point {
+    fn sub_assign(&mut self, rhs: Vector) {
+        *self = *self - rhs;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn operators_on_references() {
+        let (a, b) = (Vector::new(1.0, 2.0, 3.0), Vector::new(4.0, 5.0, 6.0));
+        let p = Point::new(1.0, 1.0, 1.0);
+        // Like values borrowed out of a shape or a slice
+        let (ra, rb, rp) = (&a, &b, &p);
+        assert_eq!(a + b, ra + rb);
+        assert_eq!(a - b, a - rb);
+        assert_eq!(p + a, rp + a);
+        assert_eq!(p - p, rp - rp);
+        assert_eq!(a * b, ra * b);
+        assert_eq!(a * 2.0, ra * 2.0);
+        assert_eq!(a / 2.0, ra / 2.0);
+        assert_eq!(-a, -ra);
+    }
+
+    #[test]
+    fn component_wise_multiply() {
+        let a = Vector::new(1.0, -2.0, 3.0);
+        assert_eq!(Vector::new(2.0, -1.0, 0.0), a * Vector::new(2.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn negating() {
+        assert_eq!(Vector::new(-1.0, 2.0, -3.0), -Vector::new(1.0, -2.0, 3.0));
+        assert_eq!(Point::new(-1.0, 2.0, -3.0), -Point::new(1.0, -2.0, 3.0));
+    }
+
+    #[test]
+    fn assigning() {
+        let mut v = Vector::new(1.0, 2.0, 3.0);
+        v += Vector::new(1.0, 1.0, 1.0);
+        assert_eq!(Vector::new(2.0, 3.0, 4.0), v);
+        v -= Vector::new(2.0, 0.0, 0.0);
+        assert_eq!(Vector::new(0.0, 3.0, 4.0), v);
+        v *= 2.0;
+        assert_eq!(Vector::new(0.0, 6.0, 8.0), v);
+
+        let mut p = Point::new(0.0, 0.0, 0.0);
+        p += Vector::new(1.0, 2.0, 3.0);
+        p -= Vector::new(0.5, 0.5, 0.5);
+        assert_eq!(Point::new(0.5, 1.5, 2.5), p);
+    }
+}
@@ -0,0 +1,320 @@
+mod dispersion;
+
+pub use dispersion::*;
+
+use crate::color::{Color, Xyz};
+use crate::util::{self, Float};
+
+use std::ops::{Add, Mul};
+use std::sync::OnceLock;
+
+// The visible range covered by the bins, which is also where Smits' basis spectra are defined
+pub const WAVELENGTH_MIN: Float = 380.0;
+pub const WAVELENGTH_MAX: Float = 720.0;
+pub const SPECTRUM_BINS: usize = 10;
+const BIN_WIDTH: Float = (WAVELENGTH_MAX - WAVELENGTH_MIN) / SPECTRUM_BINS as Float;
+// Steps per bin when integrating the matching functions
+const INTEGRATION_STEPS: usize = 32;
+
+// Smits' "An RGB to Spectrum Conversion for Reflectances" (1999)
+const SMITS_WHITE: [Float; SPECTRUM_BINS] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [Float; SPECTRUM_BINS] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [Float; SPECTRUM_BINS] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [Float; SPECTRUM_BINS] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [Float; SPECTRUM_BINS] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [Float; SPECTRUM_BINS] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [Float; SPECTRUM_BINS] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+// Light or reflectance sampled in equal width bins across the visible range. Multiplying two
+// spectra is what multiplying colors approximates, done per wavelength instead of per
+// primary.
+#[derive(Debug, Copy, Clone)]
+pub struct Spectrum([Float; SPECTRUM_BINS]);
+
+impl Spectrum {
+    pub fn new(bins: [Float; SPECTRUM_BINS]) -> Spectrum {
+        Spectrum(bins)
+    }
+
+    pub fn constant(value: Float) -> Spectrum {
+        Spectrum([value; SPECTRUM_BINS])
+    }
+
+    pub fn bins(&self) -> &[Float; SPECTRUM_BINS] {
+        &self.0
+    }
+
+    // The center wavelength of a bin, in nanometers
+    pub fn wavelength(bin: usize) -> Float {
+        WAVELENGTH_MIN + (bin as Float + 0.5) * BIN_WIDTH
+    }
+
+    // The value of the bin holding the wavelength, zero outside the visible range
+    pub fn evaluate(&self, wavelength: Float) -> Float {
+        if !(WAVELENGTH_MIN..WAVELENGTH_MAX).contains(&wavelength) {
+            return 0.0;
+        }
+        let bin = ((wavelength - WAVELENGTH_MIN) / BIN_WIDTH) as usize;
+        self.0[bin.min(SPECTRUM_BINS - 1)]
+    }
+
+    // Upsamples a reflectance with Smits' method, building it from the white spectrum plus
+    // the secondary and primary spectra needed to reach the color. Smooth spectra like these
+    // are what real pigments look like, and they turn back into (nearly) the same color.
+    pub fn from_color(color: Color) -> Spectrum {
+        let (r, g, b) = (color.red(), color.green(), color.blue());
+        let mut spectrum = [0.0; SPECTRUM_BINS];
+        let mut add = |amount: Float, basis: &[Float; SPECTRUM_BINS]| {
+            for (s, b) in spectrum.iter_mut().zip(basis) {
+                *s += amount * b;
+            }
+        };
+
+        if r <= g && r <= b {
+            add(r, &SMITS_WHITE);
+            if g <= b {
+                add(g - r, &SMITS_CYAN);
+                add(b - g, &SMITS_BLUE);
+            } else {
+                add(b - r, &SMITS_CYAN);
+                add(g - b, &SMITS_GREEN);
+            }
+        } else if g <= r && g <= b {
+            add(g, &SMITS_WHITE);
+            if r <= b {
+                add(r - g, &SMITS_MAGENTA);
+                add(b - r, &SMITS_BLUE);
+            } else {
+                add(b - g, &SMITS_MAGENTA);
+                add(r - b, &SMITS_RED);
+            }
+        } else {
+            add(b, &SMITS_WHITE);
+            if r <= g {
+                add(r - b, &SMITS_YELLOW);
+                add(g - r, &SMITS_GREEN);
+            } else {
+                add(g - b, &SMITS_YELLOW);
+                add(r - g, &SMITS_RED);
+            }
+        }
+        Spectrum(spectrum)
+    }
+
+    pub fn to_xyz(&self) -> Xyz {
+        let weights = bin_weights();
+        let mut xyz = Xyz {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        for (value, weight) in self.0.iter().zip(weights) {
+            xyz.x += value * weight.x;
+            xyz.y += value * weight.y;
+            xyz.z += value * weight.z;
+        }
+        xyz
+    }
+
+    // Integrates the spectrum against the CIE matching functions, the film's view of it
+    pub fn to_color(&self) -> Color {
+        xyz_to_color(self.to_xyz())
+    }
+}
+
+impl PartialEq for Spectrum {
+    fn eq(&self, other: &Spectrum) -> bool {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .all(|(a, b)| util::feq(*a, *b))
+    }
+}
+
+impl Add for Spectrum {
+    type Output = Spectrum;
+
+    fn add(self, rhs: Spectrum) -> Spectrum {
+        let mut sum = self.0;
+        for (s, r) in sum.iter_mut().zip(rhs.0.iter()) {
+            *s += r;
+        }
+        Spectrum(sum)
+    }
+}
+
+impl Mul<Float> for Spectrum {
+    type Output = Spectrum;
+
+    fn mul(self, rhs: Float) -> Spectrum {
+        Spectrum(self.0.map(|s| s * rhs))
+    }
+}
+
+impl Mul for Spectrum {
+    type Output = Spectrum;
+
+    fn mul(self, rhs: Spectrum) -> Spectrum {
+        let mut product = self.0;
+        for (p, r) in product.iter_mut().zip(rhs.0.iter()) {
+            *p *= r;
+        }
+        Spectrum(product)
+    }
+}
+
+// The CIE 1931 2° standard observer, using the multi-lobe fit from Wyman, Sloan and Shirley's
+// "Simple Analytic Approximations to the CIE XYZ Color Matching Functions" (2013)
+pub fn cie_matching(wavelength: Float) -> Xyz {
+    let g = |mean: Float, below: Float, above: Float| {
+        let sigma = if wavelength < mean { below } else { above };
+        let t = (wavelength - mean) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    Xyz {
+        x: 1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7)
+            - 0.065 * g(501.1, 20.4, 26.2),
+        y: 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        z: 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    }
+}
+
+// The matching functions integrated over each bin
+fn bin_weights() -> &'static [Xyz; SPECTRUM_BINS] {
+    static WEIGHTS: OnceLock<[Xyz; SPECTRUM_BINS]> = OnceLock::new();
+    WEIGHTS.get_or_init(|| {
+        let step = BIN_WIDTH / INTEGRATION_STEPS as Float;
+        let mut weights = [Xyz {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }; SPECTRUM_BINS];
+        for (bin, weight) in weights.iter_mut().enumerate() {
+            for i in 0..INTEGRATION_STEPS {
+                let wavelength =
+                    WAVELENGTH_MIN + bin as Float * BIN_WIDTH + (i as Float + 0.5) * step;
+                let matching = cie_matching(wavelength);
+                weight.x += matching.x * step;
+                weight.y += matching.y * step;
+                weight.z += matching.z * step;
+            }
+        }
+        weights
+    })
+}
+
+// Turns integrated XYZ into a linear color, white balanced so that a spectrum of constant 1
+// (the equal energy white the Smits spectra are built on) becomes Color::white()
+fn xyz_to_color(xyz: Xyz) -> Color {
+    static WHITE: OnceLock<Color> = OnceLock::new();
+    let white = WHITE.get_or_init(|| Color::from_xyz(Spectrum::constant(1.0).to_xyz()));
+    let c = Color::from_xyz(xyz);
+    Color::new(
+        c.red() / white.red(),
+        c.green() / white.green(),
+        c.blue() / white.blue(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_close(expected: Color, actual: Color, tolerance: Float) {
+        let error = (expected - actual).red().abs()
+            + (expected - actual).green().abs()
+            + (expected - actual).blue().abs();
+        assert!(
+            error <= tolerance,
+            "expected {:?} but got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn matching_functions() {
+        // Peaks of the fitted curves
+        assert!((cie_matching(555.0).y - 1.0).abs() < 0.02);
+        assert!((cie_matching(600.0).x - 1.06).abs() < 0.02);
+        assert!((cie_matching(445.0).z - 1.78).abs() < 0.05);
+        assert!(cie_matching(800.0).y < 0.001);
+    }
+
+    #[test]
+    fn white_round_trips() {
+        assert_close(Color::white(), Spectrum::constant(1.0).to_color(), 0.00001);
+        assert_close(
+            Color::white(),
+            Spectrum::from_color(Color::white()).to_color(),
+            0.002,
+        );
+        assert_eq!(
+            Spectrum::constant(0.0),
+            Spectrum::from_color(Color::black())
+        );
+    }
+
+    #[test]
+    fn colors_round_trip_approximately() {
+        for color in &[
+            Color::new(0.8, 0.2, 0.1),
+            Color::new(0.1, 0.6, 0.3),
+            Color::new(0.2, 0.3, 0.9),
+            Color::new(0.5, 0.5, 0.2),
+        ] {
+            let spectrum = Spectrum::from_color(*color);
+            assert!(spectrum.bins().iter().all(|s| *s >= 0.0));
+            assert_close(*color, spectrum.to_color(), 0.2);
+        }
+    }
+
+    #[test]
+    fn evaluating_bins() {
+        let s = Spectrum::from_color(Color::new(1.0, 0.0, 0.0));
+        assert_eq!(s.bins()[0], s.evaluate(WAVELENGTH_MIN));
+        assert_eq!(s.bins()[9], s.evaluate(700.0));
+        assert_eq!(0.0, s.evaluate(300.0));
+        assert_eq!(0.0, s.evaluate(WAVELENGTH_MAX));
+        assert_eq!(397.0, Spectrum::wavelength(0));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = Spectrum::constant(0.5);
+        let b = Spectrum::from_color(Color::new(0.0, 1.0, 0.0));
+        assert_eq!(Spectrum::constant(1.0), a + a);
+        assert_eq!(b * 0.5, a * b);
+    }
+
+    #[test]
+    fn metamerism() {
+        // A flat gray and a reflector of only blue and red light, equally bright under equal
+        // energy light, stop matching under a warm light that RGB would treat the same way
+        let gray = Spectrum::constant(0.5);
+        let mut peaked = [0.0; SPECTRUM_BINS];
+        peaked[2] = 1.0;
+        peaked[7] = 1.0;
+        let peaked = Spectrum::new(peaked);
+        let peaked = peaked * (gray.to_color().luminance() / peaked.to_color().luminance());
+
+        let warm = Spectrum::new([0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0, 1.0]);
+        let gray_under_warm = (gray * warm).to_color().luminance();
+        let peaked_under_warm = (peaked * warm).to_color().luminance();
+        assert!((gray_under_warm - peaked_under_warm).abs() > 0.01);
+    }
+}
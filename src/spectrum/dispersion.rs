@@ -0,0 +1,192 @@
+use super::*;
+
+// Wavelengths carried by a single camera ray
+pub const WAVELENGTH_SAMPLES: usize = 4;
+const WAVELENGTH_RANGE: Float = WAVELENGTH_MAX - WAVELENGTH_MIN;
+
+// Hero wavelength sampling (Wilkie et al. 2014): one random wavelength plus others spaced
+// evenly through the visible range, so a single path estimates several wavelengths at once.
+// When a dispersive surface sends each wavelength a different way, the path keeps only the
+// hero wavelength.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Wavelengths {
+    lambda: [Float; WAVELENGTH_SAMPLES],
+    count: usize,
+}
+
+impl Wavelengths {
+    // u is a uniform random number in [0, 1)
+    pub fn sample(u: Float) -> Wavelengths {
+        let hero = WAVELENGTH_MIN + u * WAVELENGTH_RANGE;
+        let mut lambda = [0.0; WAVELENGTH_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            let offset = i as Float * WAVELENGTH_RANGE / WAVELENGTH_SAMPLES as Float;
+            *l = WAVELENGTH_MIN + (hero - WAVELENGTH_MIN + offset) % WAVELENGTH_RANGE;
+        }
+        Wavelengths {
+            lambda,
+            count: WAVELENGTH_SAMPLES,
+        }
+    }
+
+    pub fn hero(&self) -> Float {
+        self.lambda[0]
+    }
+
+    // The wavelengths still being traced, hero first
+    pub fn lambda(&self) -> &[Float] {
+        &self.lambda[..self.count]
+    }
+
+    pub fn is_single(&self) -> bool {
+        self.count == 1
+    }
+
+    pub fn terminate_secondary(&mut self) {
+        self.count = 1;
+    }
+
+    // Looks up a binned spectrum at each wavelength being traced
+    pub fn evaluate(&self, spectrum: &Spectrum) -> [Float; WAVELENGTH_SAMPLES] {
+        let mut values = [0.0; WAVELENGTH_SAMPLES];
+        for (value, lambda) in values.iter_mut().zip(self.lambda()) {
+            *value = spectrum.evaluate(*lambda);
+        }
+        values
+    }
+
+    // The film's estimate of the color from the radiance carried at each wavelength. Each
+    // wavelength was picked uniformly, so it stands for an equal share of the visible range.
+    pub fn to_color(&self, radiance: &[Float; WAVELENGTH_SAMPLES]) -> Color {
+        let share = WAVELENGTH_RANGE / self.count as Float;
+        let mut xyz = Xyz {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        };
+        for (lambda, value) in self.lambda().iter().zip(radiance) {
+            let matching = cie_matching(*lambda);
+            xyz.x += value * matching.x * share;
+            xyz.y += value * matching.y * share;
+            xyz.z += value * matching.z * share;
+        }
+        xyz_to_color(xyz)
+    }
+}
+
+// Index of refraction varying with wavelength by Cauchy's equation, n = a + b / λ², with λ
+// in micrometers
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Dispersion {
+    pub a: Float,
+    pub b: Float,
+}
+
+impl Dispersion {
+    pub fn new(a: Float, b: Float) -> Dispersion {
+        Dispersion { a, b }
+    }
+
+    // BK7 borosilicate crown glass, the usual lens and prism glass
+    pub fn crown_glass() -> Dispersion {
+        Self::new(1.5046, 0.004_20)
+    }
+
+    // Dense flint glass, which spreads colors about twice as much as crown glass
+    pub fn flint_glass() -> Dispersion {
+        Self::new(1.6700, 0.007_43)
+    }
+
+    pub fn diamond() -> Dispersion {
+        Self::new(2.3850, 0.011_70)
+    }
+
+    pub fn water() -> Dispersion {
+        Self::new(1.3199, 0.003_10)
+    }
+
+    // Wavelength in nanometers
+    pub fn ior(&self, wavelength: Float) -> Float {
+        let micrometers = wavelength / 1000.0;
+        self.a + self.b / (micrometers * micrometers)
+    }
+
+    // Abbe number from the Fraunhofer d, F and C lines. Lower numbers mean more dispersion.
+    pub fn abbe_number(&self) -> Float {
+        (self.ior(587.6) - 1.0) / (self.ior(486.1) - self.ior(656.3))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sampling_wavelengths() {
+        let w = Wavelengths::sample(0.0);
+        assert_eq!(&[380.0, 465.0, 550.0, 635.0], w.lambda());
+
+        // The others wrap around the visible range
+        let w = Wavelengths::sample(0.9);
+        assert_feq!(686.0, w.hero());
+        assert_feq!(431.0, w.lambda()[1]);
+        assert!(w
+            .lambda()
+            .iter()
+            .all(|l| (WAVELENGTH_MIN..WAVELENGTH_MAX).contains(l)));
+    }
+
+    #[test]
+    fn terminating_secondary_wavelengths() {
+        let mut w = Wavelengths::sample(0.3);
+        assert!(!w.is_single());
+        w.terminate_secondary();
+        assert!(w.is_single());
+        assert_eq!(&[w.hero()], w.lambda());
+    }
+
+    #[test]
+    fn averaging_samples_converges_to_the_binned_color() {
+        let spectrum = Spectrum::from_color(Color::new(0.9, 0.4, 0.1));
+        let samples = 1000;
+        let mut sum = Color::black();
+        for i in 0..samples {
+            let w = Wavelengths::sample((i as Float + 0.5) / samples as Float);
+            sum = sum + w.to_color(&w.evaluate(&spectrum));
+        }
+        let estimate = sum * (1.0 / samples as Float);
+        let expected = spectrum.to_color();
+        assert!((estimate - expected).red().abs() < 0.01);
+        assert!((estimate - expected).green().abs() < 0.01);
+        assert!((estimate - expected).blue().abs() < 0.01);
+    }
+
+    #[test]
+    fn blue_light_bends_more() {
+        for glass in &[
+            Dispersion::crown_glass(),
+            Dispersion::flint_glass(),
+            Dispersion::diamond(),
+        ] {
+            assert!(glass.ior(450.0) > glass.ior(650.0));
+        }
+        assert!((Dispersion::crown_glass().ior(587.6) - 1.5168).abs() < 0.001);
+        assert!((Dispersion::diamond().ior(587.6) - 2.4189).abs() < 0.003);
+    }
+
+    #[test]
+    fn prism_spreads_colors() {
+        // Snell's law into the face of a prism at 45 degrees
+        let incidence = crate::util::consts::FRAC_PI_4.sin();
+        let refracted = |glass: Dispersion, wavelength: Float| {
+            (incidence / glass.ior(wavelength)).asin().to_degrees()
+        };
+        let crown = refracted(Dispersion::crown_glass(), 650.0)
+            - refracted(Dispersion::crown_glass(), 450.0);
+        let flint = refracted(Dispersion::flint_glass(), 650.0)
+            - refracted(Dispersion::flint_glass(), 450.0);
+        assert!(crown > 0.0);
+        assert!(flint > crown);
+        assert!(Dispersion::flint_glass().abbe_number() < Dispersion::crown_glass().abbe_number());
+    }
+}
@@ -1,5 +1,49 @@
-const THRESHOLD: f32 = 0.00001;
+// The precision of all of the math, f32 unless the f64 feature is enabled. Large scenes can
+// need the extra precision to avoid acne and gaps far from the origin.
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
 
-pub fn feq(f1: f32, f2: f32) -> bool {
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+// How far apart two values can be and still count as equal, scaled to what each precision
+// can represent
+#[cfg(not(feature = "f64"))]
+pub(crate) const THRESHOLD: Float = 0.00001;
+#[cfg(feature = "f64")]
+pub(crate) const THRESHOLD: Float = 0.000_000_01;
+
+pub fn feq(f1: Float, f2: Float) -> bool {
     (f1 - f2).abs() < THRESHOLD
 }
+
+// Widens to f64 for long sums, which lose too much in f32. A no-op when Float is already f64.
+#[allow(clippy::unnecessary_cast)]
+pub fn widen(f: Float) -> f64 {
+    f as f64
+}
+
+#[cfg(all(test, feature = "f64"))]
+mod test {
+    use super::*;
+    use crate::{Matrix4, Point};
+
+    // A millionth of a unit a million units out is below what f32 can hold, but f64 keeps it
+    // through a transform and back well within its THRESHOLD
+    #[test]
+    fn f64_keeps_precision_far_from_the_origin() {
+        let p = Point::new(1_000_000.000_001, -2_000_000.000_002, 500_000.000_003);
+        let transform = Matrix4::translation(12_345.678, 0.001, -98_765.432)
+            * Matrix4::rotation_y(0.3)
+            * Matrix4::scale(2.0, 2.0, 2.0);
+        let Point(x, y, z) = transform.inverse().unwrap() * (transform * p);
+        assert!(feq(x, 1_000_000.000_001), "{}", x);
+        assert!(feq(y, -2_000_000.000_002), "{}", y);
+        assert!(feq(z, 500_000.000_003), "{}", z);
+        assert!(!feq(x, 1_000_000.0));
+    }
+}
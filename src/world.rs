@@ -0,0 +1,142 @@
+use crate::ray::{Intersection, Intersections, Ray, Sphere};
+
+use std::convert::TryFrom;
+use std::ops::{Index, IndexMut};
+
+// Refers to a shape by its index in the World that owns it. Handles are handed out in order
+// starting from 0 and never reused, so the same scene built the same way always gets the same
+// handles, and they can be stored anywhere a u32 can, like an id channel in an image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShapeHandle(u32);
+
+impl ShapeHandle {
+    pub fn new(index: u32) -> ShapeHandle {
+        ShapeHandle(index)
+    }
+
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<ShapeHandle> for u32 {
+    fn from(handle: ShapeHandle) -> u32 {
+        handle.0
+    }
+}
+
+impl From<u32> for ShapeHandle {
+    fn from(index: u32) -> ShapeHandle {
+        ShapeHandle(index)
+    }
+}
+
+// Owns every shape in a scene
+#[derive(Debug, Clone, Default)]
+pub struct World {
+    shapes: Vec<Sphere>,
+}
+
+impl World {
+    pub fn new() -> World {
+        World { shapes: vec![] }
+    }
+
+    pub fn add(&mut self, shape: Sphere) -> ShapeHandle {
+        let index =
+            u32::try_from(self.shapes.len()).expect("a World holds at most u32::MAX shapes");
+        let handle = ShapeHandle(index);
+        self.shapes.push(shape);
+        handle
+    }
+
+    // None for a handle from some other, bigger world
+    pub fn get(&self, handle: ShapeHandle) -> Option<&Sphere> {
+        self.shapes.get(handle.0 as usize)
+    }
+
+    pub fn get_mut(&mut self, handle: ShapeHandle) -> Option<&mut Sphere> {
+        self.shapes.get_mut(handle.0 as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ShapeHandle, &Sphere)> {
+        self.shapes
+            .iter()
+            .enumerate()
+            .map(|(i, shape)| (ShapeHandle(i as u32), shape))
+    }
+
+    // Every intersection of the ray with every shape, nearest first
+    pub fn intersect(&self, ray: &Ray) -> Intersections {
+        self.iter()
+            .filter_map(|(handle, shape)| {
+                let ts = shape.intersect(ray)?;
+                let intersections = ts.iter().map(|t| Intersection::new(*t, handle));
+                Some(intersections.collect::<Intersections>())
+            })
+            .collect()
+    }
+}
+
+impl Index<ShapeHandle> for World {
+    type Output = Sphere;
+    fn index(&self, handle: ShapeHandle) -> &Sphere {
+        &self.shapes[handle.0 as usize]
+    }
+}
+
+impl IndexMut<ShapeHandle> for World {
+    fn index_mut(&mut self, handle: ShapeHandle) -> &mut Sphere {
+        &mut self.shapes[handle.0 as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::space::{Point, Vector};
+
+    #[test]
+    fn handles_are_indices() {
+        let mut world = World::new();
+        assert!(world.is_empty());
+        let a = world.add(Sphere::new());
+        let b = world.add(Sphere::new());
+        assert_eq!(ShapeHandle::new(0), a);
+        assert_eq!(1, b.index());
+        assert_ne!(a, b);
+        assert_eq!(2, world.len());
+        assert_eq!(Some(&Sphere::new()), world.get(b));
+        assert_eq!(None, world.get(ShapeHandle::new(2)));
+
+        // Stored as plain numbers and read back
+        let stored: Vec<u32> = world.iter().map(|(handle, _)| handle.into()).collect();
+        assert_eq!(vec![0, 1], stored);
+        assert_eq!(b, ShapeHandle::from(stored[1]));
+    }
+
+    #[test]
+    fn intersecting_every_shape() {
+        let mut world = World::new();
+        let a = world.add(Sphere::new());
+        let b = world.add(Sphere::new());
+        let ray = Ray::new(Point::new(0.0, 0.0, -5.0), Vector::new(0.0, 0.0, 1.0));
+        let intersections = world.intersect(&ray);
+        assert_eq!(4, intersections.len());
+        // Two shapes at the same distance are still told apart, in the order they were added
+        assert_eq!(Intersection::new(4.0, a), intersections[0]);
+        assert_eq!(Intersection::new(4.0, b), intersections[1]);
+        assert_eq!(Intersection::new(6.0, b), intersections[3]);
+        assert_eq!(a, intersections.hit().unwrap().object);
+
+        assert!(World::new().intersect(&ray).is_empty());
+    }
+}
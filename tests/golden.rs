@@ -0,0 +1,229 @@
+// Renders small reference scenes and compares them against the images in tests/golden. Run
+// with RAYTRACER_UPDATE_GOLDEN=1 to write new references after an intended change to the
+// output, and check the images in target/golden when a scene no longer matches.
+extern crate raytracer;
+
+use raytracer::{Canvas, Color, Float, Matrix4, Point, Ray, Sphere, Vector, World};
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SIZE: usize = 32;
+// References are stored quantized to 8 bits, so allow for a couple of steps of rounding
+const TOLERANCE: Float = 2.0 / 255.0;
+const WORST_PIXELS: usize = 5;
+const UPDATE_VARIABLE: &str = "RAYTRACER_UPDATE_GOLDEN";
+
+#[derive(Clone, Copy)]
+enum Format {
+    Ppm,
+    Png,
+}
+
+struct Scene {
+    name: &'static str,
+    format: Format,
+    render: fn() -> Canvas,
+}
+
+const SCENES: &[Scene] = &[
+    Scene {
+        name: "silhouette",
+        format: Format::Ppm,
+        render: silhouette,
+    },
+    Scene {
+        name: "depth",
+        format: Format::Ppm,
+        render: depth,
+    },
+    Scene {
+        name: "normals",
+        format: Format::Ppm,
+        render: normals,
+    },
+    Scene {
+        name: "transformed",
+        format: Format::Png,
+        render: transformed,
+    },
+];
+
+// Casts a ray from the camera through every pixel of a wall behind the unit sphere, shading
+// each pixel from the closest hit in front of the camera
+fn render<F>(transform: Matrix4, shade: F) -> Canvas
+where
+    F: Fn(&Ray, Float) -> Color,
+{
+    let inverse = transform.inverse().unwrap();
+    let origin = Point::new(0.0, 0.0, -5.0);
+    let (wall_z, wall_size) = (10.0, 7.0);
+    let pixel_size = wall_size / SIZE as Float;
+
+    let mut world = World::new();
+    world.add(Sphere::new());
+    let mut canvas = Canvas::new(SIZE, SIZE);
+    for (x, y) in canvas.iter() {
+        let target = Point::new(
+            -wall_size / 2.0 + pixel_size * (x as Float + 0.5),
+            wall_size / 2.0 - pixel_size * (y as Float + 0.5),
+            wall_z,
+        );
+        let ray = Ray::new(origin, (target - origin).normalize());
+        // Intersect in object space, where the sphere is a unit sphere at the origin
+        let local = Ray::new(inverse * ray.origin, inverse * ray.direction);
+        if let Some(hit) = world.intersect(&local).hit().map(|h| h.t) {
+            canvas[(x, y)] = shade(&local, hit);
+        }
+    }
+    canvas
+}
+
+// The normal of the unit sphere at a point in object space
+fn normal(ray: &Ray, t: Float) -> Vector {
+    (ray.position(t) - Point::new(0.0, 0.0, 0.0)).normalize()
+}
+
+fn silhouette() -> Canvas {
+    render(Matrix4::identity(), |_, _| Color::new(1.0, 0.0, 0.0))
+}
+
+fn depth() -> Canvas {
+    // The front of the sphere is between 4 and 5 units from the camera
+    render(Matrix4::identity(), |_, t| {
+        let near = 1.0 - (t - 4.0);
+        Color::new(near, near, near)
+    })
+}
+
+fn normals() -> Canvas {
+    render(Matrix4::identity(), |ray, t| {
+        let Vector(x, y, z) = normal(ray, t);
+        Color::new((x + 1.0) / 2.0, (y + 1.0) / 2.0, (-z + 1.0) / 2.0)
+    })
+}
+
+fn transformed() -> Canvas {
+    let transform =
+        Matrix4::rotation_z(raytracer::consts::PI / 4.0) * Matrix4::scale(1.0, 0.5, 1.0);
+    let light = Vector::new(-1.0, 1.0, -1.0).normalize();
+    render(transform, move |ray, t| {
+        let diffuse = normal(ray, t).dot(&light).max(0.0);
+        Color::new(1.0, 0.2, 1.0) * (0.1 + 0.9 * diffuse)
+    })
+}
+
+fn reference_path(scene: &Scene) -> PathBuf {
+    let extension = match scene.format {
+        Format::Ppm => "ppm",
+        Format::Png => "png",
+    };
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.{}", scene.name, extension))
+}
+
+fn failure_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target/golden")
+        .join(name)
+}
+
+fn save(canvas: &Canvas, path: &Path, format: Format) {
+    let result = match format {
+        Format::Ppm => canvas.save_as_ppm(path),
+        Format::Png => canvas.save_as_png(path),
+    };
+    result.unwrap_or_else(|e| panic!("couldn't write {}: {}", path.display(), e));
+}
+
+// Round trips the render through the file format so it's compared at the stored precision
+fn quantize(canvas: &Canvas, format: Format) -> Canvas {
+    let mut bytes = Vec::new();
+    match format {
+        Format::Ppm => {
+            canvas.write_ppm(&mut bytes).unwrap();
+            Canvas::read_ppm(&mut &bytes[..]).unwrap()
+        }
+        Format::Png => {
+            canvas.write_png(&mut bytes).unwrap();
+            Canvas::read_png(&mut &bytes[..]).unwrap()
+        }
+    }
+}
+
+fn check(scene: &Scene, update: bool) -> Result<(), String> {
+    let rendered = (scene.render)();
+    let path = reference_path(scene);
+    if update {
+        save(&rendered, &path, scene.format);
+        return Ok(());
+    }
+    let actual = quantize(&rendered, scene.format);
+
+    let expected = match scene.format {
+        Format::Ppm => Canvas::load_ppm(&path),
+        Format::Png => Canvas::load_png(&path),
+    }
+    .map_err(|e| {
+        format!(
+            "{}: couldn't read {} ({}), run with {}=1 to create it",
+            scene.name,
+            path.display(),
+            e,
+            UPDATE_VARIABLE
+        )
+    })?;
+
+    let diff = actual
+        .diff(&expected)
+        .map_err(|e| format!("{}: {}", scene.name, e))?;
+    if diff.within(TOLERANCE) {
+        return Ok(());
+    }
+
+    fs::create_dir_all(failure_path("")).unwrap();
+    let actual_path = failure_path(&format!("{}.actual.png", scene.name));
+    let diff_path = failure_path(&format!("{}.diff.png", scene.name));
+    save(&actual, &actual_path, Format::Png);
+    save(&diff.visualize(TOLERANCE), &diff_path, Format::Png);
+
+    let mut report = format!(
+        "{}: max error {:.4}, rmse {:.4}, psnr {:.1}dB, ssim {:.4}\n  worst pixels:",
+        scene.name, diff.max_error, diff.rmse, diff.psnr, diff.ssim
+    );
+    for ((x, y), error) in diff.worst_pixels(WORST_PIXELS) {
+        report += &format!(
+            "\n    ({}, {}) off by {:.4}: expected {:?}, got {:?}",
+            x,
+            y,
+            error,
+            expected[(x, y)],
+            actual[(x, y)]
+        );
+    }
+    report += &format!(
+        "\n  wrote {} and {}",
+        actual_path.display(),
+        diff_path.display()
+    );
+    Err(report)
+}
+
+#[test]
+fn golden_images() {
+    let update = env::var(UPDATE_VARIABLE).is_ok_and(|v| v == "1");
+    let failures: Vec<String> = SCENES
+        .iter()
+        .filter_map(|scene| check(scene, update).err())
+        .collect();
+    if !failures.is_empty() {
+        panic!(
+            "{} of {} golden images differ\n{}",
+            failures.len(),
+            SCENES.len(),
+            failures.join("\n")
+        );
+    }
+}